@@ -22,3 +22,15 @@ pub type UnixTimestamp = sys::DiscordTimestamp;
 
 /// ID of a User
 pub type UserID = sys::DiscordUserId;
+
+/// ID of a call made with [`Rpc`](struct.Rpc.html), unique per `Rpc`
+pub type RpcCallID = u64;
+
+/// ID of a stream opened with [`NetworkStreams`](struct.NetworkStreams.html), unique per sender
+pub type StreamID = u32;
+
+/// Tick of a [`Lockstep`](struct.Lockstep.html) session, starting from 0
+pub type LockstepTick = u64;
+
+/// Sequence number of a snapshot taken by a [`SnapshotHost`](struct.SnapshotHost.html)
+pub type SnapshotSequence = u32;
@@ -0,0 +1,18 @@
+use crate::{Codec, CodecError};
+use serde_crate::{de::DeserializeOwned, Serialize};
+
+/// [`Codec`](trait.Codec.html) for types implementing `serde` traits, using [`bincode`](https://docs.rs/bincode)
+///
+/// Requires the `bincode` feature.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct BincodeCodec;
+
+impl<T: Serialize + DeserializeOwned> Codec<T> for BincodeCodec {
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        bincode_crate::serialize(value).map_err(CodecError::new)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T, CodecError> {
+        bincode_crate::deserialize(data).map_err(CodecError::new)
+    }
+}
@@ -0,0 +1,15 @@
+use crate::{Codec, CodecError};
+
+/// [`Codec`](trait.Codec.html) passing bytes through unchanged
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct BytesCodec;
+
+impl Codec<Vec<u8>> for BytesCodec {
+    fn encode(&self, value: &Vec<u8>) -> Result<Vec<u8>, CodecError> {
+        Ok(value.clone())
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<Vec<u8>, CodecError> {
+        Ok(data.to_vec())
+    }
+}
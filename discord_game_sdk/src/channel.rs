@@ -0,0 +1,212 @@
+use crate::{
+    ChannelError, Codec, CodecError, Discord, LobbyID, NetworkChannelID, NetworkPeerID,
+    Reliability, Result, UserID,
+};
+use std::marker::PhantomData;
+
+/// Network channel carrying values of type `T`
+///
+/// Values are converted to bytes by a [`Codec`](trait.Codec.html) when sent,
+/// and decoded when messages are received on the channel's ID.
+/// Messages received on other channels are ignored, so several channels can be
+/// chained from the same event handler.
+///
+/// Decoding errors are returned alongside the peer or member that sent the message,
+/// they do not affect other peers.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// struct MyEventHandler {
+///     inputs: Channel<Vec<u8>, BytesCodec>,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_network_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         peer_id: NetworkPeerID,
+///         channel_id: NetworkChannelID,
+///         data: &[u8],
+///     ) {
+///         match self.inputs.on_network_message(peer_id, channel_id, data) {
+///             Some(Ok(input)) => println!("received {:?} from {}", input, peer_id),
+///             Some(Err(error)) => eprintln!("dropped message from {}: {}", peer_id, error),
+///             None => {}
+///         }
+///     }
+/// }
+///
+/// # fn example(discord: Discord<'_, ()>, peer_id: NetworkPeerID) -> std::result::Result<(), ChannelError> {
+/// let inputs = Channel::new(0, BytesCodec);
+///
+/// inputs.open(&discord, peer_id, Reliability::Unreliable)?;
+/// inputs.send(&discord, peer_id, &vec![1, 2, 3])?;
+/// # Ok(()) }
+/// ```
+pub struct Channel<T, C> {
+    channel_id: NetworkChannelID,
+    codec: C,
+    value: PhantomData<fn() -> T>,
+}
+
+impl<T, C: Codec<T>> Channel<T, C> {
+    /// Creates a channel using the given channel ID and codec.
+    pub fn new(channel_id: NetworkChannelID, codec: C) -> Self {
+        Self {
+            channel_id,
+            codec,
+            value: PhantomData,
+        }
+    }
+
+    /// The ID of the channel
+    pub fn channel_id(&self) -> NetworkChannelID {
+        self.channel_id
+    }
+
+    /// The codec of the channel
+    pub fn codec(&self) -> &C {
+        &self.codec
+    }
+
+    /// Opens the channel to a given peer ID.
+    ///
+    /// See [`open_channel`](struct.Discord.html#method.open_channel).
+    pub fn open<E>(
+        &self,
+        discord: &Discord<'_, E>,
+        peer_id: NetworkPeerID,
+        reliability: Reliability,
+    ) -> Result<()> {
+        discord.open_channel(peer_id, self.channel_id, reliability)
+    }
+
+    /// Opens the channel to all members of a lobby.
+    ///
+    /// See [`open_lobby_network_channel`](struct.Discord.html#method.open_lobby_network_channel).
+    pub fn open_lobby<E>(
+        &self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        reliability: Reliability,
+    ) -> Result<()> {
+        discord.open_lobby_network_channel(lobby_id, self.channel_id, reliability)
+    }
+
+    /// Encodes a value and sends it to a given peer ID.
+    pub fn send<E>(
+        &self,
+        discord: &Discord<'_, E>,
+        peer_id: NetworkPeerID,
+        value: &T,
+    ) -> std::result::Result<(), ChannelError> {
+        let data = self.codec.encode(value)?;
+
+        Ok(discord.send_message(peer_id, self.channel_id, data)?)
+    }
+
+    /// Encodes a value and sends it to a lobby member.
+    pub fn send_lobby<E>(
+        &self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        user_id: UserID,
+        value: &T,
+    ) -> std::result::Result<(), ChannelError> {
+        let data = self.codec.encode(value)?;
+
+        Ok(discord.send_lobby_network_message(lobby_id, user_id, self.channel_id, &data)?)
+    }
+
+    /// Decodes a message, returning `None` if it was received on another channel.
+    ///
+    /// Forward [`EventHandler::on_network_message`](trait.EventHandler.html#method.on_network_message) here.
+    pub fn on_network_message(
+        &self,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> Option<std::result::Result<T, CodecError>> {
+        if channel_id != self.channel_id {
+            return None;
+        }
+
+        let result = self.codec.decode(data);
+
+        if let Err(error) = &result {
+            log::debug!(
+                "failed to decode message from peer {} on channel {}: {}",
+                peer_id,
+                channel_id,
+                error
+            );
+        }
+
+        Some(result)
+    }
+
+    /// Decodes a message, returning `None` if it was received on another channel.
+    ///
+    /// Forward [`EventHandler::on_lobby_network_message`](trait.EventHandler.html#method.on_lobby_network_message) here.
+    pub fn on_lobby_network_message(
+        &self,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> Option<std::result::Result<T, CodecError>> {
+        if channel_id != self.channel_id {
+            return None;
+        }
+
+        let result = self.codec.decode(data);
+
+        if let Err(error) = &result {
+            log::debug!(
+                "failed to decode message from member {} of lobby {} on channel {}: {}",
+                member_id,
+                lobby_id,
+                channel_id,
+                error
+            );
+        }
+
+        Some(result)
+    }
+}
+
+impl<T, C: Clone> Clone for Channel<T, C> {
+    fn clone(&self) -> Self {
+        Self {
+            channel_id: self.channel_id,
+            codec: self.codec.clone(),
+            value: PhantomData,
+        }
+    }
+}
+
+impl<T, C: std::fmt::Debug> std::fmt::Debug for Channel<T, C> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("Channel")
+            .field("channel_id", &self.channel_id)
+            .field("codec", &self.codec)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BytesCodec;
+
+    #[test]
+    fn test_routing() {
+        let channel = Channel::new(2, BytesCodec);
+
+        assert!(channel.on_network_message(1, 1, b"abc").is_none());
+        assert_eq!(
+            channel.on_network_message(1, 2, b"abc").unwrap().unwrap(),
+            b"abc".to_vec()
+        );
+    }
+}
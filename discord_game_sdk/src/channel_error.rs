@@ -0,0 +1,42 @@
+use crate::{CodecError, Error};
+use std::fmt;
+
+/// Error while sending a value over a [`Channel`](struct.Channel.html)
+#[derive(Debug)]
+pub enum ChannelError {
+    /// The value could not be encoded
+    Codec(CodecError),
+
+    /// The message could not be sent by the SDK
+    Discord(Error),
+}
+
+impl From<CodecError> for ChannelError {
+    fn from(error: CodecError) -> Self {
+        Self::Codec(error)
+    }
+}
+
+impl From<Error> for ChannelError {
+    fn from(error: Error) -> Self {
+        Self::Discord(error)
+    }
+}
+
+impl fmt::Display for ChannelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Codec(error) => write!(f, "{}", error),
+            Self::Discord(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ChannelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Codec(error) => Some(error),
+            Self::Discord(error) => Some(error),
+        }
+    }
+}
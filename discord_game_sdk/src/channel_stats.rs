@@ -0,0 +1,153 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// Traffic counters of a network channel, or of every channel to a peer
+///
+/// See [`NetworkStats`](struct.NetworkStats.html).
+#[derive(Clone, Debug, Default)]
+pub struct ChannelStats {
+    pub(crate) packets_sent: u64,
+    pub(crate) bytes_sent: u64,
+    pub(crate) send_failures: u64,
+    pub(crate) packets_received: u64,
+    pub(crate) bytes_received: u64,
+    pub(crate) last_sent: Option<Instant>,
+    pub(crate) last_received: Option<Instant>,
+    pub(crate) window: Duration,
+    pub(crate) sent_samples: VecDeque<(Instant, usize)>,
+    pub(crate) received_samples: VecDeque<(Instant, usize)>,
+}
+
+impl ChannelStats {
+    pub(crate) fn new(window: Duration) -> Self {
+        Self {
+            window,
+            ..Self::default()
+        }
+    }
+
+    /// The number of messages sent successfully
+    pub fn packets_sent(&self) -> u64 {
+        self.packets_sent
+    }
+
+    /// The number of bytes sent successfully
+    pub fn bytes_sent(&self) -> u64 {
+        self.bytes_sent
+    }
+
+    /// The number of messages that could not be sent
+    pub fn send_failures(&self) -> u64 {
+        self.send_failures
+    }
+
+    /// The number of messages received
+    pub fn packets_received(&self) -> u64 {
+        self.packets_received
+    }
+
+    /// The number of bytes received
+    pub fn bytes_received(&self) -> u64 {
+        self.bytes_received
+    }
+
+    /// When the last message was sent successfully
+    pub fn last_sent(&self) -> Option<Instant> {
+        self.last_sent
+    }
+
+    /// When the last message was received
+    pub fn last_received(&self) -> Option<Instant> {
+        self.last_received
+    }
+
+    /// The average size of the messages sent, in bytes
+    pub fn average_sent_size(&self) -> Option<f64> {
+        average(self.bytes_sent, self.packets_sent)
+    }
+
+    /// The average size of the messages received, in bytes
+    pub fn average_received_size(&self) -> Option<f64> {
+        average(self.bytes_received, self.packets_received)
+    }
+
+    /// The bytes sent per second over the sliding window
+    pub fn sent_throughput(&self) -> f64 {
+        self.throughput(&self.sent_samples, Instant::now())
+    }
+
+    /// The bytes received per second over the sliding window
+    pub fn received_throughput(&self) -> f64 {
+        self.throughput(&self.received_samples, Instant::now())
+    }
+
+    pub(crate) fn record_sent(&mut self, now: Instant, len: usize) {
+        self.packets_sent += 1;
+        self.bytes_sent += len as u64;
+        self.last_sent = Some(now);
+
+        self.sent_samples.push_back((now, len));
+        prune(&mut self.sent_samples, self.window, now);
+    }
+
+    pub(crate) fn record_send_failure(&mut self) {
+        self.send_failures += 1;
+    }
+
+    pub(crate) fn record_received(&mut self, now: Instant, len: usize) {
+        self.packets_received += 1;
+        self.bytes_received += len as u64;
+        self.last_received = Some(now);
+
+        self.received_samples.push_back((now, len));
+        prune(&mut self.received_samples, self.window, now);
+    }
+
+    pub(crate) fn merge(&mut self, other: &Self) {
+        self.packets_sent += other.packets_sent;
+        self.bytes_sent += other.bytes_sent;
+        self.send_failures += other.send_failures;
+        self.packets_received += other.packets_received;
+        self.bytes_received += other.bytes_received;
+        self.last_sent = self.last_sent.max(other.last_sent);
+        self.last_received = self.last_received.max(other.last_received);
+        self.window = self.window.max(other.window);
+        self.sent_samples.extend(other.sent_samples.iter().copied());
+        self.received_samples
+            .extend(other.received_samples.iter().copied());
+    }
+
+    pub(crate) fn throughput(&self, samples: &VecDeque<(Instant, usize)>, now: Instant) -> f64 {
+        if self.window == Duration::from_secs(0) {
+            return 0.0;
+        }
+
+        let bytes = samples
+            .iter()
+            .filter(|(at, _)| now.saturating_duration_since(*at) < self.window)
+            .map(|(_, len)| *len as f64)
+            .sum::<f64>();
+
+        bytes / self.window.as_secs_f64()
+    }
+}
+
+fn average(bytes: u64, packets: u64) -> Option<f64> {
+    if packets == 0 {
+        None
+    } else {
+        Some(bytes as f64 / packets as f64)
+    }
+}
+
+fn prune(samples: &mut VecDeque<(Instant, usize)>, window: Duration, now: Instant) {
+    while let Some((at, _)) = samples.front() {
+        if now.saturating_duration_since(*at) < window {
+            break;
+        }
+
+        let _ = samples.pop_front();
+    }
+}
@@ -0,0 +1,62 @@
+use crate::Error;
+use std::fmt;
+
+/// Error while sending or receiving a [`ChatMessage`](struct.ChatMessage.html)
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum ChatError {
+    /// The message was sent with an envelope version this crate does not understand,
+    /// or is not a chat message at all
+    UnsupportedVersion(u8),
+
+    /// The message kind is unknown
+    UnknownKind(u8),
+
+    /// The message is shorter than its envelope
+    Truncated,
+
+    /// The message text is not valid UTF-8
+    InvalidText,
+
+    /// The timestamp of the message, in milliseconds since the Unix epoch,
+    /// cannot be represented on this platform
+    InvalidTimestamp(u64),
+
+    /// Too many messages were sent recently, the message was not sent
+    RateLimited,
+
+    /// The message could not be sent by the SDK
+    Discord(Error),
+}
+
+impl From<Error> for ChatError {
+    fn from(error: Error) -> Self {
+        Self::Discord(error)
+    }
+}
+
+impl fmt::Display for ChatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported chat envelope version {}", version)
+            }
+            Self::UnknownKind(kind) => write!(f, "unknown chat message kind {}", kind),
+            Self::Truncated => write!(f, "truncated chat message"),
+            Self::InvalidText => write!(f, "chat message text is not valid UTF-8"),
+            Self::InvalidTimestamp(millis) => {
+                write!(f, "invalid chat message timestamp {}", millis)
+            }
+            Self::RateLimited => write!(f, "chat rate limit exceeded"),
+            Self::Discord(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ChatError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Discord(error) => Some(error),
+            _ => None,
+        }
+    }
+}
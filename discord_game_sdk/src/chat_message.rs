@@ -0,0 +1,154 @@
+use crate::{ChatError, ChatMessageKind, UserID};
+use std::{
+    convert::TryInto,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 18;
+
+/// Message of a [`LobbyChat`](struct.LobbyChat.html)
+///
+/// Sent over lobby messages as:
+///
+/// | Bytes  | Content                                      |
+/// |--------|----------------------------------------------|
+/// | 1      | Envelope version, currently `1`              |
+/// | 1      | Kind: `0` text, `1` system, `2` emote         |
+/// | 8      | Sequence number, little-endian               |
+/// | 8      | Milliseconds since the Unix epoch, little-endian |
+/// | rest   | UTF-8 text                                   |
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ChatMessage {
+    pub(crate) sender_id: UserID,
+    pub(crate) kind: ChatMessageKind,
+    pub(crate) sequence: u64,
+    pub(crate) timestamp: SystemTime,
+    pub(crate) text: String,
+}
+
+impl ChatMessage {
+    /// The member who sent the message
+    pub fn sender_id(&self) -> UserID {
+        self.sender_id
+    }
+
+    /// What sort of message it is
+    pub fn kind(&self) -> ChatMessageKind {
+        self.kind
+    }
+
+    /// Sequence number assigned by the sender, increasing for every message they send in the lobby
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// When the message was sent, according to the clock of the sender
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+
+    /// The text of the message
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let millis = self
+            .timestamp
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or(0);
+
+        let mut buffer = Vec::with_capacity(HEADER_LEN + self.text.len());
+        buffer.push(VERSION);
+        buffer.push(self.kind.to_byte());
+        buffer.extend_from_slice(&self.sequence.to_le_bytes());
+        buffer.extend_from_slice(&millis.to_le_bytes());
+        buffer.extend_from_slice(self.text.as_bytes());
+        buffer
+    }
+
+    pub(crate) fn decode(sender_id: UserID, data: &[u8]) -> Result<Self, ChatError> {
+        match data.first() {
+            Some(&VERSION) => {}
+            Some(&version) => return Err(ChatError::UnsupportedVersion(version)),
+            None => return Err(ChatError::Truncated),
+        }
+
+        if data.len() < HEADER_LEN {
+            return Err(ChatError::Truncated);
+        }
+
+        let kind = ChatMessageKind::from_byte(data[1]).ok_or(ChatError::UnknownKind(data[1]))?;
+        let sequence = u64::from_le_bytes(data[2..10].try_into().unwrap());
+        let millis = u64::from_le_bytes(data[10..18].try_into().unwrap());
+
+        let timestamp = UNIX_EPOCH
+            .checked_add(Duration::from_millis(millis))
+            .ok_or(ChatError::InvalidTimestamp(millis))?;
+
+        let text = std::str::from_utf8(&data[HEADER_LEN..])
+            .map_err(|_| ChatError::InvalidText)?
+            .to_string();
+
+        Ok(Self {
+            sender_id,
+            kind,
+            sequence,
+            timestamp,
+            text,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_envelope() {
+        let message = ChatMessage {
+            sender_id: 42,
+            kind: ChatMessageKind::Emote,
+            sequence: 7,
+            timestamp: UNIX_EPOCH + Duration::from_millis(1_600_000_000_000),
+            text: "waves".to_string(),
+        };
+
+        let data = message.encode();
+        assert_eq!(ChatMessage::decode(42, &data), Ok(message));
+
+        assert_eq!(ChatMessage::decode(42, &[]), Err(ChatError::Truncated));
+        assert_eq!(
+            ChatMessage::decode(42, &data[..10]),
+            Err(ChatError::Truncated)
+        );
+        assert_eq!(
+            ChatMessage::decode(42, b"hello"),
+            Err(ChatError::UnsupportedVersion(b'h'))
+        );
+
+        let mut invalid = data.clone();
+        invalid[1] = 9;
+        assert_eq!(
+            ChatMessage::decode(42, &invalid),
+            Err(ChatError::UnknownKind(9))
+        );
+
+        // Out of range on some platforms, never a panic
+        let mut invalid = data.clone();
+        invalid[10..18].copy_from_slice(&u64::MAX.to_le_bytes());
+        match ChatMessage::decode(42, &invalid) {
+            Ok(_) | Err(ChatError::InvalidTimestamp(u64::MAX)) => {}
+            Err(error) => panic!("{}", error),
+        }
+
+        let mut invalid = data;
+        invalid.push(0xff);
+        assert_eq!(
+            ChatMessage::decode(42, &invalid),
+            Err(ChatError::InvalidText)
+        );
+    }
+}
@@ -0,0 +1,29 @@
+/// Kind of a [`ChatMessage`](struct.ChatMessage.html)
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ChatMessageKind {
+    /// Text typed by a member
+    Text,
+    /// Notice generated by the game, such as a member joining
+    System,
+    /// Action performed by a member, usually displayed as `* name waves`
+    Emote,
+}
+
+impl ChatMessageKind {
+    pub(crate) fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(Self::Text),
+            1 => Some(Self::System),
+            2 => Some(Self::Emote),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_byte(self) -> u8 {
+        match self {
+            Self::Text => 0,
+            Self::System => 1,
+            Self::Emote => 2,
+        }
+    }
+}
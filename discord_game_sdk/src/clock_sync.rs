@@ -0,0 +1,392 @@
+use crate::{Discord, LobbyID, NetworkChannelID, Result, UserID};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::TryInto,
+    time::{Duration, Instant},
+};
+
+const PING: u8 = 0;
+const PONG: u8 = 1;
+
+const PING_LEN: usize = 9;
+const PONG_LEN: usize = 17;
+
+/// Clock synchronization between lobby members
+///
+/// Every clock counts the microseconds elapsed since its `ClockSync` was created.
+/// Members are pinged at a regular interval over a lobby network channel, and answer
+/// with their clock. Each answer gives a sample of the round-trip time and of the offset
+/// between the clocks, assuming the ping and the pong took as long.
+///
+/// Since a delayed packet skews its sample, only the samples with the shortest round-trip
+/// times are used to estimate the offset, by taking their median.
+///
+/// The lobby time is the clock of the lobby owner, which every member can estimate and
+/// use for synchronized countdowns or lag compensation.
+///
+/// The channel should be opened with [`Reliability::Unreliable`](enum.Reliability.html#variant.Unreliable).
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # use std::time::Duration;
+/// struct MyEventHandler {
+///     clocks: ClockSync,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_lobby_network_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///         channel_id: NetworkChannelID,
+///         data: &[u8],
+///     ) {
+///         self.clocks
+///             .on_lobby_network_message(discord, lobby_id, member_id, channel_id, data);
+///     }
+///
+///     fn on_member_connect(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///     ) {
+///         self.clocks.on_member_connect(lobby_id, member_id);
+///     }
+///
+///     fn on_member_disconnect(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///     ) {
+///         self.clocks.on_member_disconnect(lobby_id, member_id);
+///     }
+///
+///     // ...
+/// }
+///
+/// # fn example(mut discord: Discord<'_, MyEventHandler>, clocks: &mut ClockSync, lobby_id: LobbyID) -> Result<()> {
+/// // After connecting to the lobby
+/// clocks.join(&discord, lobby_id)?;
+///
+/// // Every frame
+/// discord.run_callbacks()?;
+/// clocks.poll(&discord)?;
+///
+/// if let Some(now) = clocks.lobby_time(&discord, lobby_id)? {
+///     println!("lobby time is {:?}", now);
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ClockSync {
+    channel_id: NetworkChannelID,
+    interval: Duration,
+    samples: usize,
+    epoch: Instant,
+    members: HashMap<(LobbyID, UserID), Member>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Member {
+    last_ping: Option<Instant>,
+    samples: VecDeque<Sample>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+struct Sample {
+    round_trip: u64,
+    offset: i64,
+}
+
+impl ClockSync {
+    /// Creates a clock pinging members every second over the given channel ID,
+    /// and keeping their last 8 samples.
+    pub fn new(channel_id: NetworkChannelID) -> Self {
+        Self {
+            channel_id,
+            interval: Duration::from_secs(1),
+            samples: 8,
+            epoch: Instant::now(),
+            members: HashMap::new(),
+        }
+    }
+
+    /// Sets the time between two pings to a member.
+    pub fn interval(&mut self, interval: Duration) -> &mut Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Sets the number of samples kept per member, at least 1.
+    pub fn samples(&mut self, samples: usize) -> &mut Self {
+        self.samples = samples.max(1);
+        self
+    }
+
+    /// The local clock
+    pub fn local_time(&self) -> Duration {
+        self.epoch.elapsed()
+    }
+
+    /// The estimated clock of a lobby member, once an answer to a ping was received.
+    pub fn member_time(&self, lobby_id: LobbyID, member_id: UserID) -> Option<Duration> {
+        let local = micros(self.local_time()) as i64;
+
+        self.clock_offset(lobby_id, member_id)
+            .map(|offset| Duration::from_micros(local.saturating_add(offset).max(0) as u64))
+    }
+
+    /// The estimated clock of the lobby owner, once an answer to a ping was received.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if the lobby or the current user is not available.
+    pub fn lobby_time<E>(
+        &self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+    ) -> Result<Option<Duration>> {
+        let owner_id = discord.lobby(lobby_id)?.owner_id();
+
+        if owner_id == discord.current_user()?.id() {
+            return Ok(Some(self.local_time()));
+        }
+
+        Ok(self.member_time(lobby_id, owner_id))
+    }
+
+    /// The estimated offset in microseconds between the clock of a lobby member and the local clock,
+    /// positive if theirs is ahead.
+    pub fn clock_offset(&self, lobby_id: LobbyID, member_id: UserID) -> Option<i64> {
+        self.members
+            .get(&(lobby_id, member_id))
+            .and_then(|member| estimate(&member.samples))
+            .map(|(offset, _)| offset)
+    }
+
+    /// The median round-trip time to a lobby member.
+    pub fn round_trip_time(&self, lobby_id: LobbyID, member_id: UserID) -> Option<Duration> {
+        self.members
+            .get(&(lobby_id, member_id))
+            .and_then(|member| estimate(&member.samples))
+            .map(|(_, round_trip)| Duration::from_micros(round_trip))
+    }
+
+    /// Starts pinging the members of a lobby.
+    ///
+    /// Must be called once after [`create_lobby`](struct.Discord.html#method.create_lobby) or
+    /// [`connect_lobby`](struct.Discord.html#method.connect_lobby) succeed.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if the lobby or the current user is not available.
+    pub fn join<E>(&mut self, discord: &Discord<'_, E>, lobby_id: LobbyID) -> Result<()> {
+        let user_id = discord.current_user()?.id();
+
+        for member_id in discord.iter_lobby_member_ids(lobby_id)? {
+            let member_id = member_id?;
+
+            if member_id != user_id {
+                self.on_member_connect(lobby_id, member_id);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stops pinging the members of a lobby, and forgets their samples.
+    ///
+    /// Call this after [`disconnect_lobby`](struct.Discord.html#method.disconnect_lobby) succeeds.
+    pub fn leave(&mut self, lobby_id: LobbyID) {
+        self.members.retain(|(lobby, _), _| *lobby != lobby_id);
+    }
+
+    /// Starts pinging a new lobby member.
+    ///
+    /// Forward [`EventHandler::on_member_connect`](trait.EventHandler.html#method.on_member_connect) here.
+    pub fn on_member_connect(&mut self, lobby_id: LobbyID, member_id: UserID) {
+        let _ = self.members.entry((lobby_id, member_id)).or_default();
+    }
+
+    /// Stops pinging a lobby member, and forgets their samples.
+    ///
+    /// Forward [`EventHandler::on_member_disconnect`](trait.EventHandler.html#method.on_member_disconnect) here.
+    pub fn on_member_disconnect(&mut self, lobby_id: LobbyID, member_id: UserID) {
+        let _ = self.members.remove(&(lobby_id, member_id));
+    }
+
+    /// Pings the members that were not pinged for an interval.
+    ///
+    /// Call this every frame after [`run_callbacks`](struct.Discord.html#method.run_callbacks).
+    ///
+    /// ## Errors
+    ///
+    /// Every due member is pinged even if sending fails, the first error is returned.
+    pub fn poll<E>(&mut self, discord: &Discord<'_, E>) -> Result<()> {
+        let now = Instant::now();
+        let ping = packet(PING, &[micros(now - self.epoch)]);
+        let interval = self.interval;
+
+        let mut result = Ok(());
+
+        for ((lobby_id, member_id), member) in &mut self.members {
+            match member.last_ping {
+                Some(last_ping) if now - last_ping < interval => continue,
+                _ => member.last_ping = Some(now),
+            }
+
+            result = result.and(discord.send_lobby_network_message(
+                *lobby_id,
+                *member_id,
+                self.channel_id,
+                &ping,
+            ));
+        }
+
+        result
+    }
+
+    /// Answers pings and records the samples given by pongs.
+    ///
+    /// Messages received on other channels are ignored, and malformed messages are logged.
+    ///
+    /// Forward [`EventHandler::on_lobby_network_message`](trait.EventHandler.html#method.on_lobby_network_message) here.
+    pub fn on_lobby_network_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) {
+        if channel_id != self.channel_id {
+            return;
+        }
+
+        let now = micros(self.local_time());
+
+        match (data.first().copied(), data.len()) {
+            (Some(PING), PING_LEN) => {
+                let pong = packet(PONG, &[read_u64(&data[1..]), now]);
+
+                if let Err(error) =
+                    discord.send_lobby_network_message(lobby_id, member_id, channel_id, &pong)
+                {
+                    log::warn!("failed to answer ping from {}: {}", member_id, error);
+                }
+            }
+
+            (Some(PONG), PONG_LEN) => {
+                let sent = read_u64(&data[1..]);
+                let remote = read_u64(&data[9..]);
+
+                self.record(lobby_id, member_id, sent, remote, now);
+            }
+
+            _ => log::warn!("dropped malformed clock packet from {}", member_id),
+        }
+    }
+
+    fn record(&mut self, lobby_id: LobbyID, member_id: UserID, sent: u64, remote: u64, now: u64) {
+        let samples = self.samples;
+
+        let member = match self.members.get_mut(&(lobby_id, member_id)) {
+            Some(member) => member,
+            None => return,
+        };
+
+        // Pongs claiming to answer a ping from the future are forged
+        if sent > now {
+            log::warn!(
+                "dropped clock packet from {} with a bad timestamp",
+                member_id
+            );
+            return;
+        }
+
+        let midpoint = sent + (now - sent) / 2;
+
+        member.samples.push_back(Sample {
+            round_trip: now - sent,
+            offset: (remote as i64).wrapping_sub(midpoint as i64),
+        });
+
+        while member.samples.len() > samples {
+            let _ = member.samples.pop_front();
+        }
+    }
+}
+
+// Median offset of the half of the samples with the shortest round trips, and median round trip
+fn estimate(samples: &VecDeque<Sample>) -> Option<(i64, u64)> {
+    if samples.is_empty() {
+        return None;
+    }
+
+    let mut sorted = samples.iter().copied().collect::<Vec<_>>();
+    sorted.sort_by_key(|sample| sample.round_trip);
+
+    let round_trip = sorted[sorted.len() / 2].round_trip;
+
+    let mut offsets = sorted[..sorted.len() - sorted.len() / 2]
+        .iter()
+        .map(|sample| sample.offset)
+        .collect::<Vec<_>>();
+    offsets.sort_unstable();
+
+    Some((offsets[offsets.len() / 2], round_trip))
+}
+
+fn micros(duration: Duration) -> u64 {
+    duration.as_micros().try_into().unwrap_or(u64::MAX)
+}
+
+fn read_u64(data: &[u8]) -> u64 {
+    u64::from_le_bytes(data[..8].try_into().unwrap())
+}
+
+fn packet(kind: u8, timestamps: &[u64]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(1 + 8 * timestamps.len());
+    packet.push(kind);
+
+    for timestamp in timestamps {
+        packet.extend_from_slice(&timestamp.to_le_bytes());
+    }
+
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        let mut clocks = ClockSync::new(0);
+        clocks.samples(5);
+        clocks.on_member_connect(1, 2);
+
+        // Remote clock is 1000µs ahead, one ping is delayed on the way back only
+        for (sent, ping, pong) in [(0, 10, 10), (100, 10, 12), (200, 10, 400), (300, 11, 11)]
+            .iter()
+            .copied()
+        {
+            clocks.record(1, 2, sent, sent + ping + 1000, sent + ping + pong);
+        }
+
+        clocks.record(1, 2, 500, 0, 400);
+
+        assert_eq!(clocks.clock_offset(1, 2), Some(1000));
+        assert_eq!(
+            clocks.round_trip_time(1, 2),
+            Some(Duration::from_micros(22))
+        );
+        assert_eq!(clocks.clock_offset(1, 3), None);
+
+        clocks.samples(1).record(1, 2, 0, 5000, 20);
+        assert_eq!(clocks.clock_offset(1, 2), Some(4990));
+    }
+}
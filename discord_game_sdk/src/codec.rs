@@ -0,0 +1,38 @@
+use crate::CodecError;
+
+/// Conversion between values and the bytes sent over a [`Channel`](struct.Channel.html)
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// struct Position {
+///     x: i16,
+///     y: i16,
+/// }
+///
+/// struct PositionCodec;
+///
+/// impl Codec<Position> for PositionCodec {
+///     fn encode(&self, value: &Position) -> std::result::Result<Vec<u8>, CodecError> {
+///         let mut data = value.x.to_le_bytes().to_vec();
+///         data.extend_from_slice(&value.y.to_le_bytes());
+///         Ok(data)
+///     }
+///
+///     fn decode(&self, data: &[u8]) -> std::result::Result<Position, CodecError> {
+///         match data {
+///             [x0, x1, y0, y1] => Ok(Position {
+///                 x: i16::from_le_bytes([*x0, *x1]),
+///                 y: i16::from_le_bytes([*y0, *y1]),
+///             }),
+///             _ => Err(CodecError::new("expected 4 bytes")),
+///         }
+///     }
+/// }
+/// ```
+pub trait Codec<T> {
+    /// Encodes a value
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError>;
+
+    /// Decodes a value
+    fn decode(&self, data: &[u8]) -> Result<T, CodecError>;
+}
@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+/// Compression settings of a channel, see [`Compressor`](struct.Compressor.html)
+///
+/// Messages smaller than the threshold, 64 bytes by default, are sent uncompressed,
+/// as are messages that do not shrink when compressed.
+///
+/// Both ends must use the same dictionary, if any.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Compression {
+    pub(crate) algorithm: Algorithm,
+    pub(crate) dictionary: Option<Arc<[u8]>>,
+    pub(crate) threshold: usize,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum Algorithm {
+    None,
+    #[cfg(feature = "lz4")]
+    Lz4,
+    #[cfg(feature = "zstd")]
+    Zstd(i32),
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl Compression {
+    /// Sends messages uncompressed.
+    pub fn none() -> Self {
+        Self {
+            algorithm: Algorithm::None,
+            dictionary: None,
+            threshold: 64,
+        }
+    }
+
+    /// Compresses messages with [`lz4`](https://docs.rs/lz4_flex), fast with a modest ratio.
+    ///
+    /// Requires the `lz4` feature.
+    #[cfg(feature = "lz4")]
+    pub fn lz4() -> Self {
+        Self {
+            algorithm: Algorithm::Lz4,
+            ..Self::none()
+        }
+    }
+
+    /// Compresses messages with [`zstd`](https://docs.rs/zstd) at the given level, from 1 to 22.
+    ///
+    /// Requires the `zstd` feature.
+    #[cfg(feature = "zstd")]
+    pub fn zstd(level: i32) -> Self {
+        Self {
+            algorithm: Algorithm::Zstd(level),
+            ..Self::none()
+        }
+    }
+
+    /// Primes compression with a dictionary of data resembling the messages.
+    pub fn dictionary(&mut self, dictionary: impl Into<Vec<u8>>) -> &mut Self {
+        self.dictionary = Some(dictionary.into().into());
+        self
+    }
+
+    /// Sends messages smaller than `threshold` bytes uncompressed.
+    pub fn threshold(&mut self, threshold: usize) -> &mut Self {
+        self.threshold = threshold;
+        self
+    }
+}
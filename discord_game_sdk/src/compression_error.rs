@@ -0,0 +1,35 @@
+use std::fmt;
+
+/// Error while decompressing a message, see [`Compressor`](struct.Compressor.html)
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum CompressionError {
+    /// The message is empty or its header is incomplete
+    Truncated,
+
+    /// The message was compressed with an algorithm that is unknown or not enabled
+    UnsupportedAlgorithm(u8),
+
+    /// The decompressed message would exceed the maximum size
+    TooLarge {
+        /// Maximum size of a decompressed message
+        max: usize,
+    },
+
+    /// The compressed data is invalid, or does not match the dictionary
+    Corrupted(String),
+}
+
+impl fmt::Display for CompressionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "message is truncated"),
+            Self::UnsupportedAlgorithm(flag) => {
+                write!(f, "unsupported compression algorithm {}", flag)
+            }
+            Self::TooLarge { max } => write!(f, "message exceeds {} bytes", max),
+            Self::Corrupted(reason) => write!(f, "corrupted message: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for CompressionError {}
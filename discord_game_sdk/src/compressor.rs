@@ -0,0 +1,355 @@
+use crate::{
+    compression::Algorithm, Compression, CompressionError, Discord, LobbyID, NetworkChannelID,
+    NetworkPeerID, Result, UserID,
+};
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+};
+
+const RAW: u8 = 0;
+#[cfg_attr(not(feature = "lz4"), allow(dead_code))]
+const LZ4: u8 = 1;
+#[cfg_attr(not(feature = "zstd"), allow(dead_code))]
+const ZSTD: u8 = 2;
+
+const HEADER_LEN: usize = 5;
+
+/// Transparent compression of network and lobby messages
+///
+/// Every message starts with a flag byte telling whether and how it was compressed,
+/// so compressed and uncompressed messages can coexist on a channel.
+/// Compressed messages follow the flag with their uncompressed size as
+/// a little-endian 32 bits integer.
+///
+/// Compression is configured per channel ID, for both peer and lobby networking,
+/// and separately for lobby messages. Channels default to no compression.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// struct MyEventHandler {
+///     compressor: Compressor,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_network_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         peer_id: NetworkPeerID,
+///         channel_id: NetworkChannelID,
+///         data: &[u8],
+///     ) {
+///         match self.compressor.on_network_message(channel_id, data) {
+///             Ok(message) => println!("received {} bytes", message.len()),
+///             Err(error) => eprintln!("dropped message from {}: {}", peer_id, error),
+///         }
+///     }
+///
+///     // ...
+/// }
+///
+/// # fn example(discord: Discord<'_, ()>, compressor: &mut Compressor, peer_id: NetworkPeerID) -> Result<()> {
+/// compressor.send_message(&discord, peer_id, 0, vec![0; 4096])?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Compressor {
+    channels: HashMap<NetworkChannelID, Compression>,
+    lobby_messages: Compression,
+    max_message_size: usize,
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Self {
+            channels: HashMap::new(),
+            lobby_messages: Compression::none(),
+            max_message_size: 1024 * 1024,
+        }
+    }
+}
+
+impl Compressor {
+    /// Creates a compressor leaving every message uncompressed,
+    /// and accepting decompressed messages up to 1MiB.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the compression of a channel ID, for both peer and lobby networking.
+    pub fn channel(&mut self, channel_id: NetworkChannelID, compression: Compression) -> &mut Self {
+        let _ = self.channels.insert(channel_id, compression);
+        self
+    }
+
+    /// Sets the compression of lobby messages.
+    pub fn lobby_messages(&mut self, compression: Compression) -> &mut Self {
+        self.lobby_messages = compression;
+        self
+    }
+
+    /// Sets the maximum size of a decompressed message, larger messages are rejected.
+    pub fn max_message_size(&mut self, max_message_size: usize) -> &mut Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// Compresses and sends data to a given peer ID through the given channel.
+    ///
+    /// See [`send_message`](struct.Discord.html#method.send_message).
+    pub fn send_message<E>(
+        &self,
+        discord: &Discord<'_, E>,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: impl AsRef<[u8]>,
+    ) -> Result<()> {
+        let data = compress(self.channel_compression(channel_id), data.as_ref());
+
+        discord.send_message(peer_id, channel_id, data)
+    }
+
+    /// Compresses and sends a network message to a lobby member.
+    ///
+    /// See [`send_lobby_network_message`](struct.Discord.html#method.send_lobby_network_message).
+    pub fn send_lobby_network_message<E>(
+        &self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        user_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> Result<()> {
+        let data = compress(self.channel_compression(channel_id), data);
+
+        discord.send_lobby_network_message(lobby_id, user_id, channel_id, &data)
+    }
+
+    /// Compresses and sends a message to every member of a lobby.
+    ///
+    /// See [`send_lobby_message`](struct.Discord.html#method.send_lobby_message).
+    pub fn send_lobby_message<'d, E>(
+        &self,
+        discord: &Discord<'d, E>,
+        lobby_id: LobbyID,
+        data: impl AsRef<[u8]>,
+        callback: impl 'd + FnOnce(&Discord<'d, E>, Result<()>),
+    ) {
+        let data = compress(&self.lobby_messages, data.as_ref());
+
+        discord.send_lobby_message(lobby_id, data, callback)
+    }
+
+    /// Decompresses a message received on a given channel ID.
+    ///
+    /// Forward [`EventHandler::on_network_message`](trait.EventHandler.html#method.on_network_message) here.
+    pub fn on_network_message<'a>(
+        &self,
+        channel_id: NetworkChannelID,
+        data: &'a [u8],
+    ) -> std::result::Result<Cow<'a, [u8]>, CompressionError> {
+        decompress(
+            self.channel_compression(channel_id),
+            self.max_message_size,
+            data,
+        )
+    }
+
+    /// Decompresses a message received on a given lobby network channel ID.
+    ///
+    /// Forward [`EventHandler::on_lobby_network_message`](trait.EventHandler.html#method.on_lobby_network_message) here.
+    pub fn on_lobby_network_message<'a>(
+        &self,
+        channel_id: NetworkChannelID,
+        data: &'a [u8],
+    ) -> std::result::Result<Cow<'a, [u8]>, CompressionError> {
+        self.on_network_message(channel_id, data)
+    }
+
+    /// Decompresses a lobby message.
+    ///
+    /// Forward [`EventHandler::on_lobby_message`](trait.EventHandler.html#method.on_lobby_message) here.
+    pub fn on_lobby_message<'a>(
+        &self,
+        data: &'a [u8],
+    ) -> std::result::Result<Cow<'a, [u8]>, CompressionError> {
+        decompress(&self.lobby_messages, self.max_message_size, data)
+    }
+
+    fn channel_compression(&self, channel_id: NetworkChannelID) -> &Compression {
+        static NONE: Compression = Compression {
+            algorithm: Algorithm::None,
+            dictionary: None,
+            threshold: 0,
+        };
+
+        self.channels.get(&channel_id).unwrap_or(&NONE)
+    }
+}
+
+fn compress(compression: &Compression, data: &[u8]) -> Vec<u8> {
+    let compressed = match u32::try_from(data.len()) {
+        Ok(len) if data.len() >= compression.threshold => {
+            compress_with(compression, data).map(|compressed| (len, compressed))
+        }
+        _ => None,
+    };
+
+    match compressed {
+        Some((len, (flag, compressed))) if HEADER_LEN + compressed.len() < 1 + data.len() => {
+            let mut message = Vec::with_capacity(HEADER_LEN + compressed.len());
+            message.push(flag);
+            message.extend_from_slice(&len.to_le_bytes());
+            message.extend_from_slice(&compressed);
+            message
+        }
+
+        _ => {
+            let mut message = Vec::with_capacity(1 + data.len());
+            message.push(RAW);
+            message.extend_from_slice(data);
+            message
+        }
+    }
+}
+
+#[cfg_attr(not(any(feature = "lz4", feature = "zstd")), allow(unused_variables))]
+fn compress_with(compression: &Compression, data: &[u8]) -> Option<(u8, Vec<u8>)> {
+    let dictionary = compression.dictionary.as_deref().unwrap_or(&[]);
+
+    match compression.algorithm {
+        Algorithm::None => None,
+
+        #[cfg(feature = "lz4")]
+        Algorithm::Lz4 => Some((LZ4, lz4_flex::block::compress_with_dict(data, dictionary))),
+
+        #[cfg(feature = "zstd")]
+        Algorithm::Zstd(level) => {
+            let compressed = zstd_crate::bulk::Compressor::with_dictionary(level, dictionary)
+                .and_then(|mut compressor| compressor.compress(data));
+
+            match compressed {
+                Ok(compressed) => Some((ZSTD, compressed)),
+                Err(error) => {
+                    log::warn!("failed to compress message, sending it as is: {}", error);
+                    None
+                }
+            }
+        }
+    }
+}
+
+fn decompress<'a>(
+    compression: &Compression,
+    max_message_size: usize,
+    data: &'a [u8],
+) -> std::result::Result<Cow<'a, [u8]>, CompressionError> {
+    let (flag, data) = match data.split_first() {
+        Some((flag, data)) => (*flag, data),
+        None => return Err(CompressionError::Truncated),
+    };
+
+    if flag == RAW {
+        return Ok(Cow::Borrowed(data));
+    }
+
+    if data.len() < HEADER_LEN - 1 {
+        return Err(CompressionError::Truncated);
+    }
+
+    let len = u32::from_le_bytes(data[..HEADER_LEN - 1].try_into().unwrap()) as usize;
+    let data = &data[HEADER_LEN - 1..];
+
+    if len > max_message_size {
+        return Err(CompressionError::TooLarge {
+            max: max_message_size,
+        });
+    }
+
+    let dictionary = compression.dictionary.as_deref().unwrap_or(&[]);
+    let decompressed = decompress_with(flag, dictionary, len, data)?;
+
+    if decompressed.len() != len {
+        return Err(CompressionError::Corrupted(
+            "size does not match header".to_string(),
+        ));
+    }
+
+    Ok(Cow::Owned(decompressed))
+}
+
+#[cfg_attr(not(any(feature = "lz4", feature = "zstd")), allow(unused_variables))]
+fn decompress_with(
+    flag: u8,
+    dictionary: &[u8],
+    len: usize,
+    data: &[u8],
+) -> std::result::Result<Vec<u8>, CompressionError> {
+    match flag {
+        #[cfg(feature = "lz4")]
+        LZ4 => lz4_flex::block::decompress_with_dict(data, len, dictionary)
+            .map_err(|error| CompressionError::Corrupted(error.to_string())),
+
+        #[cfg(feature = "zstd")]
+        ZSTD => zstd_crate::bulk::Decompressor::with_dictionary(dictionary)
+            .and_then(|mut decompressor| decompressor.decompress(data, len))
+            .map_err(|error| CompressionError::Corrupted(error.to_string())),
+
+        _ => Err(CompressionError::UnsupportedAlgorithm(flag)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uncompressed() {
+        let compression = Compression::none();
+        let data = compress(&compression, b"abc");
+
+        assert_eq!(data, b"\0abc");
+        assert_eq!(decompress(&compression, 10, &data).unwrap(), &b"abc"[..]);
+        assert_eq!(
+            decompress(&compression, 10, &[]),
+            Err(CompressionError::Truncated)
+        );
+        assert_eq!(
+            decompress(&compression, 10, &[LZ4 + ZSTD + 1, 0, 0, 0, 0]),
+            Err(CompressionError::UnsupportedAlgorithm(LZ4 + ZSTD + 1))
+        );
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn test_lz4() {
+        let mut compression = Compression::lz4();
+        compression.dictionary(b"position velocity".to_vec());
+
+        let data = b"position velocity ".repeat(10);
+        let compressed = compress(&compression, &data);
+
+        assert_eq!(compressed[0], LZ4);
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress(&compression, 1024, &compressed).unwrap(), data);
+        assert_eq!(
+            decompress(&compression, 10, &compressed),
+            Err(CompressionError::TooLarge { max: 10 })
+        );
+
+        assert_eq!(compress(&compression, b"small")[0], RAW);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn test_zstd() {
+        let compression = Compression::zstd(3);
+
+        let data = b"position velocity ".repeat(10);
+        let compressed = compress(&compression, &data);
+
+        assert_eq!(compressed[0], ZSTD);
+        assert_eq!(decompress(&compression, 1024, &compressed).unwrap(), data);
+    }
+}
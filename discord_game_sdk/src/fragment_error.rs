@@ -0,0 +1,53 @@
+use crate::Error;
+use std::fmt;
+
+/// Error while sending or reassembling a message with a [`Fragmenter`](struct.Fragmenter.html)
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum FragmentError {
+    /// The fragment is shorter than its header
+    Truncated,
+
+    /// The fragment does not follow the previous one, the partial message was discarded
+    OutOfOrder,
+
+    /// The messages reassembled from the same sender would exceed the maximum size, it is discarded
+    TooLarge {
+        /// The maximum size of a reassembled message
+        max: usize,
+    },
+
+    /// The message to send needs more fragments than can be numbered, it was not sent
+    TooManyFragments,
+
+    /// A fragment could not be sent by the SDK
+    Discord(Error),
+}
+
+impl From<Error> for FragmentError {
+    fn from(error: Error) -> Self {
+        Self::Discord(error)
+    }
+}
+
+impl fmt::Display for FragmentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(f, "truncated fragment"),
+            Self::OutOfOrder => write!(f, "fragment received out of order"),
+            Self::TooLarge { max } => {
+                write!(f, "reassembled message exceeds {} bytes", max)
+            }
+            Self::TooManyFragments => write!(f, "message needs too many fragments"),
+            Self::Discord(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for FragmentError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Discord(error) => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,405 @@
+use crate::{Discord, FragmentError, LobbyID, NetworkChannelID, NetworkPeerID, UserID};
+use std::{
+    collections::HashMap,
+    convert::{TryFrom, TryInto},
+};
+
+const HEADER_LEN: usize = 12;
+
+/// Fragmentation of large messages over network channels
+///
+/// Messages are split into numbered fragments small enough for the transport,
+/// and reassembled on the receiving end. Every fragment starts with a 12 bytes header:
+/// the message ID, the index of the fragment and the number of fragments,
+/// as little-endian 32 bits integers.
+///
+/// Fragments must be sent on channels opened with
+/// [`Reliability::Reliable`](enum.Reliability.html#variant.Reliable), so they are neither lost nor reordered.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// struct MyEventHandler {
+///     fragmenter: Fragmenter,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_network_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         peer_id: NetworkPeerID,
+///         channel_id: NetworkChannelID,
+///         data: &[u8],
+///     ) {
+///         match self.fragmenter.on_network_message(peer_id, channel_id, data) {
+///             Ok(Some(message)) => println!("received {} bytes", message.len()),
+///             Ok(None) => {}
+///             Err(error) => eprintln!("dropped message from {}: {}", peer_id, error),
+///         }
+///     }
+/// }
+///
+/// # fn example(discord: Discord<'_, ()>, peer_id: NetworkPeerID) -> std::result::Result<(), FragmentError> {
+/// let mut fragmenter = Fragmenter::new();
+/// fragmenter.max_message_size(4 * 1024 * 1024);
+///
+/// discord.open_channel(peer_id, 0, Reliability::Reliable)?;
+///
+/// let map = vec![0; 300 * 1024];
+/// fragmenter.send_message(&discord, peer_id, 0, &map)?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Fragmenter {
+    fragment_size: usize,
+    max_message_size: usize,
+    next_message_id: u32,
+    partials: HashMap<Sender, Partial>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Sender {
+    Peer(NetworkPeerID, NetworkChannelID),
+    Member(LobbyID, UserID, NetworkChannelID),
+}
+
+impl Sender {
+    fn is_same_remote(&self, other: &Sender) -> bool {
+        match (self, other) {
+            (Sender::Peer(peer_id, _), Sender::Peer(other, _)) => peer_id == other,
+            (
+                Sender::Member(lobby_id, member_id, _),
+                Sender::Member(other_lobby, other_member, _),
+            ) => (lobby_id, member_id) == (other_lobby, other_member),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Partial {
+    message_id: u32,
+    next_index: u32,
+    count: u32,
+    data: Vec<u8>,
+    discarded: bool,
+}
+
+impl Default for Fragmenter {
+    fn default() -> Self {
+        Self {
+            fragment_size: 1024,
+            max_message_size: 1024 * 1024,
+            next_message_id: 0,
+            partials: HashMap::new(),
+        }
+    }
+}
+
+impl Fragmenter {
+    /// Creates a fragmenter sending fragments of 1KiB and accepting messages up to 1MiB.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum size of the data carried by each fragment, excluding the header.
+    pub fn fragment_size(&mut self, fragment_size: usize) -> &mut Self {
+        self.fragment_size = fragment_size.max(1);
+        self
+    }
+
+    /// Sets the maximum size of a reassembled message, larger messages are discarded.
+    ///
+    /// The limit applies to each peer or lobby member, across channels:
+    /// messages reassembled at the same time on several channels share it.
+    pub fn max_message_size(&mut self, max_message_size: usize) -> &mut Self {
+        self.max_message_size = max_message_size;
+        self
+    }
+
+    /// Sends a message to a given peer ID through the given channel, in as many fragments as needed.
+    ///
+    /// ## Errors
+    ///
+    /// [`FragmentError::TooManyFragments`](enum.FragmentError.html#variant.TooManyFragments)
+    /// if the message needs more than 4 294 967 295 fragments, nothing is sent.
+    ///
+    /// Fragments that were sent before an error are not recalled,
+    /// the receiving end discards them when the next message arrives.
+    pub fn send_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: impl AsRef<[u8]>,
+    ) -> std::result::Result<(), FragmentError> {
+        for fragment in self.fragments(data.as_ref())? {
+            discord.send_message(peer_id, channel_id, fragment)?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends a message to a lobby member through the given lobby network channel,
+    /// in as many fragments as needed.
+    ///
+    /// ## Errors
+    ///
+    /// [`FragmentError::TooManyFragments`](enum.FragmentError.html#variant.TooManyFragments)
+    /// if the message needs more than 4 294 967 295 fragments, nothing is sent.
+    ///
+    /// Fragments that were sent before an error are not recalled,
+    /// the receiving end discards them when the next message arrives.
+    pub fn send_lobby_network_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        user_id: UserID,
+        channel_id: NetworkChannelID,
+        data: impl AsRef<[u8]>,
+    ) -> std::result::Result<(), FragmentError> {
+        for fragment in self.fragments(data.as_ref())? {
+            discord.send_lobby_network_message(lobby_id, user_id, channel_id, &fragment)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reassembles a fragment, returning the message once every fragment was received.
+    ///
+    /// Forward [`EventHandler::on_network_message`](trait.EventHandler.html#method.on_network_message) here.
+    pub fn on_network_message(
+        &mut self,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> std::result::Result<Option<Vec<u8>>, FragmentError> {
+        self.reassemble(Sender::Peer(peer_id, channel_id), data)
+    }
+
+    /// Reassembles a fragment, returning the message once every fragment was received.
+    ///
+    /// Forward [`EventHandler::on_lobby_network_message`](trait.EventHandler.html#method.on_lobby_network_message) here.
+    pub fn on_lobby_network_message(
+        &mut self,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> std::result::Result<Option<Vec<u8>>, FragmentError> {
+        self.reassemble(Sender::Member(lobby_id, member_id, channel_id), data)
+    }
+
+    /// Discards the partial messages received from a given peer ID.
+    ///
+    /// Call this after [`close_peer`](struct.Discord.html#method.close_peer).
+    pub fn forget_peer(&mut self, peer_id: NetworkPeerID) {
+        self.partials.retain(|sender, _| match sender {
+            Sender::Peer(id, _) => *id != peer_id,
+            _ => true,
+        });
+    }
+
+    /// Discards the partial messages received from a given lobby member.
+    ///
+    /// Call this from [`EventHandler::on_member_disconnect`](trait.EventHandler.html#method.on_member_disconnect).
+    pub fn forget_member(&mut self, lobby_id: LobbyID, member_id: UserID) {
+        self.partials.retain(|sender, _| match sender {
+            Sender::Member(lobby, member, _) => (*lobby, *member) != (lobby_id, member_id),
+            _ => true,
+        });
+    }
+
+    fn fragments(&mut self, data: &[u8]) -> std::result::Result<Vec<Vec<u8>>, FragmentError> {
+        let count = fragment_count(data.len(), self.fragment_size)?;
+
+        let message_id = self.next_message_id;
+        self.next_message_id = self.next_message_id.wrapping_add(1);
+
+        if data.is_empty() {
+            return Ok(vec![fragment(message_id, 0, count, &[])]);
+        }
+
+        Ok((0..count)
+            .zip(data.chunks(self.fragment_size))
+            .map(|(index, chunk)| fragment(message_id, index, count, chunk))
+            .collect())
+    }
+
+    fn reassemble(
+        &mut self,
+        sender: Sender,
+        data: &[u8],
+    ) -> std::result::Result<Option<Vec<u8>>, FragmentError> {
+        if data.len() < HEADER_LEN {
+            return Err(FragmentError::Truncated);
+        }
+
+        let message_id = u32::from_le_bytes(data[0..4].try_into().unwrap());
+        let index = u32::from_le_bytes(data[4..8].try_into().unwrap());
+        let count = u32::from_le_bytes(data[8..12].try_into().unwrap());
+        let payload = &data[HEADER_LEN..];
+
+        if index >= count {
+            let _ = self.partials.remove(&sender);
+            return Err(FragmentError::OutOfOrder);
+        }
+
+        if index == 0 {
+            let _ = self.partials.insert(
+                sender,
+                Partial {
+                    message_id,
+                    next_index: 0,
+                    count,
+                    data: Vec::new(),
+                    discarded: false,
+                },
+            );
+        }
+
+        // Data buffered from the same remote on every channel, including this one
+        let buffered: usize = self
+            .partials
+            .iter()
+            .filter(|(other, _)| other.is_same_remote(&sender))
+            .map(|(_, partial)| partial.data.len())
+            .sum();
+
+        let partial = match self.partials.get_mut(&sender) {
+            Some(partial)
+                if partial.message_id == message_id
+                    && partial.next_index == index
+                    && partial.count == count =>
+            {
+                partial
+            }
+
+            _ => {
+                let _ = self.partials.remove(&sender);
+                return Err(FragmentError::OutOfOrder);
+            }
+        };
+
+        partial.next_index += 1;
+        let complete = partial.next_index == partial.count;
+
+        let result = if partial.discarded {
+            Ok(None)
+        } else if buffered + payload.len() > self.max_message_size {
+            partial.discarded = true;
+            partial.data = Vec::new();
+
+            Err(FragmentError::TooLarge {
+                max: self.max_message_size,
+            })
+        } else {
+            partial.data.extend_from_slice(payload);
+            Ok(None)
+        };
+
+        if complete {
+            let partial = self.partials.remove(&sender).unwrap();
+
+            if result.is_ok() && !partial.discarded {
+                return Ok(Some(partial.data));
+            }
+        }
+
+        result
+    }
+}
+
+// Empty messages are sent as a single empty fragment
+fn fragment_count(len: usize, fragment_size: usize) -> std::result::Result<u32, FragmentError> {
+    let count = match len % fragment_size {
+        0 => len / fragment_size,
+        _ => len / fragment_size + 1,
+    };
+
+    u32::try_from(count.max(1)).map_err(|_| FragmentError::TooManyFragments)
+}
+
+fn fragment(message_id: u32, index: u32, count: u32, chunk: &[u8]) -> Vec<u8> {
+    let mut fragment = Vec::with_capacity(HEADER_LEN + chunk.len());
+    fragment.extend_from_slice(&message_id.to_le_bytes());
+    fragment.extend_from_slice(&index.to_le_bytes());
+    fragment.extend_from_slice(&count.to_le_bytes());
+    fragment.extend_from_slice(chunk);
+    fragment
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fragment_count() {
+        assert_eq!(fragment_count(0, 4), Ok(1));
+        assert_eq!(fragment_count(8, 4), Ok(2));
+        assert_eq!(fragment_count(9, 4), Ok(3));
+        assert_eq!(fragment_count(u32::MAX as usize, 1), Ok(u32::MAX));
+
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(
+            fragment_count(u32::MAX as usize + 1, 1),
+            Err(FragmentError::TooManyFragments)
+        );
+    }
+
+    #[test]
+    fn test_reassemble() {
+        let mut fragmenter = Fragmenter::new();
+        fragmenter.fragment_size(4).max_message_size(10);
+
+        let sender = Sender::Peer(1, 0);
+        let fragments = fragmenter.fragments(b"0123456789").unwrap();
+        assert_eq!(fragments.len(), 3);
+
+        assert_eq!(fragmenter.reassemble(sender, &fragments[0]), Ok(None));
+        assert_eq!(fragmenter.reassemble(sender, &fragments[1]), Ok(None));
+        assert_eq!(
+            fragmenter.reassemble(sender, &fragments[2]),
+            Ok(Some(b"0123456789".to_vec()))
+        );
+
+        let empty = fragmenter.fragments(b"").unwrap();
+        assert_eq!(fragmenter.reassemble(sender, &empty[0]), Ok(Some(vec![])));
+
+        let fragments = fragmenter.fragments(b"0123456789a").unwrap();
+        assert_eq!(fragmenter.reassemble(sender, &fragments[0]), Ok(None));
+        assert_eq!(fragmenter.reassemble(sender, &fragments[1]), Ok(None));
+        assert_eq!(
+            fragmenter.reassemble(sender, &fragments[2]),
+            Err(FragmentError::TooLarge { max: 10 })
+        );
+
+        // The limit is shared by the channels of a peer
+        let other_channel = Sender::Peer(1, 1);
+        let first = fragmenter.fragments(b"01234567").unwrap();
+        let second = fragmenter.fragments(b"01234567").unwrap();
+        assert_eq!(fragmenter.reassemble(sender, &first[0]), Ok(None));
+        assert_eq!(fragmenter.reassemble(other_channel, &second[0]), Ok(None));
+        assert_eq!(
+            fragmenter.reassemble(sender, &first[1]),
+            Err(FragmentError::TooLarge { max: 10 })
+        );
+        assert_eq!(
+            fragmenter.reassemble(other_channel, &second[1]),
+            Ok(Some(b"01234567".to_vec()))
+        );
+        assert_eq!(
+            fragmenter.reassemble(Sender::Peer(2, 0), &first[0]),
+            Ok(None)
+        );
+
+        let fragments = fragmenter.fragments(b"01234567").unwrap();
+        assert_eq!(
+            fragmenter.reassemble(sender, &fragments[1]),
+            Err(FragmentError::OutOfOrder)
+        );
+        assert_eq!(
+            fragmenter.reassemble(sender, &fragments[0][..4]),
+            Err(FragmentError::Truncated)
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use crate::{Discord, LobbyID, LobbyMemberTransaction, LobbyTransaction, Result, UserID};
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Host migration for peer-hosted lobbies
+///
+/// Every member stores the time at which it joined in its member metadata,
+/// the member that joined first is the host. Since every member sees the same metadata,
+/// they all elect the same host without having to communicate.
+///
+/// When the lobby owner is not the elected host, for example after the previous owner left,
+/// ownership is transferred to the elected host with [`update_lobby`](struct.Discord.html#method.update_lobby).
+///
+/// The helper is opt-in, events must be forwarded to it from the
+/// [`EventHandler`](trait.EventHandler.html).
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// #[derive(Default)]
+/// struct MyEventHandler {
+///     hosts: HostMigration,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_lobby_update(&mut self, discord: &Discord<'_, Self>, lobby_id: LobbyID) {
+///         if let Ok(Some(host)) = self.hosts.on_lobby_update(discord, lobby_id) {
+///             println!("{} is now hosting lobby {}", host, lobby_id);
+///         }
+///     }
+///
+///     fn on_member_disconnect(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///     ) {
+///         if let Ok(Some(host)) = self.hosts.on_member_disconnect(discord, lobby_id, member_id) {
+///             println!("{} is now hosting lobby {}", host, lobby_id);
+///         }
+///     }
+///
+///     // ...
+/// }
+///
+/// # fn example(discord: Discord<'_, MyEventHandler>, lobby_id: LobbyID) -> Result<()> {
+/// // After connecting to the lobby
+/// HostMigration::join(&discord, lobby_id, |discord, result| {
+///     if let Err(error) = result {
+///         eprintln!("failed to announce join order: {}", error);
+///     }
+/// })?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct HostMigration {
+    hosts: HashMap<LobbyID, UserID>,
+}
+
+impl HostMigration {
+    /// The member metadata key under which the join order is stored
+    pub const JOIN_ORDER_KEY: &'static str = "host_migration.join_order";
+
+    /// Creates a helper that has not elected any host yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The last host elected in a given lobby.
+    pub fn host(&self, lobby_id: LobbyID) -> Option<UserID> {
+        self.hosts.get(&lobby_id).copied()
+    }
+
+    /// Stores the join order of the current user in their member metadata.
+    ///
+    /// Must be called once after [`create_lobby`](struct.Discord.html#method.create_lobby) or
+    /// [`connect_lobby`](struct.Discord.html#method.connect_lobby) succeed.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if the current user is not available yet.
+    pub fn join<'d, E>(
+        discord: &Discord<'d, E>,
+        lobby_id: LobbyID,
+        callback: impl 'd + FnOnce(&Discord<'d, E>, Result<()>),
+    ) -> Result<()> {
+        let user_id = discord.current_user()?.id();
+
+        let joined_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis())
+            .unwrap_or(0);
+
+        discord.update_member(
+            lobby_id,
+            user_id,
+            LobbyMemberTransaction::new()
+                .add_metadata(Self::JOIN_ORDER_KEY.to_string(), joined_at.to_string()),
+            callback,
+        );
+
+        Ok(())
+    }
+
+    /// Elects the host of a lobby among its current members, without side effects.
+    ///
+    /// Members that did not announce their join order are only elected if no other member did,
+    /// ties are broken by user ID.
+    pub fn elect<E>(discord: &Discord<'_, E>, lobby_id: LobbyID) -> Result<Option<UserID>> {
+        Self::elect_among(discord, lobby_id, None)
+    }
+
+    /// Re-elects the host and transfers ownership if needed.
+    ///
+    /// Returns the new host if it changed.
+    ///
+    /// Forward [`EventHandler::on_lobby_update`](trait.EventHandler.html#method.on_lobby_update) here.
+    pub fn on_lobby_update<'d, E>(
+        &mut self,
+        discord: &Discord<'d, E>,
+        lobby_id: LobbyID,
+    ) -> Result<Option<UserID>> {
+        let host = Self::elect_among(discord, lobby_id, None)?;
+        self.migrate(discord, lobby_id, host)
+    }
+
+    /// Re-elects the host without the member that left and transfers ownership if needed.
+    ///
+    /// Returns the new host if it changed.
+    ///
+    /// Forward [`EventHandler::on_member_disconnect`](trait.EventHandler.html#method.on_member_disconnect) here.
+    pub fn on_member_disconnect<'d, E>(
+        &mut self,
+        discord: &Discord<'d, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> Result<Option<UserID>> {
+        let host = Self::elect_among(discord, lobby_id, Some(member_id))?;
+        self.migrate(discord, lobby_id, host)
+    }
+
+    /// Forgets the host of the lobby.
+    ///
+    /// Forward [`EventHandler::on_lobby_delete`](trait.EventHandler.html#method.on_lobby_delete) here,
+    /// and call it after [`disconnect_lobby`](struct.Discord.html#method.disconnect_lobby) succeeds.
+    pub fn on_lobby_delete(&mut self, lobby_id: LobbyID) {
+        let _ = self.hosts.remove(&lobby_id);
+    }
+
+    fn elect_among<E>(
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        excluded: Option<UserID>,
+    ) -> Result<Option<UserID>> {
+        let mut candidates = Vec::new();
+
+        for user_id in discord.iter_lobby_member_ids(lobby_id)? {
+            let user_id = user_id?;
+
+            if Some(user_id) == excluded {
+                continue;
+            }
+
+            let joined_at = discord
+                .lobby_member_metadata(lobby_id, user_id, Self::JOIN_ORDER_KEY)
+                .ok()
+                .and_then(|value| value.parse().ok());
+
+            candidates.push((user_id, joined_at));
+        }
+
+        Ok(elect(&candidates))
+    }
+
+    fn migrate<'d, E>(
+        &mut self,
+        discord: &Discord<'d, E>,
+        lobby_id: LobbyID,
+        host: Option<UserID>,
+    ) -> Result<Option<UserID>> {
+        let host = match host {
+            Some(host) => host,
+            None => return Ok(None),
+        };
+
+        let owner_id = discord.lobby(lobby_id)?.owner_id();
+
+        // Only the owner may transfer ownership, if this fails it is retried on the next update
+        if owner_id != host && owner_id == discord.current_user()?.id() {
+            discord.update_lobby(
+                lobby_id,
+                LobbyTransaction::new().owner(host),
+                move |_, result| {
+                    if let Err(error) = result {
+                        log::warn!(
+                            "failed to transfer ownership of lobby {}: {}",
+                            lobby_id,
+                            error
+                        );
+                    }
+                },
+            );
+        }
+
+        Ok(match self.hosts.insert(lobby_id, host) {
+            Some(previous) if previous == host => None,
+            _ => Some(host),
+        })
+    }
+}
+
+// Earliest join first, members without a join order last, then by user ID
+fn elect(candidates: &[(UserID, Option<u128>)]) -> Option<UserID> {
+    candidates
+        .iter()
+        .min_by_key(|(user_id, joined_at)| (joined_at.is_none(), *joined_at, *user_id))
+        .map(|(user_id, _)| *user_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_elect() {
+        assert_eq!(elect(&[]), None);
+        assert_eq!(elect(&[(3, None), (2, None)]), Some(2));
+        assert_eq!(elect(&[(1, None), (2, Some(20)), (3, Some(10))]), Some(3));
+        assert_eq!(elect(&[(4, Some(10)), (3, Some(10))]), Some(3));
+    }
+}
@@ -0,0 +1,18 @@
+use crate::{Codec, CodecError};
+use serde_crate::{de::DeserializeOwned, Serialize};
+
+/// [`Codec`](trait.Codec.html) for types implementing `serde` traits, using [`serde_json`](https://docs.rs/serde_json)
+///
+/// Requires the `serde` feature.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct JsonCodec;
+
+impl<T: Serialize + DeserializeOwned> Codec<T> for JsonCodec {
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        serde_json::to_vec(value).map_err(CodecError::new)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T, CodecError> {
+        serde_json::from_slice(data).map_err(CodecError::new)
+    }
+}
@@ -12,7 +12,7 @@
 //! - Store Transactions
 //! - Achievements
 //!
-//! *Version requirement: Rust 1.47 and up.*
+//! *Version requirement: Rust 1.47 and up, optional features may require newer versions (see below).*
 //!
 //! *[Release Notes](https://github.com/ldesgoui/discord_game_sdk/releases)*
 //!
@@ -63,6 +63,11 @@
 //! This allows for `cargo run` to function.
 //!
 //!
+//! ### `derive`
+//!
+//! Provides `#[derive(Metadata)]` for lobby and member metadata, see the `Metadata` trait.
+//!
+//!
 //! ### [`image`](https://docs.rs/image)
 //!
 //! Optional crate.
@@ -70,6 +75,62 @@
 //! Provides a conversion from our `Image` to `image::RgbaImage`.
 //!
 //!
+//! ### [`serde`](https://docs.rs/serde)
+//!
+//! Optional crate.
+//!
+//! Provides `Discord::save` and `Discord::load` for values saved to storage as [JSON](https://docs.rs/serde_json),
+//! see the `SaveFormat` struct for other formats, versions and migrations.
+//!
+//! Provides `JsonCodec` for typed network channels, see the `Channel` struct.
+//!
+//! Required by the `bincode`, `postcard` and `msgpack` features.
+//!
+//! *Requires Rust 1.71 and up with the latest release of `serde_json`.*
+//!
+//!
+//! ### `bincode`
+//!
+//! Enables `serde`, provides `BincodeCodec` for typed network channels, see the `Channel` struct.
+//!
+//!
+//! ### `postcard`
+//!
+//! Enables `serde`, provides `PostcardCodec` for typed network channels, see the `Channel` struct.
+//!
+//!
+//! ### `msgpack`
+//!
+//! Enables `serde`, provides `MessagePackCodec` for typed network channels, see the `Channel` struct.
+//!
+//! *Requires Rust 1.85 and up with the latest release of `rmp-serde`.*
+//!
+//!
+//! ### [`lz4`](https://docs.rs/lz4_flex)
+//!
+//! Optional crate.
+//!
+//! Provides `Compression::lz4` for compressed network and lobby messages, see the `Compressor` struct.
+//!
+//! *Requires Rust 1.81 and up with the latest release of `lz4_flex` 0.11.*
+//!
+//!
+//! ### [`zstd`](https://docs.rs/zstd)
+//!
+//! Optional crate.
+//!
+//! Provides `Compression::zstd` for compressed network and lobby messages, see the `Compressor` struct.
+//!
+//! *Requires Rust 1.64 and up, since `zstd` 0.13.*
+//!
+//!
+//! ### `encryption`
+//!
+//! Provides authenticated and encrypted network channels, see the `SecureChannel` struct.
+//!
+//! *Requires Rust 1.60 and up, since `x25519-dalek` 2.0.*
+//!
+//!
 //! # Safety
 //!
 //! This crate relies on the SDK to provide correct data and behavior:
@@ -108,8 +169,23 @@ mod action;
 mod activity;
 mod activity_kind;
 mod aliases;
+#[cfg(feature = "bincode")]
+mod bincode_codec;
+mod bytes_codec;
 mod cast;
+mod channel;
+mod channel_error;
+mod channel_stats;
+mod chat_error;
+mod chat_message;
+mod chat_message_kind;
+mod clock_sync;
+mod codec;
+mod codec_error;
 mod comparison;
+mod compression;
+mod compression_error;
+mod compressor;
 mod create_flags;
 mod discord;
 mod distance;
@@ -120,27 +196,76 @@ mod event_handler;
 pub(crate) mod events;
 mod fetch_kind;
 mod file_stat;
+mod fragment_error;
+mod fragmenter;
+mod host_migration;
 mod image;
 mod image_handle;
 mod image_kind;
 mod input_mode;
 mod input_mode_kind;
 pub(crate) mod iter;
+#[cfg(feature = "serde")]
+mod json_codec;
 mod lobby;
+mod lobby_change;
+mod lobby_chat;
 mod lobby_kind;
+mod lobby_member_snapshot;
 mod lobby_member_transaction;
+mod lobby_mirror;
+mod lobby_snapshot;
 mod lobby_transaction;
+mod lockstep;
+mod lockstep_event;
+mod matchmaker;
+mod matchmaking_outcome;
+#[cfg(feature = "msgpack")]
+mod message_pack_codec;
+mod metadata;
+mod network_conditions;
+mod network_simulator;
+mod network_stats;
+mod network_streams;
 mod oauth2_token;
+mod peer_manager;
+#[cfg(feature = "postcard")]
+mod postcard_codec;
 mod premium_kind;
 mod presence;
+mod ready_check;
+mod ready_state;
 mod relationship;
 mod relationship_kind;
 mod reliability;
 mod request_reply;
+mod rpc;
+mod rpc_endpoint;
+mod rpc_error;
+mod rpc_event;
+mod save_data;
+#[cfg(feature = "serde")]
+mod save_error;
+#[cfg(feature = "serde")]
+mod save_format;
+mod save_slot;
+mod save_slot_error;
 mod search_query;
+#[cfg(feature = "encryption")]
+mod secure_channel;
+#[cfg(feature = "encryption")]
+mod secure_channel_error;
+mod simulated_message;
 mod sku;
 mod sku_kind;
+mod snapshot_client;
+mod snapshot_host;
+mod snapshot_interpolation;
 mod status;
+mod storage_file;
+mod storage_writer;
+mod stream_reader;
+mod stream_writer;
 mod to_result;
 mod user;
 mod user_achievement;
@@ -171,13 +296,48 @@ mod mock;
 
 pub(crate) use discord_game_sdk_sys as sys;
 
+#[cfg(feature = "derive")]
+pub use discord_game_sdk_derive::Metadata;
+
+// Lets derived code in unit tests refer to this crate by name
+#[cfg(all(test, feature = "derive"))]
+extern crate self as discord_game_sdk;
+
+#[cfg(feature = "serde")]
+pub use self::{json_codec::JsonCodec, save_error::SaveError, save_format::SaveFormat};
+
+#[cfg(feature = "bincode")]
+pub use self::bincode_codec::BincodeCodec;
+
+#[cfg(feature = "postcard")]
+pub use self::postcard_codec::PostcardCodec;
+
+#[cfg(feature = "msgpack")]
+pub use self::message_pack_codec::MessagePackCodec;
+
+#[cfg(feature = "encryption")]
+pub use self::{secure_channel::SecureChannel, secure_channel_error::SecureChannelError};
+
 pub use self::{
     action::Action,
     activity::Activity,
     activity_kind::ActivityKind,
     aliases::*,
+    bytes_codec::BytesCodec,
     cast::Cast,
+    channel::Channel,
+    channel_error::ChannelError,
+    channel_stats::ChannelStats,
+    chat_error::ChatError,
+    chat_message::ChatMessage,
+    chat_message_kind::ChatMessageKind,
+    clock_sync::ClockSync,
+    codec::Codec,
+    codec_error::CodecError,
     comparison::Comparison,
+    compression::Compression,
+    compression_error::CompressionError,
+    compressor::Compressor,
     create_flags::CreateFlags,
     discord::Discord,
     distance::Distance,
@@ -187,26 +347,61 @@ pub use self::{
     event_handler::EventHandler,
     fetch_kind::FetchKind,
     file_stat::FileStat,
+    fragment_error::FragmentError,
+    fragmenter::Fragmenter,
+    host_migration::HostMigration,
     image::Image,
     image_handle::ImageHandle,
     image_kind::ImageKind,
     input_mode::InputMode,
     input_mode_kind::InputModeKind,
     lobby::Lobby,
+    lobby_change::LobbyChange,
+    lobby_chat::LobbyChat,
     lobby_kind::LobbyKind,
+    lobby_member_snapshot::LobbyMemberSnapshot,
     lobby_member_transaction::LobbyMemberTransaction,
+    lobby_mirror::LobbyMirror,
+    lobby_snapshot::LobbySnapshot,
     lobby_transaction::LobbyTransaction,
+    lockstep::Lockstep,
+    lockstep_event::LockstepEvent,
+    matchmaker::Matchmaker,
+    matchmaking_outcome::MatchmakingOutcome,
+    metadata::{Metadata, MetadataError, MetadataValue},
+    network_conditions::NetworkConditions,
+    network_simulator::NetworkSimulator,
+    network_stats::NetworkStats,
+    network_streams::NetworkStreams,
     oauth2_token::OAuth2Token,
+    peer_manager::PeerManager,
     premium_kind::PremiumKind,
     presence::Presence,
+    ready_check::ReadyCheck,
+    ready_state::ReadyState,
     relationship::Relationship,
     relationship_kind::RelationshipKind,
     reliability::Reliability,
     request_reply::RequestReply,
+    rpc::Rpc,
+    rpc_endpoint::RpcEndpoint,
+    rpc_error::RpcError,
+    rpc_event::RpcEvent,
+    save_data::SaveData,
+    save_slot::SaveSlot,
+    save_slot_error::SaveSlotError,
     search_query::SearchQuery,
+    simulated_message::SimulatedMessage,
     sku::Sku,
     sku_kind::SkuKind,
+    snapshot_client::SnapshotClient,
+    snapshot_host::SnapshotHost,
+    snapshot_interpolation::SnapshotInterpolation,
     status::Status,
+    storage_file::StorageFile,
+    storage_writer::StorageWriter,
+    stream_reader::StreamReader,
+    stream_writer::StreamWriter,
     user::User,
     user_achievement::UserAchievement,
     user_flags::UserFlags,
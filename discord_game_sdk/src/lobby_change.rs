@@ -0,0 +1,66 @@
+use crate::{LobbyKind, UserID};
+
+/// Change observed by [`LobbyMirror`](struct.LobbyMirror.html) between two snapshots of a lobby
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum LobbyChange {
+    /// The lobby was deleted
+    Deleted,
+
+    /// The lobby changed from public to private or the opposite
+    KindChanged {
+        /// Previous kind
+        old: LobbyKind,
+        /// Current kind
+        new: LobbyKind,
+    },
+
+    /// The lobby changed owner
+    OwnerChanged {
+        /// Previous owner
+        old: UserID,
+        /// Current owner
+        new: UserID,
+    },
+
+    /// The secret of the lobby changed
+    SecretChanged,
+
+    /// The capacity of the lobby changed
+    CapacityChanged {
+        /// Previous capacity
+        old: u32,
+        /// Current capacity
+        new: u32,
+    },
+
+    /// The lobby was locked or unlocked
+    LockedChanged(bool),
+
+    /// A lobby metadata value was added, changed or deleted
+    MetadataChanged {
+        /// The key of the value
+        key: String,
+        /// Previous value, `None` if it was added
+        old: Option<String>,
+        /// Current value, `None` if it was deleted
+        new: Option<String>,
+    },
+
+    /// A member joined the lobby
+    MemberConnected(UserID),
+
+    /// A member left the lobby
+    MemberDisconnected(UserID),
+
+    /// A member metadata value was added, changed or deleted
+    MemberMetadataChanged {
+        /// The member
+        user_id: UserID,
+        /// The key of the value
+        key: String,
+        /// Previous value, `None` if it was added
+        old: Option<String>,
+        /// Current value, `None` if it was deleted
+        new: Option<String>,
+    },
+}
@@ -0,0 +1,250 @@
+use crate::{ChatError, ChatMessage, ChatMessageKind, Discord, LobbyID, Result, UserID};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Structured chat over lobby messages
+///
+/// Messages are framed in a versioned envelope carrying their kind, a sequence number and
+/// a timestamp, see [`ChatMessage`](struct.ChatMessage.html).
+/// The last messages of every lobby are kept in memory, and the number of messages
+/// the current user can send is limited to avoid being rate-limited by Discord.
+///
+/// Lobby messages that cannot be decoded are reported as errors rather than displayed,
+/// messages sent by other components over the same lobby should be filtered out beforehand.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// struct MyEventHandler {
+///     chat: LobbyChat,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_lobby_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///         data: &[u8],
+///     ) {
+///         match self.chat.on_lobby_message(lobby_id, member_id, data) {
+///             Ok(Some(message)) => println!("{}: {}", message.sender_id(), message.text()),
+///             Ok(None) => {}
+///             Err(error) => eprintln!("dropped chat message from {}: {}", member_id, error),
+///         }
+///     }
+/// }
+///
+/// # fn example(discord: Discord<'_, ()>, chat: &mut LobbyChat, lobby_id: LobbyID) -> Result<()> {
+/// let sent = chat.send(&discord, lobby_id, ChatMessageKind::Text, "gg", |_, result| {
+///     if let Err(error) = result {
+///         eprintln!("failed to send chat message: {}", error);
+///     }
+/// });
+///
+/// if let Err(ChatError::RateLimited) = sent {
+///     eprintln!("slow down");
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct LobbyChat {
+    history_len: usize,
+    rate_limit: (usize, Duration),
+    sent: VecDeque<Instant>,
+    lobbies: HashMap<LobbyID, LobbyChatState>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct LobbyChatState {
+    next_sequence: u64,
+    history: VecDeque<ChatMessage>,
+}
+
+impl LobbyChat {
+    /// Creates a chat keeping the last `history_len` messages of every lobby, at least one.
+    ///
+    /// The rate limit defaults to 5 messages per second.
+    pub fn new(history_len: usize) -> Self {
+        Self {
+            history_len,
+            rate_limit: (5, Duration::from_secs(1)),
+            sent: VecDeque::new(),
+            lobbies: HashMap::new(),
+        }
+    }
+
+    /// Allows at most `messages` to be sent over any `period`, across all lobbies.
+    pub fn rate_limit(&mut self, messages: usize, period: Duration) -> &mut Self {
+        self.rate_limit = (messages, period);
+        self
+    }
+
+    /// Returns an `Iterator` over the history of a lobby, oldest first.
+    pub fn history(&self, lobby_id: LobbyID) -> impl '_ + Iterator<Item = &ChatMessage> {
+        self.lobbies
+            .get(&lobby_id)
+            .into_iter()
+            .flat_map(|lobby| lobby.history.iter())
+    }
+
+    /// Sends a message to every member of a lobby and adds it to the history.
+    ///
+    /// ## Errors
+    ///
+    /// [`ChatError::RateLimited`](enum.ChatError.html#variant.RateLimited) if the rate limit is exceeded,
+    /// the message is then neither sent nor added to the history.
+    /// Fails if the current user is not available yet.
+    /// Failures to send the message are passed to `callback`.
+    pub fn send<'d, E>(
+        &mut self,
+        discord: &Discord<'d, E>,
+        lobby_id: LobbyID,
+        kind: ChatMessageKind,
+        text: impl Into<String>,
+        callback: impl 'd + FnOnce(&Discord<'d, E>, Result<()>),
+    ) -> std::result::Result<&ChatMessage, ChatError> {
+        let now = Instant::now();
+
+        if !self.allow(now) {
+            return Err(ChatError::RateLimited);
+        }
+
+        let sender_id = discord.current_user()?.id();
+        let lobby = self.lobbies.entry(lobby_id).or_default();
+
+        let message = ChatMessage {
+            sender_id,
+            kind,
+            sequence: lobby.next_sequence,
+            timestamp: SystemTime::now(),
+            text: text.into(),
+        };
+
+        discord.send_lobby_message(lobby_id, message.encode(), callback);
+
+        self.sent.push_back(now);
+        lobby.next_sequence += 1;
+
+        Ok(push(&mut lobby.history, self.history_len, message))
+    }
+
+    /// Decodes a message and adds it to the history.
+    ///
+    /// Returns `None` if the message was already received.
+    ///
+    /// Forward [`EventHandler::on_lobby_message`](trait.EventHandler.html#method.on_lobby_message) here.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if `data` is not a valid chat message, it is then not added to the history.
+    pub fn on_lobby_message(
+        &mut self,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        data: &[u8],
+    ) -> std::result::Result<Option<&ChatMessage>, ChatError> {
+        let message = ChatMessage::decode(member_id, data)?;
+        let lobby = self.lobbies.entry(lobby_id).or_default();
+
+        let duplicate = lobby
+            .history
+            .iter()
+            .any(|m| m.sender_id == message.sender_id && m.sequence == message.sequence);
+
+        if duplicate {
+            return Ok(None);
+        }
+
+        Ok(Some(push(&mut lobby.history, self.history_len, message)))
+    }
+
+    /// Forgets the history of the lobby.
+    ///
+    /// Forward [`EventHandler::on_lobby_delete`](trait.EventHandler.html#method.on_lobby_delete) here,
+    /// and call it after [`disconnect_lobby`](struct.Discord.html#method.disconnect_lobby) succeeds.
+    pub fn on_lobby_delete(&mut self, lobby_id: LobbyID) {
+        let _ = self.lobbies.remove(&lobby_id);
+    }
+
+    fn allow(&mut self, now: Instant) -> bool {
+        let (messages, period) = self.rate_limit;
+
+        while let Some(sent) = self.sent.front() {
+            if now.duration_since(*sent) < period {
+                break;
+            }
+
+            let _ = self.sent.pop_front();
+        }
+
+        self.sent.len() < messages
+    }
+}
+
+fn push(history: &mut VecDeque<ChatMessage>, len: usize, message: ChatMessage) -> &ChatMessage {
+    history.push_back(message);
+
+    while history.len() > len.max(1) {
+        let _ = history.pop_front();
+    }
+
+    history.back().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_history() {
+        let mut chat = LobbyChat::new(2);
+        let message = |sequence: u64, text: &str| {
+            ChatMessage {
+                sender_id: 1,
+                kind: ChatMessageKind::Text,
+                sequence,
+                timestamp: SystemTime::now(),
+                text: text.to_string(),
+            }
+            .encode()
+        };
+
+        assert!(chat
+            .on_lobby_message(1, 1, &message(0, "a"))
+            .unwrap()
+            .is_some());
+        assert!(chat
+            .on_lobby_message(1, 1, &message(0, "a"))
+            .unwrap()
+            .is_none());
+        assert!(chat
+            .on_lobby_message(1, 1, &message(1, "b"))
+            .unwrap()
+            .is_some());
+        assert!(chat
+            .on_lobby_message(1, 1, &message(2, "c"))
+            .unwrap()
+            .is_some());
+        assert!(chat.on_lobby_message(1, 1, b"garbage").is_err());
+
+        let texts = chat.history(1).map(ChatMessage::text).collect::<Vec<_>>();
+        assert_eq!(texts, vec!["b", "c"]);
+    }
+
+    #[test]
+    fn test_rate_limit() {
+        let mut chat = LobbyChat::new(10);
+        chat.rate_limit(2, Duration::from_secs(1));
+
+        let now = Instant::now();
+
+        assert!(chat.allow(now));
+        chat.sent.push_back(now);
+        assert!(chat.allow(now));
+        chat.sent.push_back(now);
+        assert!(!chat.allow(now));
+        assert!(chat.allow(now + Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,21 @@
+use crate::UserID;
+use std::collections::HashMap;
+
+/// Owned copy of a lobby member, kept by [`LobbyMirror`](struct.LobbyMirror.html)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LobbyMemberSnapshot {
+    pub(crate) user_id: UserID,
+    pub(crate) metadata: HashMap<String, String>,
+}
+
+impl LobbyMemberSnapshot {
+    /// The ID of the member
+    pub fn user_id(&self) -> UserID {
+        self.user_id
+    }
+
+    /// The metadata of the member
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+}
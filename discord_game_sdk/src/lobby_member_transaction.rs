@@ -1,4 +1,7 @@
-use crate::{sys, to_result::ToResult, Result};
+use crate::{
+    lobby_transaction::diff_metadata, sys, to_result::ToResult, LobbyMemberSnapshot, Metadata,
+    Result,
+};
 use std::collections::HashMap;
 
 /// Lobby Member Transaction
@@ -38,6 +41,17 @@ impl LobbyMemberTransaction {
         self
     }
 
+    /// Sets every metadata value of `metadata` for the user
+    ///
+    /// See [`Metadata`](trait.Metadata.html).
+    pub fn add_typed_metadata(&mut self, metadata: &impl Metadata) -> &mut Self {
+        for (key, value) in metadata.to_metadata() {
+            self.add_metadata(key, value);
+        }
+
+        self
+    }
+
     /// Deletes metadata value under a given key for the user
     ///
     /// ## Performance
@@ -45,7 +59,7 @@ impl LobbyMemberTransaction {
     /// A nul byte will be appended to `key` if one is not present.
     ///
     /// > [Method in official docs](https://discordapp.com/developers/docs/game-sdk/lobbies#lobbymembertransactiondeletemetadata)
-    pub fn delete_metadata<S>(&mut self, mut key: String) -> &mut Self {
+    pub fn delete_metadata(&mut self, mut key: String) -> &mut Self {
         if !key.ends_with('\0') {
             key.push('\0')
         }
@@ -54,6 +68,41 @@ impl LobbyMemberTransaction {
         self
     }
 
+    /// Builds the smallest transaction that turns the metadata described by `old` into `new`
+    ///
+    /// Keys set in `old` but absent from `new` are deleted.
+    ///
+    /// ```rust
+    /// # use discord_game_sdk::*;
+    /// # fn example(discord: Discord<'_, ()>, lobby_id: LobbyID, user_id: UserID) -> Result<()> {
+    /// let mut old = LobbyMemberTransaction::new();
+    /// old.add_metadata("ready".into(), "0".into());
+    ///
+    /// let mut new = LobbyMemberTransaction::new();
+    /// new.add_metadata("ready".into(), "1".into());
+    ///
+    /// let diff = LobbyMemberTransaction::diff(&old, &new);
+    ///
+    /// if !diff.is_empty() {
+    ///     discord.update_member(lobby_id, user_id, &diff, |discord, result| {
+    ///         if let Err(error) = result {
+    ///             eprintln!("failed to update member: {}", error);
+    ///         }
+    ///     });
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn diff(old: &Self, new: &Self) -> Self {
+        Self {
+            metadata: diff_metadata(&old.metadata, &new.metadata),
+        }
+    }
+
+    /// Whether applying the transaction would change nothing
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_empty()
+    }
+
     pub(crate) unsafe fn process(
         &self,
         tx: *mut sys::IDiscordLobbyMemberTransaction,
@@ -84,3 +133,15 @@ impl LobbyMemberTransaction {
         Ok(())
     }
 }
+
+impl From<&LobbyMemberSnapshot> for LobbyMemberTransaction {
+    fn from(snapshot: &LobbyMemberSnapshot) -> Self {
+        let mut transaction = Self::new();
+
+        for (key, value) in snapshot.metadata() {
+            transaction.add_metadata(key.clone(), value.clone());
+        }
+
+        transaction
+    }
+}
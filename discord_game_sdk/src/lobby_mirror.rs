@@ -0,0 +1,383 @@
+use crate::{Discord, LobbyChange, LobbyID, LobbyMemberSnapshot, LobbySnapshot, Result, UserID};
+use std::collections::{HashMap, HashSet};
+
+/// Local mirror of connected lobbies
+///
+/// Lobby events only carry IDs, this keeps owned snapshots of every lobby, its metadata and
+/// its members so they don't need to be queried again, and reports what changed between
+/// two events.
+///
+/// The mirror is opt-in, events must be forwarded to it from the
+/// [`EventHandler`](trait.EventHandler.html).
+/// The first event received for an unknown lobby starts tracking it and reports no changes,
+/// use [`track`](#method.track) after connecting to a lobby to start earlier.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// #[derive(Default)]
+/// struct MyEventHandler {
+///     lobbies: LobbyMirror,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_lobby_update(&mut self, discord: &Discord<'_, Self>, lobby_id: LobbyID) {
+///         match self.lobbies.on_lobby_update(discord, lobby_id) {
+///             Ok(changes) => {
+///                 for change in changes {
+///                     if let LobbyChange::MetadataChanged { key, old, new } = change {
+///                         println!("{} changed from {:?} to {:?}", key, old, new);
+///                     }
+///                 }
+///             }
+///             Err(error) => eprintln!("failed to refresh lobby: {}", error),
+///         }
+///     }
+///
+///     fn on_lobby_delete(&mut self, discord: &Discord<'_, Self>, lobby_id: LobbyID, reason: u32) {
+///         self.lobbies.on_lobby_delete(lobby_id);
+///     }
+///
+///     // ...
+/// }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LobbyMirror {
+    lobbies: HashMap<LobbyID, LobbySnapshot>,
+}
+
+impl LobbyMirror {
+    /// Creates an empty mirror.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The snapshot of a given lobby, if it is tracked.
+    pub fn lobby(&self, lobby_id: LobbyID) -> Option<&LobbySnapshot> {
+        self.lobbies.get(&lobby_id)
+    }
+
+    /// Returns an `Iterator` over the snapshots of tracked lobbies.
+    pub fn iter_lobbies(&self) -> impl '_ + Iterator<Item = &LobbySnapshot> {
+        self.lobbies.values()
+    }
+
+    /// Starts tracking a given lobby, or refreshes it if it is already tracked.
+    ///
+    /// Call this after [`create_lobby`](struct.Discord.html#method.create_lobby) or
+    /// [`connect_lobby`](struct.Discord.html#method.connect_lobby) succeed.
+    pub fn track<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+    ) -> Result<&LobbySnapshot> {
+        let snapshot = read_lobby(discord, lobby_id, self.lobbies.get(&lobby_id))?;
+
+        let _ = self.lobbies.insert(lobby_id, snapshot);
+
+        Ok(&self.lobbies[&lobby_id])
+    }
+
+    /// Stops tracking a given lobby, returning its last snapshot.
+    ///
+    /// Call this after [`disconnect_lobby`](struct.Discord.html#method.disconnect_lobby) succeeds.
+    pub fn untrack(&mut self, lobby_id: LobbyID) -> Option<LobbySnapshot> {
+        self.lobbies.remove(&lobby_id)
+    }
+
+    /// Refreshes the lobby, its metadata and its list of members.
+    ///
+    /// Forward [`EventHandler::on_lobby_update`](trait.EventHandler.html#method.on_lobby_update) here.
+    pub fn on_lobby_update<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+    ) -> Result<Vec<LobbyChange>> {
+        let snapshot = read_lobby(discord, lobby_id, self.lobbies.get(&lobby_id))?;
+
+        Ok(match self.lobbies.insert(lobby_id, snapshot) {
+            Some(old) => diff(&old, &self.lobbies[&lobby_id]),
+            None => Vec::new(),
+        })
+    }
+
+    /// Stops tracking the lobby.
+    ///
+    /// Forward [`EventHandler::on_lobby_delete`](trait.EventHandler.html#method.on_lobby_delete) here.
+    pub fn on_lobby_delete(&mut self, lobby_id: LobbyID) -> Vec<LobbyChange> {
+        match self.lobbies.remove(&lobby_id) {
+            Some(_) => vec![LobbyChange::Deleted],
+            None => Vec::new(),
+        }
+    }
+
+    /// Adds the member to the lobby.
+    ///
+    /// Forward [`EventHandler::on_member_connect`](trait.EventHandler.html#method.on_member_connect) here.
+    pub fn on_member_connect<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> Result<Vec<LobbyChange>> {
+        self.on_member_update(discord, lobby_id, member_id)
+    }
+
+    /// Refreshes the metadata of the member.
+    ///
+    /// Forward [`EventHandler::on_member_update`](trait.EventHandler.html#method.on_member_update) here.
+    pub fn on_member_update<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> Result<Vec<LobbyChange>> {
+        let snapshot = match self.lobbies.get_mut(&lobby_id) {
+            Some(snapshot) => snapshot,
+            None => return self.track(discord, lobby_id).map(|_| Vec::new()),
+        };
+
+        let member = read_member(discord, lobby_id, member_id)?;
+        let mut changes = Vec::new();
+
+        match snapshot.members.iter_mut().find(|m| m.user_id == member_id) {
+            Some(old) => {
+                diff_member(old, &member, &mut changes);
+                *old = member;
+            }
+
+            None => {
+                changes.push(LobbyChange::MemberConnected(member_id));
+                snapshot.members.push(member);
+            }
+        }
+
+        Ok(changes)
+    }
+
+    /// Removes the member from the lobby.
+    ///
+    /// Forward [`EventHandler::on_member_disconnect`](trait.EventHandler.html#method.on_member_disconnect) here.
+    pub fn on_member_disconnect(
+        &mut self,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> Vec<LobbyChange> {
+        let snapshot = match self.lobbies.get_mut(&lobby_id) {
+            Some(snapshot) => snapshot,
+            None => return Vec::new(),
+        };
+
+        let count = snapshot.members.len();
+        snapshot
+            .members
+            .retain(|member| member.user_id != member_id);
+
+        if snapshot.members.len() < count {
+            vec![LobbyChange::MemberDisconnected(member_id)]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+fn read_member<E>(
+    discord: &Discord<'_, E>,
+    lobby_id: LobbyID,
+    user_id: UserID,
+) -> Result<LobbyMemberSnapshot> {
+    Ok(LobbyMemberSnapshot {
+        user_id,
+        metadata: discord
+            .iter_lobby_member_metadata(lobby_id, user_id)?
+            .collect::<Result<_>>()?,
+    })
+}
+
+// Members that were already known keep their position
+fn read_lobby<E>(
+    discord: &Discord<'_, E>,
+    lobby_id: LobbyID,
+    previous: Option<&LobbySnapshot>,
+) -> Result<LobbySnapshot> {
+    let lobby = discord.lobby(lobby_id)?;
+
+    let metadata = discord
+        .iter_lobby_metadata(lobby_id)?
+        .collect::<Result<_>>()?;
+
+    let member_ids = discord
+        .iter_lobby_member_ids(lobby_id)?
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut ordered = previous
+        .map(|previous| {
+            previous
+                .members
+                .iter()
+                .map(|member| member.user_id)
+                .filter(|user_id| member_ids.contains(user_id))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+
+    for user_id in member_ids {
+        if !ordered.contains(&user_id) {
+            ordered.push(user_id);
+        }
+    }
+
+    let members = ordered
+        .into_iter()
+        .map(|user_id| read_member(discord, lobby_id, user_id))
+        .collect::<Result<_>>()?;
+
+    Ok(LobbySnapshot {
+        lobby,
+        metadata,
+        members,
+    })
+}
+
+fn diff(old: &LobbySnapshot, new: &LobbySnapshot) -> Vec<LobbyChange> {
+    let mut changes = Vec::new();
+
+    if old.lobby.kind() != new.lobby.kind() {
+        changes.push(LobbyChange::KindChanged {
+            old: old.lobby.kind(),
+            new: new.lobby.kind(),
+        });
+    }
+
+    if old.lobby.owner_id() != new.lobby.owner_id() {
+        changes.push(LobbyChange::OwnerChanged {
+            old: old.lobby.owner_id(),
+            new: new.lobby.owner_id(),
+        });
+    }
+
+    if old.lobby.secret() != new.lobby.secret() {
+        changes.push(LobbyChange::SecretChanged);
+    }
+
+    if old.lobby.capacity() != new.lobby.capacity() {
+        changes.push(LobbyChange::CapacityChanged {
+            old: old.lobby.capacity(),
+            new: new.lobby.capacity(),
+        });
+    }
+
+    if old.lobby.locked() != new.lobby.locked() {
+        changes.push(LobbyChange::LockedChanged(new.lobby.locked()));
+    }
+
+    for (key, old, new) in diff_metadata(&old.metadata, &new.metadata) {
+        changes.push(LobbyChange::MetadataChanged { key, old, new });
+    }
+
+    for member in &old.members {
+        if new.member(member.user_id).is_none() {
+            changes.push(LobbyChange::MemberDisconnected(member.user_id));
+        }
+    }
+
+    for member in &new.members {
+        match old.member(member.user_id) {
+            Some(old) => diff_member(old, member, &mut changes),
+            None => changes.push(LobbyChange::MemberConnected(member.user_id)),
+        }
+    }
+
+    changes
+}
+
+fn diff_member(
+    old_member: &LobbyMemberSnapshot,
+    new_member: &LobbyMemberSnapshot,
+    changes: &mut Vec<LobbyChange>,
+) {
+    for (key, old, new) in diff_metadata(&old_member.metadata, &new_member.metadata) {
+        changes.push(LobbyChange::MemberMetadataChanged {
+            user_id: new_member.user_id,
+            key,
+            old,
+            new,
+        });
+    }
+}
+
+// Sorted by key to keep the order of changes stable
+fn diff_metadata(
+    old: &HashMap<String, String>,
+    new: &HashMap<String, String>,
+) -> Vec<(String, Option<String>, Option<String>)> {
+    let mut keys = old
+        .keys()
+        .chain(new.keys())
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .collect::<Vec<_>>();
+    keys.sort();
+
+    keys.into_iter()
+        .filter(|key| old.get(*key) != new.get(*key))
+        .map(|key| (key.clone(), old.get(key).cloned(), new.get(key).cloned()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sys, Lobby};
+
+    fn snapshot(owner_id: UserID, metadata: &[(&str, &str)], members: &[UserID]) -> LobbySnapshot {
+        LobbySnapshot {
+            lobby: Lobby(sys::DiscordLobby {
+                owner_id,
+                ..sys::DiscordLobby::default()
+            }),
+            metadata: metadata
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            members: members
+                .iter()
+                .map(|&user_id| LobbyMemberSnapshot {
+                    user_id,
+                    metadata: HashMap::new(),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = snapshot(1, &[("map", "dust"), ("mode", "ctf")], &[1, 2]);
+        let new = snapshot(2, &[("map", "nuke"), ("round", "1")], &[2, 3]);
+
+        assert_eq!(
+            diff(&old, &new),
+            vec![
+                LobbyChange::OwnerChanged { old: 1, new: 2 },
+                LobbyChange::MetadataChanged {
+                    key: "map".to_string(),
+                    old: Some("dust".to_string()),
+                    new: Some("nuke".to_string()),
+                },
+                LobbyChange::MetadataChanged {
+                    key: "mode".to_string(),
+                    old: Some("ctf".to_string()),
+                    new: None,
+                },
+                LobbyChange::MetadataChanged {
+                    key: "round".to_string(),
+                    old: None,
+                    new: Some("1".to_string()),
+                },
+                LobbyChange::MemberDisconnected(1),
+                LobbyChange::MemberConnected(3),
+            ]
+        );
+
+        assert_eq!(diff(&new, &new), vec![]);
+    }
+}
@@ -0,0 +1,33 @@
+use crate::{Lobby, LobbyMemberSnapshot, UserID};
+use std::collections::HashMap;
+
+/// Owned copy of a lobby, its metadata and its members, kept by
+/// [`LobbyMirror`](struct.LobbyMirror.html)
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LobbySnapshot {
+    pub(crate) lobby: Lobby,
+    pub(crate) metadata: HashMap<String, String>,
+    pub(crate) members: Vec<LobbyMemberSnapshot>,
+}
+
+impl LobbySnapshot {
+    /// The lobby
+    pub fn lobby(&self) -> &Lobby {
+        &self.lobby
+    }
+
+    /// The metadata of the lobby
+    pub fn metadata(&self) -> &HashMap<String, String> {
+        &self.metadata
+    }
+
+    /// The members of the lobby, in the order they were first seen
+    pub fn members(&self) -> &[LobbyMemberSnapshot] {
+        &self.members
+    }
+
+    /// The member with the given ID
+    pub fn member(&self, user_id: UserID) -> Option<&LobbyMemberSnapshot> {
+        self.members.iter().find(|member| member.user_id == user_id)
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{sys, to_result::ToResult, LobbyKind, Result, UserID};
+use crate::{sys, to_result::ToResult, LobbyKind, LobbySnapshot, Metadata, Result, UserID};
 use std::collections::HashMap;
 
 /// Lobby Transaction
@@ -66,6 +66,17 @@ impl LobbyTransaction {
         self
     }
 
+    /// Sets every metadata value of `metadata` for the lobby
+    ///
+    /// See [`Metadata`](trait.Metadata.html).
+    pub fn add_typed_metadata(&mut self, metadata: &impl Metadata) -> &mut Self {
+        for (key, value) in metadata.to_metadata() {
+            self.add_metadata(key, value);
+        }
+
+        self
+    }
+
     /// Deletes metadata value under a given key for the lobby
     ///
     /// ## Performance
@@ -73,7 +84,7 @@ impl LobbyTransaction {
     /// A nul byte will be appended to `key` if one is not present.
     ///
     /// > [Method in official docs](https://discordapp.com/developers/docs/game-sdk/lobbies#lobbytransactiondeletemetadata)
-    pub fn delete_metadata<S>(&mut self, mut key: String) -> &mut Self {
+    pub fn delete_metadata(&mut self, mut key: String) -> &mut Self {
         if !key.ends_with('\0') {
             key.push('\0')
         }
@@ -90,6 +101,55 @@ impl LobbyTransaction {
         self
     }
 
+    /// Builds the smallest transaction that turns the state described by `old` into `new`
+    ///
+    /// Both transactions describe a full lobby state, as if used to create the lobby.
+    /// Settings are only included if they are set in `new` and differ from `old`,
+    /// metadata keys set in `old` but absent from `new` are deleted.
+    ///
+    /// ```rust
+    /// # use discord_game_sdk::*;
+    /// # fn example(discord: Discord<'_, ()>, lobby_id: LobbyID) -> Result<()> {
+    /// let mut old = LobbyTransaction::new();
+    /// old.capacity(8).add_metadata("map".into(), "dust".into());
+    ///
+    /// let mut new = old.clone();
+    /// new.locked(true).add_metadata("round".into(), "1".into());
+    ///
+    /// let diff = LobbyTransaction::diff(&old, &new);
+    ///
+    /// if !diff.is_empty() {
+    ///     discord.update_lobby(lobby_id, &diff, |discord, result| {
+    ///         if let Err(error) = result {
+    ///             eprintln!("failed to update lobby: {}", error);
+    ///         }
+    ///     });
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn diff(old: &Self, new: &Self) -> Self {
+        fn changed<T: Copy + PartialEq>(old: Option<T>, new: Option<T>) -> Option<T> {
+            new.filter(|new| old != Some(*new))
+        }
+
+        Self {
+            kind: changed(old.kind, new.kind),
+            owner: changed(old.owner, new.owner),
+            capacity: changed(old.capacity, new.capacity),
+            locked: changed(old.locked, new.locked),
+            metadata: diff_metadata(&old.metadata, &new.metadata),
+        }
+    }
+
+    /// Whether applying the transaction would change nothing
+    pub fn is_empty(&self) -> bool {
+        self.kind.is_none()
+            && self.owner.is_none()
+            && self.capacity.is_none()
+            && self.locked.is_none()
+            && self.metadata.is_empty()
+    }
+
     pub(crate) unsafe fn process(&self, tx: *mut sys::IDiscordLobbyTransaction) -> Result<()> {
         if let Some(kind) = self.kind {
             (*tx).set_type.unwrap()(tx, kind.into()).to_result()?;
@@ -134,3 +194,73 @@ impl LobbyTransaction {
         Ok(())
     }
 }
+
+impl From<&LobbySnapshot> for LobbyTransaction {
+    fn from(snapshot: &LobbySnapshot) -> Self {
+        let lobby = snapshot.lobby();
+        let mut transaction = Self::new();
+
+        transaction
+            .kind(lobby.kind())
+            .owner(lobby.owner_id())
+            .capacity(lobby.capacity())
+            .locked(lobby.locked());
+
+        for (key, value) in snapshot.metadata() {
+            transaction.add_metadata(key.clone(), value.clone());
+        }
+
+        transaction
+    }
+}
+
+pub(crate) fn diff_metadata(
+    old: &HashMap<String, Option<String>>,
+    new: &HashMap<String, Option<String>>,
+) -> HashMap<String, Option<String>> {
+    let mut metadata = HashMap::new();
+
+    for (key, value) in new {
+        if value.is_some() && old.get(key) != Some(value) {
+            let _ = metadata.insert(key.clone(), value.clone());
+        }
+    }
+
+    for (key, value) in old {
+        if value.is_some() && !matches!(new.get(key), Some(Some(_))) {
+            let _ = metadata.insert(key.clone(), None);
+        }
+    }
+
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let mut old = LobbyTransaction::new();
+        old.capacity(8)
+            .locked(false)
+            .add_metadata("map".into(), "dust".into())
+            .add_metadata("mode".into(), "ctf".into());
+
+        let mut new = LobbyTransaction::new();
+        new.capacity(8)
+            .locked(true)
+            .add_metadata("map".into(), "dust".into())
+            .add_metadata("round".into(), "1".into());
+
+        let diff = LobbyTransaction::diff(&old, &new);
+
+        assert_eq!(diff.capacity, None);
+        assert_eq!(diff.locked, Some(true));
+        assert_eq!(diff.metadata.len(), 2);
+        assert_eq!(diff.metadata["mode\0"], None);
+        assert_eq!(diff.metadata["round\0"], Some("1\0".to_string()));
+
+        assert!(LobbyTransaction::diff(&new, &new).is_empty());
+    }
+}
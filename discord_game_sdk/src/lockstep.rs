@@ -0,0 +1,713 @@
+use crate::{
+    Discord, LobbyID, LockstepEvent, LockstepTick, NetworkChannelID, Reliability, Result, UserID,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    convert::TryInto,
+};
+
+const INPUT: u8 = 0;
+const CHECKSUM: u8 = 1;
+const DROP: u8 = 2;
+const RELAY: u8 = 3;
+
+const HEADER_LEN: usize = 9;
+
+// Ticks of inputs and checksums kept after simulating them, to relay inputs and compare late checksums
+const HISTORY: LockstepTick = 256;
+
+/// Deterministic lockstep session over lobby networking
+///
+/// Every member submits one input per tick, which is scheduled a few ticks ahead to hide latency,
+/// and sent to the other members over a reliable lobby network channel. A tick is only produced
+/// once the input of every member arrived, so all members simulate the same inputs in the same order.
+/// The ticks before the input delay have no input.
+///
+/// When a member disconnects, the remaining members exchange the number of inputs they received
+/// from them, and relay the inputs that others missed. Ticks after the last input received by
+/// any remaining member are then simulated without the member.
+///
+/// Members can exchange a checksum of their state after simulating a tick to detect desyncs.
+///
+/// The members of the session are the members of the lobby when it starts.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// struct MyEventHandler {
+///     lockstep: Lockstep,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_lobby_network_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///         channel_id: NetworkChannelID,
+///         data: &[u8],
+///     ) {
+///         self.lockstep
+///             .on_lobby_network_message(discord, lobby_id, member_id, channel_id, data);
+///     }
+///
+///     fn on_member_disconnect(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///     ) {
+///         let _ = self.lockstep.on_member_disconnect(discord, lobby_id, member_id);
+///     }
+///
+///     // ...
+/// }
+///
+/// # fn example(mut discord: Discord<'_, MyEventHandler>, lockstep: &mut Lockstep) -> Result<()> {
+/// // Once every member is in the lobby
+/// lockstep.input_delay(3).start(&discord)?;
+///
+/// // Every frame
+/// discord.run_callbacks()?;
+/// lockstep.submit(&discord, b"move 3 4")?;
+///
+/// for event in lockstep.poll() {
+///     match event {
+///         LockstepEvent::Tick { tick, inputs } => {
+///             // Simulate the inputs, then
+///             lockstep.checksum(&discord, tick, 0xC0FFEE)?;
+///         }
+///
+///         LockstepEvent::Dropped { member_id, .. } => println!("{} dropped out", member_id),
+///
+///         LockstepEvent::Desync { tick, member_id } => {
+///             eprintln!("desync with {} at tick {}", member_id, tick);
+///         }
+///     }
+/// }
+///
+/// discord.flush_lobby_network()?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Lockstep {
+    lobby_id: LobbyID,
+    channel_id: NetworkChannelID,
+    input_delay: LockstepTick,
+    user_id: UserID,
+    next_tick: LockstepTick,
+    members: BTreeMap<UserID, Member>,
+    checksums: BTreeMap<LockstepTick, u64>,
+    remote_checksums: BTreeMap<LockstepTick, Vec<(UserID, u64)>>,
+    events: Vec<LockstepEvent>,
+}
+
+#[derive(Clone, Debug)]
+struct Member {
+    inputs: BTreeMap<LockstepTick, Vec<u8>>,
+    // Number of inputs received, including the ticks before the input delay
+    received: LockstepTick,
+    drop: Option<DropOut>,
+}
+
+#[derive(Clone, Debug)]
+struct DropOut {
+    reports: HashMap<UserID, LockstepTick>,
+    last_tick: Option<LockstepTick>,
+}
+
+impl Lockstep {
+    /// Creates a session in a given lobby and channel ID, with an input delay of 2 ticks.
+    pub fn new(lobby_id: LobbyID, channel_id: NetworkChannelID) -> Self {
+        Self {
+            lobby_id,
+            channel_id,
+            input_delay: 2,
+            user_id: 0,
+            next_tick: 0,
+            members: BTreeMap::new(),
+            checksums: BTreeMap::new(),
+            remote_checksums: BTreeMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Sets the number of ticks between the submission of an input and its simulation.
+    ///
+    /// Must be the same for every member and set before the session starts.
+    pub fn input_delay(&mut self, input_delay: LockstepTick) -> &mut Self {
+        self.input_delay = input_delay;
+        self
+    }
+
+    /// The next tick to be produced by [`poll`](#method.poll)
+    pub fn tick(&self) -> LockstepTick {
+        self.next_tick
+    }
+
+    /// Returns an `Iterator` over the members taking part in the session, including dropping members.
+    pub fn iter_members(&self) -> impl '_ + Iterator<Item = UserID> {
+        self.members.keys().copied()
+    }
+
+    /// Connects to the lobby network, opens the channel and starts the session
+    /// with the current members of the lobby.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if the lobby, its members or the current user are not available,
+    /// or if the network cannot be connected.
+    pub fn start<E>(&mut self, discord: &Discord<'_, E>) -> Result<()> {
+        self.user_id = discord.current_user()?.id();
+
+        discord.connect_lobby_network(self.lobby_id)?;
+        discord.open_lobby_network_channel(
+            self.lobby_id,
+            self.channel_id,
+            Reliability::Reliable,
+        )?;
+
+        let mut members = Vec::new();
+
+        for member_id in discord.iter_lobby_member_ids(self.lobby_id)? {
+            members.push(member_id?);
+        }
+
+        self.reset(self.user_id, members);
+
+        Ok(())
+    }
+
+    /// Schedules the input of the current user `input_delay` ticks ahead of the next tick,
+    /// and sends it to the other members.
+    ///
+    /// Returns the tick of the input, or `None` if the input of that tick was already submitted.
+    ///
+    /// ## Errors
+    ///
+    /// The input is scheduled and sent to every member even if sending fails, the first error is returned.
+    pub fn submit<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        input: &[u8],
+    ) -> Result<Option<LockstepTick>> {
+        let tick = match self.schedule(input) {
+            Some(tick) => tick,
+            None => return Ok(None),
+        };
+
+        self.broadcast(discord, &packet(INPUT, tick, input))?;
+
+        Ok(Some(tick))
+    }
+
+    /// Records the checksum of the state after simulating a tick, and sends it to the other members.
+    ///
+    /// ## Errors
+    ///
+    /// The checksum is sent to every member even if sending fails, the first error is returned.
+    pub fn checksum<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        tick: LockstepTick,
+        checksum: u64,
+    ) -> Result<()> {
+        self.record_checksum(tick, checksum);
+
+        self.broadcast(discord, &packet(CHECKSUM, tick, &checksum.to_le_bytes()))
+    }
+
+    /// Returns the desyncs and drop-outs detected, followed by every tick whose inputs all arrived.
+    ///
+    /// Call this every frame after [`run_callbacks`](struct.Discord.html#method.run_callbacks).
+    pub fn poll(&mut self) -> Vec<LockstepEvent> {
+        let mut events = std::mem::take(&mut self.events);
+
+        while let Some(inputs) = self.take_inputs() {
+            events.push(LockstepEvent::Tick {
+                tick: self.next_tick,
+                inputs,
+            });
+
+            self.next_tick += 1;
+        }
+
+        events
+    }
+
+    /// Records inputs, checksums and drop-out reports of the other members,
+    /// and relays the inputs of members that dropped out.
+    ///
+    /// Messages received on other lobbies or channels are ignored, malformed messages
+    /// and failures to send are logged.
+    ///
+    /// Forward [`EventHandler::on_lobby_network_message`](trait.EventHandler.html#method.on_lobby_network_message) here.
+    pub fn on_lobby_network_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) {
+        if lobby_id != self.lobby_id || channel_id != self.channel_id {
+            return;
+        }
+
+        for (recipient, data) in self.receive(member_id, data) {
+            let sent = match recipient {
+                Some(recipient) => discord.send_lobby_network_message(
+                    self.lobby_id,
+                    recipient,
+                    self.channel_id,
+                    &data,
+                ),
+                None => self.broadcast(discord, &data),
+            };
+
+            if let Err(error) = sent {
+                log::warn!("failed to send lockstep message: {}", error);
+            }
+        }
+    }
+
+    /// Starts agreeing on the last tick of a member that disconnected.
+    ///
+    /// Forward [`EventHandler::on_member_disconnect`](trait.EventHandler.html#method.on_member_disconnect) here.
+    ///
+    /// ## Errors
+    ///
+    /// The report is sent to every member even if sending fails, the first error is returned.
+    pub fn on_member_disconnect<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> Result<()> {
+        if lobby_id != self.lobby_id {
+            return Ok(());
+        }
+
+        match self.drop_member(member_id) {
+            Some(report) => self.broadcast(discord, &report),
+            None => Ok(()),
+        }
+    }
+
+    fn reset(&mut self, user_id: UserID, members: Vec<UserID>) {
+        self.user_id = user_id;
+        self.next_tick = 0;
+        self.checksums.clear();
+        self.remote_checksums.clear();
+        self.events.clear();
+
+        self.members = members
+            .into_iter()
+            .chain(Some(user_id))
+            .map(|member_id| {
+                let member = Member {
+                    inputs: BTreeMap::new(),
+                    received: self.input_delay,
+                    drop: None,
+                };
+
+                (member_id, member)
+            })
+            .collect();
+    }
+
+    fn broadcast<E>(&self, discord: &Discord<'_, E>, data: &[u8]) -> Result<()> {
+        let mut result = Ok(());
+
+        for (member_id, member) in &self.members {
+            if *member_id != self.user_id && member.drop.is_none() {
+                result = result.and(discord.send_lobby_network_message(
+                    self.lobby_id,
+                    *member_id,
+                    self.channel_id,
+                    data,
+                ));
+            }
+        }
+
+        result
+    }
+
+    fn schedule(&mut self, input: &[u8]) -> Option<LockstepTick> {
+        let limit = self.next_tick + self.input_delay;
+        let member = self.members.get_mut(&self.user_id)?;
+
+        if member.received > limit {
+            return None;
+        }
+
+        let tick = member.received;
+        let _ = member.inputs.insert(tick, input.to_vec());
+        member.received += 1;
+
+        Some(tick)
+    }
+
+    fn record_checksum(&mut self, tick: LockstepTick, checksum: u64) {
+        let _ = self.checksums.insert(tick, checksum);
+
+        while self.checksums.len() as LockstepTick > HISTORY {
+            let oldest = *self.checksums.keys().next().unwrap();
+            let _ = self.checksums.remove(&oldest);
+        }
+
+        for (member_id, remote) in self.remote_checksums.remove(&tick).unwrap_or_default() {
+            self.compare_checksum(tick, member_id, remote);
+        }
+    }
+
+    fn compare_checksum(&mut self, tick: LockstepTick, member_id: UserID, remote: u64) {
+        match self.checksums.get(&tick) {
+            Some(local) if *local != remote => {
+                self.events.push(LockstepEvent::Desync { tick, member_id })
+            }
+
+            Some(_) => {}
+
+            // Checksums too old to be compared are ignored
+            None if tick.saturating_add(HISTORY) < self.next_tick => {}
+
+            // Members cannot have simulated ticks this far ahead
+            None if tick > self.next_tick + self.input_delay + HISTORY => {
+                log::warn!("dropped checksum for tick {} from {}", tick, member_id)
+            }
+
+            None => self
+                .remote_checksums
+                .entry(tick)
+                .or_default()
+                .push((member_id, remote)),
+        }
+    }
+
+    fn drop_member(&mut self, member_id: UserID) -> Option<Vec<u8>> {
+        let user_id = self.user_id;
+        let member = self.members.get_mut(&member_id)?;
+
+        if member_id == user_id || member.drop.is_some() {
+            return None;
+        }
+
+        let mut reports = HashMap::new();
+        let _ = reports.insert(user_id, member.received);
+
+        member.drop = Some(DropOut {
+            reports,
+            last_tick: None,
+        });
+
+        let report = packet(DROP, member.received, &member_id.to_le_bytes());
+
+        self.settle_drops();
+
+        Some(report)
+    }
+
+    // Agrees on the last tick of dropping members once every remaining member reported,
+    // the members that received fewer inputs get the others relayed
+    fn settle_drops(&mut self) {
+        let remaining = self
+            .members
+            .iter()
+            .filter(|(_, member)| member.drop.is_none())
+            .map(|(member_id, _)| *member_id)
+            .collect::<Vec<_>>();
+
+        for (member_id, member) in &mut self.members {
+            let drop = match &mut member.drop {
+                Some(drop) if drop.last_tick.is_none() => drop,
+                _ => continue,
+            };
+
+            let received = remaining
+                .iter()
+                .map(|reporter| drop.reports.get(reporter).copied())
+                .collect::<Option<Vec<_>>>();
+
+            if let Some(tick) = received.and_then(|received| received.into_iter().max()) {
+                drop.last_tick = Some(tick);
+
+                self.events.push(LockstepEvent::Dropped {
+                    member_id: *member_id,
+                    tick,
+                });
+            }
+        }
+    }
+
+    // Returns the messages to send in response, to a member or to every member
+    fn receive(&mut self, member_id: UserID, data: &[u8]) -> Vec<(Option<UserID>, Vec<u8>)> {
+        let mut responses = Vec::new();
+
+        if data.len() < HEADER_LEN {
+            log::warn!("dropped truncated lockstep message from {}", member_id);
+            return responses;
+        }
+
+        let kind = data[0];
+        let tick = LockstepTick::from_le_bytes(data[1..HEADER_LEN].try_into().unwrap());
+        let payload = &data[HEADER_LEN..];
+
+        if !self.members.contains_key(&member_id) || member_id == self.user_id {
+            log::warn!("dropped lockstep message from non-member {}", member_id);
+            return responses;
+        }
+
+        match kind {
+            INPUT => self.record_input(member_id, tick, payload),
+
+            CHECKSUM => match payload.try_into() {
+                Ok(checksum) => {
+                    self.compare_checksum(tick, member_id, u64::from_le_bytes(checksum))
+                }
+                Err(_) => log::warn!("dropped malformed checksum from {}", member_id),
+            },
+
+            DROP => {
+                let dropped = match payload.try_into() {
+                    Ok(dropped) => UserID::from_le_bytes(dropped),
+                    Err(_) => {
+                        log::warn!("dropped malformed drop-out report from {}", member_id);
+                        return responses;
+                    }
+                };
+
+                // Members may learn of a drop-out from the report of another member first
+                // Members cannot have received inputs this far ahead
+                match self.members.get(&dropped) {
+                    Some(member) if tick > member.received.saturating_add(HISTORY) => {
+                        log::warn!(
+                            "dropped drop-out report for tick {} from {}",
+                            tick,
+                            member_id
+                        );
+                        return responses;
+                    }
+                    _ => {}
+                }
+
+                if let Some(report) = self.drop_member(dropped) {
+                    responses.push((None, report));
+                }
+
+                if let Some(member) = self.members.get_mut(&dropped) {
+                    if let Some(drop) = &mut member.drop {
+                        let _ = drop.reports.insert(member_id, tick);
+                    }
+
+                    for missed in tick..member.received {
+                        match member.inputs.get(&missed) {
+                            Some(input) => {
+                                let mut payload = dropped.to_le_bytes().to_vec();
+                                payload.extend_from_slice(input);
+
+                                responses.push((Some(member_id), packet(RELAY, missed, &payload)));
+                            }
+
+                            None => log::warn!(
+                                "input of {} for tick {} is too old to be relayed",
+                                dropped,
+                                missed
+                            ),
+                        }
+                    }
+                }
+
+                self.settle_drops();
+            }
+
+            RELAY if payload.len() >= 8 => {
+                let dropped = UserID::from_le_bytes(payload[..8].try_into().unwrap());
+
+                match self.members.get(&dropped) {
+                    Some(member) if member.drop.is_some() => {
+                        self.record_input(dropped, tick, &payload[8..])
+                    }
+                    _ => log::warn!("dropped relayed input of non-dropping member {}", dropped),
+                }
+            }
+
+            _ => log::warn!("dropped malformed lockstep message from {}", member_id),
+        }
+
+        responses
+    }
+
+    fn record_input(&mut self, member_id: UserID, tick: LockstepTick, input: &[u8]) {
+        // Members cannot have scheduled inputs this far ahead
+        if tick > self.next_tick + self.input_delay + HISTORY {
+            log::warn!("dropped input for tick {} from {}", tick, member_id);
+            return;
+        }
+
+        let member = self.members.get_mut(&member_id).unwrap();
+
+        // Relayed inputs may already have arrived from the member
+        if tick < member.received {
+            return;
+        }
+
+        if tick != member.received {
+            log::warn!(
+                "dropped input for tick {} from {}, expected tick {}",
+                tick,
+                member_id,
+                member.received
+            );
+            return;
+        }
+
+        let _ = member.inputs.insert(tick, input.to_vec());
+        member.received += 1;
+    }
+
+    fn take_inputs(&mut self) -> Option<Vec<(UserID, Vec<u8>)>> {
+        let tick = self.next_tick;
+
+        let ready = self.members.values().all(|member| match &member.drop {
+            Some(DropOut {
+                last_tick: Some(last_tick),
+                ..
+            }) if tick >= *last_tick => true,
+            _ => tick < member.received,
+        });
+
+        if !ready {
+            return None;
+        }
+
+        let inputs = self
+            .members
+            .iter_mut()
+            .filter_map(|(member_id, member)| {
+                if tick >= HISTORY {
+                    let _ = member.inputs.remove(&(tick - HISTORY));
+                }
+
+                match member.drop {
+                    Some(DropOut {
+                        last_tick: Some(last_tick),
+                        ..
+                    }) if tick >= last_tick => None,
+                    _ => Some((
+                        *member_id,
+                        member.inputs.get(&tick).cloned().unwrap_or_default(),
+                    )),
+                }
+            })
+            .collect();
+
+        // Members that dropped out no longer take part once their last tick was simulated
+        self.members.retain(|_, member| match member.drop {
+            Some(DropOut {
+                last_tick: Some(last_tick),
+                ..
+            }) => tick + 1 < last_tick,
+            _ => true,
+        });
+
+        Some(inputs)
+    }
+}
+
+fn packet(kind: u8, tick: LockstepTick, payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(HEADER_LEN + payload.len());
+    packet.push(kind);
+    packet.extend_from_slice(&tick.to_le_bytes());
+    packet.extend_from_slice(payload);
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockstep() {
+        let mut lockstep = Lockstep::new(0, 0);
+        lockstep.input_delay(1).reset(1, vec![1, 2, 3]);
+
+        assert_eq!(
+            lockstep.poll(),
+            vec![LockstepEvent::Tick {
+                tick: 0,
+                inputs: vec![(1, vec![]), (2, vec![]), (3, vec![])],
+            }]
+        );
+
+        assert_eq!(lockstep.schedule(b"a"), Some(1));
+        assert_eq!(lockstep.schedule(b"b"), Some(2));
+        assert_eq!(lockstep.schedule(b"x"), None);
+        assert!(lockstep.receive(2, &packet(INPUT, 1, b"c")).is_empty());
+        assert!(lockstep.receive(2, &packet(INPUT, 5, b"d")).is_empty());
+
+        // Inputs sent in order but far ahead of the session are not kept
+        let mut flooding = Lockstep::new(0, 0);
+        flooding.reset(1, vec![1, 2]);
+        let limit = flooding.input_delay + HISTORY;
+        for tick in flooding.input_delay..limit + 2 {
+            assert!(flooding.receive(2, &packet(INPUT, tick, b"z")).is_empty());
+        }
+        assert_eq!(flooding.members[&2].received, limit + 1);
+        assert!(lockstep
+            .receive(2, &packet(DROP, LockstepTick::MAX, &3i64.to_le_bytes()))
+            .is_empty());
+        assert!(lockstep.members[&3].drop.is_none());
+        assert!(lockstep.receive(3, &packet(INPUT, 1, b"e")).is_empty());
+        assert!(lockstep.receive(3, &packet(INPUT, 2, b"f")).is_empty());
+        assert_eq!(lockstep.poll().len(), 1);
+
+        // Member 2 missed the last input of member 3, which is relayed to them
+        let report = packet(DROP, 3, &3i64.to_le_bytes());
+        assert_eq!(lockstep.drop_member(3), Some(report));
+        assert_eq!(
+            lockstep.receive(2, &packet(DROP, 2, &3i64.to_le_bytes())),
+            vec![(
+                Some(2),
+                packet(RELAY, 2, &[&3i64.to_le_bytes()[..], b"f"].concat())
+            )]
+        );
+
+        lockstep.receive(2, &packet(INPUT, 2, b"h"));
+        lockstep.receive(2, &packet(INPUT, 3, b"i"));
+        assert_eq!(lockstep.schedule(b"j"), Some(3));
+
+        assert_eq!(
+            lockstep.poll(),
+            vec![
+                LockstepEvent::Dropped {
+                    member_id: 3,
+                    tick: 3
+                },
+                LockstepEvent::Tick {
+                    tick: 2,
+                    inputs: vec![(1, b"b".to_vec()), (2, b"h".to_vec()), (3, b"f".to_vec())],
+                },
+                LockstepEvent::Tick {
+                    tick: 3,
+                    inputs: vec![(1, b"j".to_vec()), (2, b"i".to_vec())],
+                }
+            ]
+        );
+        assert_eq!(lockstep.iter_members().collect::<Vec<_>>(), vec![1, 2]);
+
+        lockstep.receive(2, &packet(CHECKSUM, 1, &7u64.to_le_bytes()));
+        lockstep.record_checksum(1, 8);
+        lockstep.record_checksum(0, 9);
+        lockstep.receive(2, &packet(CHECKSUM, 0, &9u64.to_le_bytes()));
+        lockstep.receive(2, &packet(CHECKSUM, HISTORY * 2, &9u64.to_le_bytes()));
+        lockstep.receive(2, &packet(CHECKSUM, LockstepTick::MAX, &9u64.to_le_bytes()));
+        assert!(lockstep.remote_checksums.is_empty());
+        assert_eq!(
+            lockstep.poll(),
+            vec![LockstepEvent::Desync {
+                tick: 1,
+                member_id: 2
+            }]
+        );
+    }
+}
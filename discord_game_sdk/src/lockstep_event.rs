@@ -0,0 +1,29 @@
+use crate::{LockstepTick, UserID};
+
+/// Event produced by a [`Lockstep`](struct.Lockstep.html) session
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum LockstepEvent {
+    /// Every input of a tick arrived, the tick can be simulated
+    Tick {
+        /// The tick
+        tick: LockstepTick,
+        /// The input of every member taking part in the tick, ordered by user ID
+        inputs: Vec<(UserID, Vec<u8>)>,
+    },
+
+    /// The remaining members agreed on the last tick of a member that dropped out
+    Dropped {
+        /// The member
+        member_id: UserID,
+        /// The first tick simulated without the member
+        tick: LockstepTick,
+    },
+
+    /// A member reported a different checksum than the current user for a tick
+    Desync {
+        /// The tick
+        tick: LockstepTick,
+        /// The member
+        member_id: UserID,
+    },
+}
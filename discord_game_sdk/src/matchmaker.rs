@@ -0,0 +1,299 @@
+use crate::{
+    Cast, Comparison, Discord, Distance, Error, LobbyID, LobbyTransaction, MatchmakingOutcome,
+    Result, SearchQuery,
+};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+const DISTANCES: [Distance; 4] = [
+    Distance::Local,
+    Distance::Default,
+    Distance::Extended,
+    Distance::Global,
+];
+
+type Score = dyn Fn(LobbyID, &HashMap<String, String>) -> Option<f64>;
+
+type Callback<'d, E> = Box<dyn 'd + FnOnce(&Discord<'d, E>, Result<MatchmakingOutcome>)>;
+
+/// Widening lobby matchmaking
+///
+/// Runs [`lobby_search`](struct.Discord.html#method.lobby_search) in stages.
+/// The first stage searches [`Distance::Local`](enum.Distance.html#variant.Local) lobbies,
+/// every following stage widens the distance up to [`Distance::Global`](enum.Distance.html#variant.Global)
+/// and relaxes the numeric ranges added with [`range`](#method.range).
+///
+/// Lobbies found at each stage are ranked with the scoring function over their metadata,
+/// then joined in order: a candidate that is full or whose secret changed is skipped for the next one.
+/// When every stage is exhausted, a new lobby is created from the template.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # fn example(discord: Discord<'_, ()>) -> Result<()> {
+/// let mut base = SearchQuery::new();
+/// base.filter("mode".into(), Comparison::Equal, "ranked".into(), Cast::String);
+///
+/// let mut template = LobbyTransaction::new();
+/// template
+///     .capacity(8)
+///     .add_metadata("mode".into(), "ranked".into())
+///     .add_metadata("skill".into(), "1200".into());
+///
+/// Matchmaker::new(base, template)
+///     .range("skill".into(), 1200.0, 50.0, 100.0)
+///     .score(|_, metadata| {
+///         let skill: f64 = metadata.get("skill")?.parse().ok()?;
+///         Some(-(skill - 1200.0).abs())
+///     })
+///     .start(&discord, |discord, result| match result {
+///         Ok(MatchmakingOutcome::Joined(lobby)) => println!("joined lobby {}", lobby.id()),
+///         Ok(MatchmakingOutcome::Created(lobby)) => println!("created lobby {}", lobby.id()),
+///         Err(error) => eprintln!("failed to find a lobby: {}", error),
+///     });
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct Matchmaker {
+    query: SearchQuery,
+    template: LobbyTransaction,
+    ranges: Vec<(String, f64, f64, f64)>,
+    relaxations: u32,
+    score: Rc<Score>,
+}
+
+impl Matchmaker {
+    /// Creates a matchmaker searching with `query` and creating lobbies from `template`.
+    ///
+    /// The distance of `query` is overridden at every stage.
+    pub fn new(query: SearchQuery, template: LobbyTransaction) -> Self {
+        Self {
+            query,
+            template,
+            ranges: Vec::new(),
+            relaxations: 0,
+            score: Rc::new(|_, _| Some(0.0)),
+        }
+    }
+
+    /// Only matches lobbies whose numeric metadata under `key` is within `tolerance` of `target`.
+    ///
+    /// The tolerance grows by `widening` at every stage.
+    pub fn range(&mut self, key: String, target: f64, tolerance: f64, widening: f64) -> &mut Self {
+        self.ranges.push((key, target, tolerance, widening));
+        self
+    }
+
+    /// Adds stages after reaching [`Distance::Global`](enum.Distance.html#variant.Global)
+    /// that keep relaxing numeric ranges.
+    ///
+    /// Defaults to 0.
+    pub fn relaxations(&mut self, relaxations: u32) -> &mut Self {
+        self.relaxations = relaxations;
+        self
+    }
+
+    /// Ranks candidates, higher scores are joined first and `None` rejects the lobby,
+    /// as do infinite and NaN scores.
+    ///
+    /// Every lobby scores equally by default.
+    pub fn score(
+        &mut self,
+        score: impl 'static + Fn(LobbyID, &HashMap<String, String>) -> Option<f64>,
+    ) -> &mut Self {
+        self.score = Rc::new(score);
+        self
+    }
+
+    /// Runs the matchmaking until a lobby is joined or created.
+    ///
+    /// ## Errors
+    ///
+    /// Errors other than [`Error::LobbyFull`](enum.Error.html#variant.LobbyFull) and
+    /// [`Error::InvalidLobbySecret`](enum.Error.html#variant.InvalidLobbySecret)
+    /// stop the matchmaking and are passed to `callback`.
+    pub fn start<'d, E: 'd>(
+        &self,
+        discord: &Discord<'d, E>,
+        callback: impl 'd + FnOnce(&Discord<'d, E>, Result<MatchmakingOutcome>),
+    ) {
+        search(
+            discord,
+            Attempt {
+                matchmaker: self.clone(),
+                stage: 0,
+                tried: HashSet::new(),
+                callback: Box::new(callback),
+            },
+        )
+    }
+
+    fn stages(&self) -> u32 {
+        DISTANCES.len() as u32 + self.relaxations
+    }
+
+    fn stage_query(&self, stage: u32) -> SearchQuery {
+        let mut query = self.query.clone();
+
+        query.distance(DISTANCES[(stage as usize).min(DISTANCES.len() - 1)]);
+
+        for (key, target, tolerance, widening) in &self.ranges {
+            let tolerance = tolerance + widening * f64::from(stage);
+
+            query
+                .filter(
+                    key.clone(),
+                    Comparison::GreaterThanOrEqual,
+                    (target - tolerance).to_string(),
+                    Cast::Number,
+                )
+                .filter(
+                    key.clone(),
+                    Comparison::LessThanOrEqual,
+                    (target + tolerance).to_string(),
+                    Cast::Number,
+                );
+        }
+
+        query
+    }
+}
+
+impl std::fmt::Debug for Matchmaker {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("Matchmaker")
+            .field("query", &self.query)
+            .field("template", &self.template)
+            .field("ranges", &self.ranges)
+            .field("relaxations", &self.relaxations)
+            .finish()
+    }
+}
+
+struct Attempt<'d, E> {
+    matchmaker: Matchmaker,
+    stage: u32,
+    tried: HashSet<LobbyID>,
+    callback: Callback<'d, E>,
+}
+
+fn search<'d, E: 'd>(discord: &Discord<'d, E>, attempt: Attempt<'d, E>) {
+    if attempt.stage >= attempt.matchmaker.stages() {
+        return create(discord, attempt);
+    }
+
+    let query = attempt.matchmaker.stage_query(attempt.stage);
+
+    discord.lobby_search(&query, move |discord, result| {
+        if let Err(error) = result {
+            return (attempt.callback)(discord, Err(error));
+        }
+
+        let mut candidates = Vec::new();
+
+        for lobby_id in discord.iter_lobbies() {
+            let lobby_id = match lobby_id {
+                Ok(lobby_id) => lobby_id,
+                Err(error) => return (attempt.callback)(discord, Err(error)),
+            };
+
+            if attempt.tried.contains(&lobby_id) {
+                continue;
+            }
+
+            let metadata = discord
+                .iter_lobby_metadata(lobby_id)
+                .and_then(|iter| iter.collect::<Result<HashMap<_, _>>>());
+
+            // The lobby may have been deleted since the search returned
+            let metadata = match metadata {
+                Ok(metadata) => metadata,
+                Err(_) => continue,
+            };
+
+            // Scores that cannot be ranked, such as NaN, reject the lobby
+            match (attempt.matchmaker.score)(lobby_id, &metadata) {
+                Some(score) if score.is_finite() => candidates.push((lobby_id, score)),
+                _ => {}
+            }
+        }
+
+        candidates.sort_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+
+        connect(
+            discord,
+            attempt,
+            candidates.into_iter().map(|(id, _)| id).collect(),
+        )
+    });
+}
+
+// `candidates` is ordered worst first, so the best one can be popped
+fn connect<'d, E: 'd>(
+    discord: &Discord<'d, E>,
+    mut attempt: Attempt<'d, E>,
+    mut candidates: Vec<LobbyID>,
+) {
+    let lobby_id = match candidates.pop() {
+        Some(lobby_id) => lobby_id,
+        None => {
+            attempt.stage += 1;
+            return search(discord, attempt);
+        }
+    };
+
+    let _ = attempt.tried.insert(lobby_id);
+
+    let secret = match discord.lobby(lobby_id) {
+        Ok(lobby) => lobby.secret().to_string(),
+        Err(_) => return connect(discord, attempt, candidates),
+    };
+
+    discord.connect_lobby(lobby_id, secret, move |discord, result| match result {
+        Ok(lobby) => (attempt.callback)(discord, Ok(MatchmakingOutcome::Joined(lobby.clone()))),
+
+        Err(Error::LobbyFull) | Err(Error::InvalidLobbySecret) => {
+            connect(discord, attempt, candidates)
+        }
+
+        Err(error) => (attempt.callback)(discord, Err(error)),
+    });
+}
+
+fn create<'d, E: 'd>(discord: &Discord<'d, E>, attempt: Attempt<'d, E>) {
+    let callback = attempt.callback;
+
+    discord.create_lobby(&attempt.matchmaker.template, move |discord, result| {
+        callback(
+            discord,
+            result.map(|lobby| MatchmakingOutcome::Created(lobby.clone())),
+        )
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stage_query() {
+        let mut matchmaker = Matchmaker::new(SearchQuery::new(), LobbyTransaction::new());
+        matchmaker
+            .range("skill".into(), 1000.0, 50.0, 100.0)
+            .relaxations(1);
+
+        assert_eq!(matchmaker.stages(), 5);
+
+        let query = matchmaker.stage_query(0);
+        assert_eq!(query.distance, Some(Distance::Local));
+        assert_eq!(query.filters[0].1, "950\0");
+        assert_eq!(query.filters[1].1, "1050\0");
+
+        let query = matchmaker.stage_query(4);
+        assert_eq!(query.distance, Some(Distance::Global));
+        assert_eq!(query.filters[0].1, "550\0");
+        assert_eq!(query.filters[1].1, "1450\0");
+    }
+}
@@ -0,0 +1,20 @@
+use crate::Lobby;
+
+/// Result of a successful [`Matchmaker`](struct.Matchmaker.html) run
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum MatchmakingOutcome {
+    /// An existing lobby was found and joined
+    Joined(Lobby),
+
+    /// No lobby matched, a new one was created from the template
+    Created(Lobby),
+}
+
+impl MatchmakingOutcome {
+    /// The lobby the current user is now a member of
+    pub fn lobby(&self) -> &Lobby {
+        match self {
+            Self::Joined(lobby) | Self::Created(lobby) => lobby,
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use crate::{Codec, CodecError};
+use serde_crate::{de::DeserializeOwned, Serialize};
+
+/// [`Codec`](trait.Codec.html) for types implementing `serde` traits, using [`rmp-serde`](https://docs.rs/rmp-serde)
+///
+/// Structs are encoded as maps, so fields can be added with `#[serde(default)]` without breaking
+/// data encoded before.
+///
+/// Requires the `msgpack` feature.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct MessagePackCodec;
+
+impl<T: Serialize + DeserializeOwned> Codec<T> for MessagePackCodec {
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        rmp_serde::to_vec_named(value).map_err(CodecError::new)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T, CodecError> {
+        rmp_serde::from_slice(data).map_err(CodecError::new)
+    }
+}
@@ -0,0 +1,276 @@
+use crate::Error;
+use std::{collections::HashMap, fmt};
+
+/// Mapping between a Rust type and lobby or member metadata
+///
+/// Each field is stored under its own key, encoded with [`MetadataValue`](trait.MetadataValue.html).
+///
+/// With the `derive` feature, this trait can be derived for structs with named fields.
+/// Fields are stored under their name unless renamed with `#[metadata(rename = "key")]`,
+/// and fields marked with `#[metadata(default)]` fall back to `Default::default()` when missing.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # use std::collections::HashMap;
+/// struct GameInfo {
+///     mode: String,
+///     skill: u32,
+/// }
+///
+/// impl Metadata for GameInfo {
+///     fn to_metadata(&self) -> HashMap<String, String> {
+///         let mut metadata = HashMap::new();
+///         metadata.insert("mode".to_string(), self.mode.to_metadata_value());
+///         metadata.insert("skill".to_string(), self.skill.to_metadata_value());
+///         metadata
+///     }
+///
+///     fn from_metadata(
+///         metadata: &HashMap<String, String>,
+///     ) -> std::result::Result<Self, MetadataError> {
+///         Ok(Self {
+///             mode: MetadataValue::from_metadata(metadata, "mode")?,
+///             skill: MetadataValue::from_metadata(metadata, "skill")?,
+///         })
+///     }
+/// }
+///
+/// # fn example(discord: Discord<'_, ()>, lobby_id: LobbyID) -> std::result::Result<(), MetadataError> {
+/// let info: GameInfo = discord.lobby_typed_metadata(lobby_id)?;
+///
+/// discord.lobby_search(
+///     SearchQuery::new().filter(
+///         "skill".into(),
+///         Comparison::GreaterThanOrEqual,
+///         info.skill.to_metadata_value(),
+///         Cast::Number,
+///     ),
+///     |discord, result| {
+///         // ...
+///     },
+/// );
+/// # Ok(()) }
+/// ```
+#[cfg_attr(
+    feature = "derive",
+    doc = r#"
+Deriving for enums or tuple structs fails to compile:
+
+```rust,compile_fail
+# use discord_game_sdk::*;
+#[derive(Metadata)]
+enum Mode {
+    Casual,
+    Ranked,
+}
+```
+
+```rust,compile_fail
+# use discord_game_sdk::*;
+#[derive(Metadata)]
+struct Skill(u32);
+```
+"#
+)]
+pub trait Metadata: Sized {
+    /// Encodes every field as a key-value pair
+    fn to_metadata(&self) -> HashMap<String, String>;
+
+    /// Decodes an instance from key-value pairs
+    ///
+    /// ## Errors
+    ///
+    /// [`MetadataError::Missing`](enum.MetadataError.html#variant.Missing) if a required key is absent,
+    /// [`MetadataError::Malformed`](enum.MetadataError.html#variant.Malformed) if a value cannot be decoded.
+    fn from_metadata(metadata: &HashMap<String, String>) -> Result<Self, MetadataError>;
+}
+
+/// Encoding of a single metadata value
+///
+/// Numbers are written in decimal so they can be compared with [`Cast::Number`](enum.Cast.html#variant.Number),
+/// booleans are written as `0` and `1` for the same reason.
+pub trait MetadataValue: Sized {
+    /// Encodes the value
+    fn to_metadata_value(&self) -> String;
+
+    /// Decodes a value, returns `None` if it is malformed
+    fn from_metadata_value(value: &str) -> Option<Self>;
+
+    /// Decodes the value stored under `key`
+    ///
+    /// ## Errors
+    ///
+    /// [`MetadataError::Missing`](enum.MetadataError.html#variant.Missing) if `key` is absent,
+    /// [`MetadataError::Malformed`](enum.MetadataError.html#variant.Malformed) if its value cannot be decoded.
+    fn from_metadata(metadata: &HashMap<String, String>, key: &str) -> Result<Self, MetadataError> {
+        let value = metadata
+            .get(key)
+            .ok_or_else(|| MetadataError::Missing(key.to_string()))?;
+
+        Self::from_metadata_value(value).ok_or_else(|| MetadataError::Malformed {
+            key: key.to_string(),
+            value: value.clone(),
+        })
+    }
+}
+
+impl MetadataValue for String {
+    fn to_metadata_value(&self) -> String {
+        self.clone()
+    }
+
+    fn from_metadata_value(value: &str) -> Option<Self> {
+        Some(value.to_string())
+    }
+}
+
+impl MetadataValue for bool {
+    fn to_metadata_value(&self) -> String {
+        if *self { "1" } else { "0" }.to_string()
+    }
+
+    fn from_metadata_value(value: &str) -> Option<Self> {
+        match value {
+            "1" | "true" => Some(true),
+            "0" | "false" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+macro_rules! impl_metadata_value_for_numbers {
+    ($($ty:ty),*) => {
+        $(
+            impl MetadataValue for $ty {
+                fn to_metadata_value(&self) -> String {
+                    self.to_string()
+                }
+
+                fn from_metadata_value(value: &str) -> Option<Self> {
+                    value.parse().ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_metadata_value_for_numbers!(i8, i16, i32, i64, u8, u16, u32, u64, f32, f64);
+
+/// Error while decoding [`Metadata`](trait.Metadata.html)
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum MetadataError {
+    /// A required key is absent
+    Missing(String),
+
+    /// The value under a key could not be decoded
+    Malformed {
+        /// The key under which the value is stored
+        key: String,
+        /// The raw value
+        value: String,
+    },
+
+    /// The metadata could not be read from the SDK
+    Discord(Error),
+}
+
+impl From<Error> for MetadataError {
+    fn from(error: Error) -> Self {
+        Self::Discord(error)
+    }
+}
+
+impl fmt::Display for MetadataError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(key) => write!(f, "missing metadata key {:?}", key),
+            Self::Malformed { key, value } => {
+                write!(f, "malformed metadata value {:?} for key {:?}", value, key)
+            }
+            Self::Discord(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for MetadataError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Discord(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_value() {
+        assert_eq!(true.to_metadata_value(), "1");
+        assert_eq!(bool::from_metadata_value("0"), Some(false));
+        assert_eq!(bool::from_metadata_value("yes"), None);
+        assert_eq!((-42_i64).to_metadata_value(), "-42");
+        assert_eq!(u32::from_metadata_value("1200"), Some(1200));
+        assert_eq!(u8::from_metadata_value("256"), None);
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_derive() {
+        #[derive(Debug, PartialEq, crate::Metadata)]
+        struct GameInfo {
+            #[metadata(rename = "game_mode")]
+            mode: String,
+            skill: u32,
+            #[metadata(default)]
+            ranked: bool,
+        }
+
+        let info = GameInfo {
+            mode: "deathmatch".to_string(),
+            skill: 1200,
+            ranked: true,
+        };
+
+        let mut metadata = info.to_metadata();
+        assert_eq!(metadata.get("game_mode").unwrap(), "deathmatch");
+        assert_eq!(metadata.get("ranked").unwrap(), "1");
+        assert_eq!(GameInfo::from_metadata(&metadata), Ok(info));
+
+        let _ = metadata.remove("ranked");
+        assert_eq!(
+            GameInfo::from_metadata(&metadata),
+            Ok(GameInfo {
+                mode: "deathmatch".to_string(),
+                skill: 1200,
+                ranked: false,
+            })
+        );
+
+        let _ = metadata.remove("skill");
+        assert_eq!(
+            GameInfo::from_metadata(&metadata),
+            Err(MetadataError::Missing("skill".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_from_metadata() {
+        let mut metadata = HashMap::new();
+        let _ = metadata.insert("skill".to_string(), "high".to_string());
+
+        assert_eq!(
+            u32::from_metadata(&metadata, "mode"),
+            Err(MetadataError::Missing("mode".to_string()))
+        );
+
+        assert_eq!(
+            u32::from_metadata(&metadata, "skill"),
+            Err(MetadataError::Malformed {
+                key: "skill".to_string(),
+                value: "high".to_string()
+            })
+        );
+    }
+}
@@ -1,5 +1,5 @@
-use crate::{sys, to_result::ToResult, Discord, FetchKind, Image, ImageHandle, Result};
-use std::convert::{TryFrom, TryInto};
+use crate::{sys, to_result::ToResult, Discord, Error, FetchKind, Image, ImageHandle, Result};
+use std::convert::TryFrom;
 
 /// # Images
 ///
@@ -65,23 +65,28 @@ impl<'d, E> Discord<'d, E> {
     ///
     /// The image must be [fetched](#method.fetch_image) first.
     ///
+    /// ## Errors
+    ///
+    /// [`Error::InsufficientBuffer`](enum.Error.html#variant.InsufficientBuffer) if the data of the image
+    /// exceeds 4 294 967 295 bytes, the limit of the SDK.
+    ///
     /// > [Method in official docs](https://discordapp.com/developers/docs/game-sdk/images#getdata)
     pub fn image(&self, handle: ImageHandle) -> Result<Image> {
         let (width, height) = self.image_dimensions(handle.clone())?;
-        let mut data = vec![0; 4 * width as usize * height as usize];
 
-        debug_assert!(u32::try_from(data.len()).is_ok());
+        // Images whose data exceeds the limit of the SDK cannot be retrieved whole
+        let len = u64::from(width)
+            .checked_mul(u64::from(height))
+            .and_then(|pixels| pixels.checked_mul(4))
+            .and_then(|len| u32::try_from(len).ok())
+            .ok_or(Error::InsufficientBuffer)?;
+
+        let mut data = vec![0; len as usize];
 
         unsafe {
             let mgr = self.image_manager();
 
-            (*mgr).get_data.unwrap()(
-                mgr,
-                handle.0,
-                data.as_mut_ptr(),
-                data.len().try_into().unwrap_or(u32::max_value()),
-            )
-            .to_result()?;
+            (*mgr).get_data.unwrap()(mgr, handle.0, data.as_mut_ptr(), len).to_result()?;
         }
 
         Ok(Image {
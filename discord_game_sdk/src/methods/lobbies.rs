@@ -1,9 +1,11 @@
 use crate::{
     iter, sys, to_result::ToResult, utils, Discord, Lobby, LobbyID, LobbyMemberTransaction,
-    LobbyTransaction, NetworkChannelID, Reliability, Result, SearchQuery, UserID,
+    LobbyTransaction, Metadata, MetadataError, NetworkChannelID, Reliability, Result, SearchQuery,
+    User, UserID,
 };
 use std::{
     borrow::Cow,
+    collections::HashMap,
     convert::{TryFrom, TryInto},
     mem::size_of,
 };
@@ -343,6 +345,23 @@ impl<'d, E> Discord<'d, E> {
         ))
     }
 
+    /// Reads the metadata of a given lobby into a [`Metadata`](trait.Metadata.html) type.
+    ///
+    /// ## Errors
+    ///
+    /// Keys that are missing or hold malformed values are reported as a
+    /// [`MetadataError`](enum.MetadataError.html).
+    pub fn lobby_typed_metadata<T: Metadata>(
+        &self,
+        lobby_id: LobbyID,
+    ) -> std::result::Result<T, MetadataError> {
+        let metadata = self
+            .iter_lobby_metadata(lobby_id)?
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        T::from_metadata(&metadata)
+    }
+
     /// Updates lobby member info for a given member of the lobby.
     ///
     /// > [Method in official docs](https://discordapp.com/developers/docs/game-sdk/lobbies#updatemember)
@@ -431,6 +450,64 @@ impl<'d, E> Discord<'d, E> {
         ))
     }
 
+    /// Returns the user information of a lobby member.
+    ///
+    /// Unlike [`user`](#method.user), this does not require a round-trip to Discord.
+    ///
+    /// > [Method in official docs](https://discordapp.com/developers/docs/game-sdk/lobbies#getmemberuser)
+    ///
+    /// ```rust
+    /// # use discord_game_sdk::*;
+    /// # fn example(discord: Discord<'_, ()>, lobby_id: LobbyID, user_id: UserID) -> Result<()> {
+    /// let member = discord.lobby_member_user(lobby_id, user_id)?;
+    ///
+    /// println!("{}#{} is in the lobby", member.username(), member.discriminator());
+    /// # Ok(()) }
+    /// ```
+    pub fn lobby_member_user(&self, lobby_id: LobbyID, user_id: UserID) -> Result<User> {
+        let mut user = User(sys::DiscordUser::default());
+
+        unsafe {
+            let mgr = self.lobby_manager();
+
+            (*mgr).get_member_user.unwrap()(mgr, lobby_id, user_id, &mut user.0).to_result()?;
+        }
+
+        Ok(user)
+    }
+
+    /// Returns an `Iterator` over the user information of the members of a lobby.
+    ///
+    /// ```rust
+    /// # use discord_game_sdk::*;
+    /// # fn example(discord: Discord<'_, ()>, lobby_id: LobbyID) -> Result<()> {
+    /// for member in discord.iter_lobby_member_users(lobby_id)? {
+    ///     let member = member?;
+    ///     println!("{}#{}", member.username(), member.discriminator());
+    /// }
+    /// # Ok(()) }
+    /// ```
+    pub fn iter_lobby_member_users(
+        &self,
+        lobby_id: LobbyID,
+    ) -> Result<
+        impl '_
+            + Iterator<Item = Result<User>>
+            + DoubleEndedIterator
+            + ExactSizeIterator
+            + std::iter::FusedIterator
+            + std::fmt::Debug,
+    > {
+        Ok(iter::Collection::new(
+            Box::new(move |i| {
+                let discord = self.ref_copy();
+                let user_id = discord.lobby_member_id_at(lobby_id, i)?;
+                discord.lobby_member_user(lobby_id, user_id)
+            }),
+            self.lobby_member_count(lobby_id)?,
+        ))
+    }
+
     /// Returns member metadata value for a given key.
     ///
     /// ## Performance
@@ -552,6 +629,24 @@ impl<'d, E> Discord<'d, E> {
         ))
     }
 
+    /// Reads the metadata of a given lobby member into a [`Metadata`](trait.Metadata.html) type.
+    ///
+    /// ## Errors
+    ///
+    /// Keys that are missing or hold malformed values are reported as a
+    /// [`MetadataError`](enum.MetadataError.html).
+    pub fn lobby_member_typed_metadata<T: Metadata>(
+        &self,
+        lobby_id: LobbyID,
+        user_id: UserID,
+    ) -> std::result::Result<T, MetadataError> {
+        let metadata = self
+            .iter_lobby_member_metadata(lobby_id, user_id)?
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        T::from_metadata(&metadata)
+    }
+
     /// Sends a message to the lobby on behalf of the current user.
     ///
     /// You must be connected to the lobby you are messaging.
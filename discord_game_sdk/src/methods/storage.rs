@@ -1,10 +1,18 @@
-use crate::{iter, sys, to_result::ToResult, utils, Discord, FileStat, Result};
+use crate::{
+    iter, sys, to_result::ToResult, utils, Discord, Error, FileStat, Result, StorageFile,
+    StorageWriter,
+};
 use std::{
     borrow::Cow,
     convert::{TryFrom, TryInto},
     mem::size_of,
 };
 
+#[cfg(feature = "serde")]
+use crate::{JsonCodec, SaveError, SaveFormat};
+#[cfg(feature = "serde")]
+use serde_crate::{de::DeserializeOwned, Serialize};
+
 /// # Storage
 ///
 /// > [Chapter in official docs](https://discordapp.com/developers/docs/game-sdk/storage)
@@ -14,7 +22,17 @@ impl<'d, E> Discord<'d, E> {
     /// The file is mapped by key-value pair, and this function will read data that exists
     /// for the given key name.
     ///
-    /// `buffer` should not exceed 4 294 967 295 bytes.
+    /// The exact size of the file is given by [`FileStat::size`](struct.FileStat.html#method.size).
+    ///
+    /// At most 4 294 967 295 bytes, the limit of the SDK, are read, even if `buffer` is larger.
+    /// This function does not read in chunks, since partial reads only complete in
+    /// [`run_callbacks`](#method.run_callbacks): larger files are read in chunks
+    /// by the [`StorageFile`](struct.StorageFile.html) returned by [`open_file`](#method.open_file).
+    ///
+    /// ## Errors
+    ///
+    /// [`Error::InvalidFileSize`](enum.Error.html#variant.InvalidFileSize) if the file is larger
+    /// than 4 294 967 295 bytes and `buffer` could hold more, it must then be read with `open_file`.
     ///
     /// ## Performance
     ///
@@ -45,25 +63,24 @@ impl<'d, E> Discord<'d, E> {
         let mut read = 0;
 
         let buffer = buffer.as_mut();
-
-        debug_assert!(u32::try_from(buffer.len()).is_ok());
+        let len = read_len(buffer.len());
 
         unsafe {
             let mgr = self.storage_manager();
 
-            (*mgr).read.unwrap()(
-                mgr,
-                filename.as_ptr(),
-                buffer.as_mut_ptr(),
-                // XXX: u32 should be u64
-                buffer.len().try_into().unwrap_or(u32::max_value()),
-                &mut read,
-            )
-            .to_result()?;
+            (*mgr).read.unwrap()(mgr, filename.as_ptr(), buffer.as_mut_ptr(), len, &mut read)
+                .to_result()?;
+        }
+
+        // The rest of the file would fit in the buffer but cannot be returned
+        if read == len
+            && buffer.len() > len as usize
+            && self.file_stat(filename.as_ref())?.size() > u64::from(len)
+        {
+            return Err(Error::InvalidFileSize);
         }
 
-        // XXX: u32 should be u64
-        Ok(read.try_into().unwrap())
+        Ok(u64::from(read))
     }
 
     /// Reads data asynchronously from the game's allocated save file into a buffer.
@@ -165,7 +182,10 @@ impl<'d, E> Discord<'d, E> {
 
     /// Writes data synchronously to disk, under the given key name.
     ///
-    /// `buffer` should not exceed 4 294 967 295 bytes.
+    /// ## Errors
+    ///
+    /// [`Error::InvalidFileSize`](enum.Error.html#variant.InvalidFileSize) if `buffer` exceeds
+    /// 4 294 967 295 bytes, the limit of the SDK. Nothing is written.
     ///
     /// ## Performance
     ///
@@ -193,8 +213,7 @@ impl<'d, E> Discord<'d, E> {
         }
 
         let buffer = buffer.as_ref();
-
-        debug_assert!(u32::try_from(buffer.len()).is_ok());
+        let len = write_len(buffer.len())?;
 
         unsafe {
             let mgr = self.storage_manager();
@@ -204,8 +223,7 @@ impl<'d, E> Discord<'d, E> {
                 filename.as_ptr(),
                 // XXX: *mut should be *const
                 buffer.as_ptr() as *mut u8,
-                // XXX: u32 should be u64
-                buffer.len().try_into().unwrap_or(u32::max_value()),
+                len,
             )
             .to_result()
         }
@@ -213,7 +231,10 @@ impl<'d, E> Discord<'d, E> {
 
     /// Writes data asynchronously to disk under the given key.
     ///
-    /// `buffer` should not exceed 4 294 967 295 bytes.
+    /// ## Errors
+    ///
+    /// [`Error::InvalidFileSize`](enum.Error.html#variant.InvalidFileSize) if `buffer` exceeds
+    /// 4 294 967 295 bytes, the limit of the SDK. Nothing is written and `callback` is called immediately.
     ///
     /// ## Performance
     ///
@@ -247,7 +268,10 @@ impl<'d, E> Discord<'d, E> {
 
         let buffer = buffer.as_ref();
 
-        debug_assert!(u32::try_from(buffer.len()).is_ok());
+        let len = match write_len(buffer.len()) {
+            Ok(len) => len,
+            Err(error) => return callback(self, Err(error)),
+        };
 
         let (ptr, fun) = self
             .one_param(move |discord, res: sys::EDiscordResult| callback(discord, res.to_result()));
@@ -260,14 +284,186 @@ impl<'d, E> Discord<'d, E> {
                 filename.as_ptr(),
                 // XXX: *mut should be *const
                 buffer.as_ptr() as *mut u8,
-                // XXX: u32 should be u64
-                buffer.len().try_into().unwrap_or(u32::max_value()),
+                len,
                 ptr,
                 fun,
             )
         }
     }
 
+    /// Opens a file for reading and seeking, without loading it whole.
+    ///
+    /// See [`StorageFile`](struct.StorageFile.html).
+    ///
+    /// ## Performance
+    ///
+    /// A nul byte will be appended to `filename` if one is not present.
+    ///
+    /// ```rust
+    /// # use discord_game_sdk::*;
+    /// # use std::io::{Read, Seek, SeekFrom};
+    /// # fn example(mut discord: Discord<'_, ()>) -> std::result::Result<(), Box<dyn std::error::Error>> {
+    /// let mut file = discord.open_file("replay_1.save\0")?;
+    /// let mut header = [0; 16];
+    ///
+    /// file.seek(SeekFrom::End(-16))?;
+    /// file.read_exact(&mut header)?;
+    /// # Ok(()) }
+    /// ```
+    pub fn open_file<'a, 's>(
+        &'a mut self,
+        filename: impl Into<Cow<'s, str>>,
+    ) -> Result<StorageFile<'a, 'd, E>> {
+        let mut filename = filename.into().into_owned();
+
+        if !filename.ends_with('\0') {
+            filename.push('\0')
+        }
+
+        let size = self.file_stat(filename.as_str())?.size();
+
+        Ok(StorageFile::new(self, filename, size))
+    }
+
+    /// Creates a buffered writer that replaces a file when it is closed.
+    ///
+    /// See [`StorageWriter`](struct.StorageWriter.html).
+    ///
+    /// ## Performance
+    ///
+    /// A nul byte will be appended to `filename` if one is not present.
+    ///
+    /// ```rust
+    /// # use discord_game_sdk::*;
+    /// # use std::io::Write;
+    /// # fn example(discord: Discord<'_, ()>) -> Result<()> {
+    /// let mut file = discord.create_file("profile_1.save\0");
+    ///
+    /// writeln!(file, "level = 3").unwrap();
+    /// file.close()?;
+    /// # Ok(()) }
+    /// ```
+    pub fn create_file<'s>(&self, filename: impl Into<Cow<'s, str>>) -> StorageWriter<'_, 'd, E> {
+        let mut filename = filename.into().into_owned();
+
+        if !filename.ends_with('\0') {
+            filename.push('\0')
+        }
+
+        StorageWriter::new(self, filename)
+    }
+
+    /// Saves a value as JSON under a given name, in a crash-safe [`SaveSlot`](struct.SaveSlot.html).
+    ///
+    /// See [`SaveFormat`](struct.SaveFormat.html) for other formats, versions and migrations.
+    /// The name must not be used with a `SaveFormat` of another codec.
+    ///
+    /// Requires the `serde` feature.
+    ///
+    /// ```rust
+    /// # use discord_game_sdk::*;
+    /// # use std::collections::HashMap;
+    /// # fn example(discord: Discord<'_, ()>) -> std::result::Result<(), SaveError> {
+    /// let mut settings = HashMap::new();
+    /// settings.insert("volume".to_string(), 80_u8);
+    ///
+    /// discord.save("settings", &settings)?;
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn save<T: Serialize + DeserializeOwned>(
+        &self,
+        name: &str,
+        value: &T,
+    ) -> std::result::Result<(), SaveError> {
+        SaveFormat::new(JsonCodec).save(self, name, value)
+    }
+
+    /// Saves a value as JSON under a given name asynchronously, in a crash-safe
+    /// [`SaveSlot`](struct.SaveSlot.html).
+    ///
+    /// See [`SaveFormat`](struct.SaveFormat.html) for other formats, versions and migrations.
+    /// The name must not be used with a `SaveFormat` of another codec.
+    ///
+    /// Requires the `serde` feature.
+    ///
+    /// ```rust
+    /// # use discord_game_sdk::*;
+    /// # fn example(discord: Discord<'_, ()>) -> Result<()> {
+    /// discord.save_async("scores", &vec![120_u32, 95], |discord, result| {
+    ///     if let Err(error) = result {
+    ///         eprintln!("failed to save scores: {}", error);
+    ///     }
+    /// });
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn save_async<T: Serialize + DeserializeOwned>(
+        &self,
+        name: &str,
+        value: &T,
+        callback: impl 'd + FnOnce(&Discord<'d, E>, std::result::Result<(), SaveError>),
+    ) {
+        SaveFormat::new(JsonCodec).save_async(self, name, value, callback)
+    }
+
+    /// Loads the value saved as JSON under a given name, `None` if it was never saved.
+    ///
+    /// ## Errors
+    ///
+    /// [`SaveError::UnsupportedVersion`](enum.SaveError.html#variant.UnsupportedVersion)
+    /// if the value was saved with a [`SaveFormat`](struct.SaveFormat.html) in another version than 0.
+    /// [`SaveError::Codec`](enum.SaveError.html#variant.Codec) if it was saved with another codec
+    /// than `JsonCodec`, or cannot be decoded as `T`.
+    ///
+    /// Requires the `serde` feature.
+    ///
+    /// ```rust
+    /// # use discord_game_sdk::*;
+    /// # use std::collections::HashMap;
+    /// # fn example(discord: Discord<'_, ()>) -> std::result::Result<(), SaveError> {
+    /// let settings: HashMap<String, u8> = discord.load("settings")?.unwrap_or_default();
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn load<T: Serialize + DeserializeOwned>(
+        &self,
+        name: &str,
+    ) -> std::result::Result<Option<T>, SaveError> {
+        SaveFormat::new(JsonCodec).load(self, name)
+    }
+
+    /// Loads the value saved as JSON under a given name asynchronously, `None` if it was never saved.
+    ///
+    /// ## Errors
+    ///
+    /// [`SaveError::UnsupportedVersion`](enum.SaveError.html#variant.UnsupportedVersion)
+    /// if the value was saved with a [`SaveFormat`](struct.SaveFormat.html) in another version than 0.
+    /// [`SaveError::Codec`](enum.SaveError.html#variant.Codec) if it was saved with another codec
+    /// than `JsonCodec`, or cannot be decoded as `T`.
+    ///
+    /// Requires the `serde` feature.
+    ///
+    /// ```rust
+    /// # use discord_game_sdk::*;
+    /// # fn example(discord: Discord<'_, ()>) -> Result<()> {
+    /// discord.load_async("scores", |discord, scores: std::result::Result<Option<Vec<u32>>, _>| {
+    ///     match scores {
+    ///         Ok(scores) => println!("loaded {} scores", scores.unwrap_or_default().len()),
+    ///         Err(error) => eprintln!("failed to load scores: {}", error),
+    ///     }
+    /// });
+    /// # Ok(()) }
+    /// ```
+    #[cfg(feature = "serde")]
+    pub fn load_async<T: Serialize + DeserializeOwned>(
+        &self,
+        name: &str,
+        callback: impl 'd + FnOnce(&Discord<'d, E>, std::result::Result<Option<T>, SaveError>),
+    ) {
+        SaveFormat::new(JsonCodec).load_async(self, name, callback)
+    }
+
     /// Deletes written data for the given key.
     ///
     /// ## Performance
@@ -451,3 +647,29 @@ impl<'d, E> Discord<'d, E> {
         Ok(utils::charbuf_to_str(&path).to_string())
     }
 }
+
+// Reads into larger buffers are clamped to the limit of the SDK
+fn read_len(len: usize) -> u32 {
+    u32::try_from(len).unwrap_or(u32::MAX)
+}
+
+// Writes of larger buffers would be truncated
+fn write_len(len: usize) -> Result<u32> {
+    u32::try_from(len).map_err(|_| Error::InvalidFileSize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lengths() {
+        assert_eq!(read_len(2048), 2048);
+        assert_eq!(read_len(usize::MAX), u32::MAX);
+        assert_eq!(write_len(2048), Ok(2048));
+        assert_eq!(write_len(u32::MAX as usize), Ok(u32::MAX));
+
+        #[cfg(target_pointer_width = "64")]
+        assert_eq!(write_len(usize::MAX), Err(Error::InvalidFileSize));
+    }
+}
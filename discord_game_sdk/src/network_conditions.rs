@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+/// Simulated network conditions applied by a [`NetworkSimulator`](struct.NetworkSimulator.html)
+///
+/// Defaults to perfect conditions.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # use std::time::Duration;
+/// let mut conditions = NetworkConditions::new();
+/// conditions
+///     .latency(Duration::from_millis(80))
+///     .jitter(Duration::from_millis(20))
+///     .loss(0.02)
+///     .duplication(0.01)
+///     .reordering(0.05);
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct NetworkConditions {
+    pub(crate) latency: Duration,
+    pub(crate) jitter: Duration,
+    pub(crate) loss: f64,
+    pub(crate) duplication: f64,
+    pub(crate) reordering: f64,
+}
+
+impl NetworkConditions {
+    /// Creates perfect conditions: no latency, jitter, loss, duplication or reordering.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Delays every message by a fixed duration.
+    pub fn latency(&mut self, latency: Duration) -> &mut Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Adds or removes up to `jitter` to the latency of every unreliable message.
+    pub fn jitter(&mut self, jitter: Duration) -> &mut Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Sets the probability of an unreliable message to be dropped, between 0 and 1.
+    pub fn loss(&mut self, loss: f64) -> &mut Self {
+        self.loss = loss;
+        self
+    }
+
+    /// Sets the probability of an unreliable message to be delivered twice, between 0 and 1.
+    pub fn duplication(&mut self, duplication: f64) -> &mut Self {
+        self.duplication = duplication;
+        self
+    }
+
+    /// Sets the probability of an unreliable message to be held back so that later messages
+    /// overtake it, between 0 and 1.
+    pub fn reordering(&mut self, reordering: f64) -> &mut Self {
+        self.reordering = reordering;
+        self
+    }
+}
@@ -0,0 +1,423 @@
+use crate::{
+    Discord, LobbyID, NetworkChannelID, NetworkConditions, NetworkPeerID, Reliability, Result,
+    SimulatedMessage, UserID,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
+
+/// Simulation of degraded network conditions for local testing
+///
+/// Messages sent through the simulator and messages forwarded to it are held back
+/// and delivered later according to its [`NetworkConditions`](struct.NetworkConditions.html):
+/// outgoing messages are sent by [`flush`](#method.flush) and incoming messages are returned by
+/// [`receive`](#method.receive), both should be called every frame.
+///
+/// Messages on channels declared unreliable with [`channel`](#method.channel) may be
+/// delayed, dropped, duplicated and reordered. Other channels are considered reliable,
+/// their messages are only delayed by the latency and keep their order.
+///
+/// The simulation is deterministic for a given seed and sequence of calls.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # use std::time::Duration;
+/// struct MyEventHandler {
+///     simulator: NetworkSimulator,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_network_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         peer_id: NetworkPeerID,
+///         channel_id: NetworkChannelID,
+///         data: &[u8],
+///     ) {
+///         self.simulator.on_network_message(peer_id, channel_id, data);
+///     }
+///
+///     // ...
+/// }
+///
+/// # fn example(discord: Discord<'_, ()>, peer_id: NetworkPeerID) -> Result<()> {
+/// let mut conditions = NetworkConditions::new();
+/// conditions
+///     .latency(Duration::from_millis(50))
+///     .jitter(Duration::from_millis(10))
+///     .loss(0.05);
+///
+/// let mut simulator = NetworkSimulator::new(conditions, 42);
+/// simulator.channel(1, Reliability::Unreliable);
+///
+/// simulator.send_message(peer_id, 1, b"position");
+///
+/// // Every frame
+/// simulator.flush(&discord)?;
+///
+/// for message in simulator.receive() {
+///     println!("{:?}", message);
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct NetworkSimulator {
+    conditions: NetworkConditions,
+    rng: Rng,
+    unreliable: HashSet<NetworkChannelID>,
+    next_sequence: u64,
+    pending: Vec<Pending>,
+    last_delivery: HashMap<(Direction, Target, NetworkChannelID), Instant>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Direction {
+    Outgoing,
+    Incoming,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Target {
+    Peer(NetworkPeerID),
+    Member(LobbyID, UserID),
+}
+
+#[derive(Clone, Debug)]
+struct Pending {
+    deliver_at: Instant,
+    sequence: u64,
+    direction: Direction,
+    target: Target,
+    channel_id: NetworkChannelID,
+    data: Vec<u8>,
+}
+
+impl NetworkSimulator {
+    /// Creates a simulator applying the given conditions, with a seed for reproducibility.
+    pub fn new(conditions: NetworkConditions, seed: u64) -> Self {
+        Self {
+            conditions,
+            rng: Rng(seed),
+            unreliable: HashSet::new(),
+            next_sequence: 0,
+            pending: Vec::new(),
+            last_delivery: HashMap::new(),
+        }
+    }
+
+    /// Changes the simulated conditions, messages already held back are not affected.
+    pub fn conditions(&mut self, conditions: NetworkConditions) -> &mut Self {
+        self.conditions = conditions;
+        self
+    }
+
+    /// Declares the reliability a channel was opened with, channels are reliable by default.
+    pub fn channel(&mut self, channel_id: NetworkChannelID, reliability: Reliability) -> &mut Self {
+        match reliability {
+            Reliability::Unreliable => {
+                let _ = self.unreliable.insert(channel_id);
+            }
+
+            Reliability::Reliable => {
+                let _ = self.unreliable.remove(&channel_id);
+            }
+        }
+
+        self
+    }
+
+    /// Holds back a message to a given peer ID until it is sent by [`flush`](#method.flush).
+    pub fn send_message(
+        &mut self,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: impl AsRef<[u8]>,
+    ) {
+        self.schedule(
+            Instant::now(),
+            Direction::Outgoing,
+            Target::Peer(peer_id),
+            channel_id,
+            data.as_ref(),
+        )
+    }
+
+    /// Holds back a message to a lobby member until it is sent by [`flush`](#method.flush).
+    pub fn send_lobby_network_message(
+        &mut self,
+        lobby_id: LobbyID,
+        user_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) {
+        self.schedule(
+            Instant::now(),
+            Direction::Outgoing,
+            Target::Member(lobby_id, user_id),
+            channel_id,
+            data,
+        )
+    }
+
+    /// Sends the outgoing messages that are due.
+    ///
+    /// ## Errors
+    ///
+    /// Messages that fail to send are dropped, the first error is returned
+    /// after every due message was sent.
+    pub fn flush<E>(&mut self, discord: &Discord<'_, E>) -> Result<()> {
+        let mut result = Ok(());
+
+        for pending in self.take_due(Instant::now(), Direction::Outgoing) {
+            let sent = match pending.target {
+                Target::Peer(peer_id) => {
+                    discord.send_message(peer_id, pending.channel_id, &pending.data)
+                }
+
+                Target::Member(lobby_id, user_id) => discord.send_lobby_network_message(
+                    lobby_id,
+                    user_id,
+                    pending.channel_id,
+                    &pending.data,
+                ),
+            };
+
+            result = result.and(sent);
+        }
+
+        result
+    }
+
+    /// Holds back a received message until it is returned by [`receive`](#method.receive).
+    ///
+    /// Forward [`EventHandler::on_network_message`](trait.EventHandler.html#method.on_network_message) here.
+    pub fn on_network_message(
+        &mut self,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) {
+        self.schedule(
+            Instant::now(),
+            Direction::Incoming,
+            Target::Peer(peer_id),
+            channel_id,
+            data,
+        )
+    }
+
+    /// Holds back a received message until it is returned by [`receive`](#method.receive).
+    ///
+    /// Forward [`EventHandler::on_lobby_network_message`](trait.EventHandler.html#method.on_lobby_network_message) here.
+    pub fn on_lobby_network_message(
+        &mut self,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) {
+        self.schedule(
+            Instant::now(),
+            Direction::Incoming,
+            Target::Member(lobby_id, member_id),
+            channel_id,
+            data,
+        )
+    }
+
+    /// Returns the received messages that are due, in delivery order.
+    pub fn receive(&mut self) -> Vec<SimulatedMessage> {
+        self.take_due(Instant::now(), Direction::Incoming)
+            .into_iter()
+            .map(|pending| match pending.target {
+                Target::Peer(peer_id) => SimulatedMessage::Peer {
+                    peer_id,
+                    channel_id: pending.channel_id,
+                    data: pending.data,
+                },
+
+                Target::Member(lobby_id, member_id) => SimulatedMessage::Lobby {
+                    lobby_id,
+                    member_id,
+                    channel_id: pending.channel_id,
+                    data: pending.data,
+                },
+            })
+            .collect()
+    }
+
+    fn schedule(
+        &mut self,
+        now: Instant,
+        direction: Direction,
+        target: Target,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) {
+        let conditions = self.conditions;
+        let key = (direction, target, channel_id);
+        let last = self.last_delivery.get(&key).copied().unwrap_or(now);
+
+        if !self.unreliable.contains(&channel_id) {
+            let deliver_at = last.max(now + conditions.latency);
+            let _ = self.last_delivery.insert(key, deliver_at);
+
+            return self.push(deliver_at, direction, target, channel_id, data.to_vec());
+        }
+
+        if self.rng.chance(conditions.loss) {
+            return;
+        }
+
+        let copies = if self.rng.chance(conditions.duplication) {
+            2
+        } else {
+            1
+        };
+
+        for _ in 0..copies {
+            let delay = self.rng.delay(conditions.latency, conditions.jitter);
+
+            let deliver_at = if self.rng.chance(conditions.reordering) {
+                // Held back without affecting the order of the next messages
+                now + delay + conditions.latency + conditions.jitter
+            } else {
+                let deliver_at = last.max(now + delay);
+                let _ = self.last_delivery.insert(key, deliver_at);
+                deliver_at
+            };
+
+            self.push(deliver_at, direction, target, channel_id, data.to_vec());
+        }
+    }
+
+    fn push(
+        &mut self,
+        deliver_at: Instant,
+        direction: Direction,
+        target: Target,
+        channel_id: NetworkChannelID,
+        data: Vec<u8>,
+    ) {
+        self.pending.push(Pending {
+            deliver_at,
+            sequence: self.next_sequence,
+            direction,
+            target,
+            channel_id,
+            data,
+        });
+
+        self.next_sequence += 1;
+    }
+
+    fn take_due(&mut self, now: Instant, direction: Direction) -> Vec<Pending> {
+        let (mut due, pending) = self
+            .pending
+            .drain(..)
+            .partition::<Vec<_>, _>(|p| p.direction == direction && p.deliver_at <= now);
+
+        self.pending = pending;
+
+        due.sort_by_key(|p| (p.deliver_at, p.sequence));
+        due
+    }
+}
+
+// SplitMix64, good enough for simulation and stable across platforms
+#[derive(Clone, Debug)]
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [0, 1)
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        probability > 0.0 && self.unit() < probability
+    }
+
+    // Uniform in [latency - jitter, latency + jitter], never negative
+    fn delay(&mut self, latency: Duration, jitter: Duration) -> Duration {
+        let offset = jitter.mul_f64(self.unit() * 2.0);
+
+        (latency + offset)
+            .checked_sub(jitter)
+            .unwrap_or_else(|| Duration::from_secs(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delivered(simulator: &mut NetworkSimulator, now: Instant) -> Vec<u8> {
+        simulator
+            .take_due(now, Direction::Incoming)
+            .into_iter()
+            .map(|pending| pending.data[0])
+            .collect()
+    }
+
+    #[test]
+    fn test_reliable() {
+        let mut conditions = NetworkConditions::new();
+        conditions
+            .latency(Duration::from_millis(100))
+            .jitter(Duration::from_millis(50))
+            .loss(1.0);
+
+        let mut simulator = NetworkSimulator::new(conditions, 0);
+        let now = Instant::now();
+
+        for i in 0..10 {
+            simulator.schedule(now, Direction::Incoming, Target::Peer(1), 0, &[i]);
+        }
+
+        assert!(delivered(&mut simulator, now).is_empty());
+        assert_eq!(
+            delivered(&mut simulator, now + Duration::from_millis(100)),
+            (0..10).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_unreliable() {
+        let mut conditions = NetworkConditions::new();
+        conditions
+            .latency(Duration::from_millis(100))
+            .jitter(Duration::from_millis(50))
+            .loss(0.2)
+            .duplication(0.2)
+            .reordering(0.2);
+
+        let run = |seed| {
+            let mut simulator = NetworkSimulator::new(conditions, seed);
+            simulator.channel(0, Reliability::Unreliable);
+
+            let now = Instant::now();
+
+            for i in 0..100 {
+                simulator.schedule(now, Direction::Incoming, Target::Peer(1), 0, &[i]);
+            }
+
+            delivered(&mut simulator, now + Duration::from_secs(1))
+        };
+
+        let first = run(7);
+        assert_eq!(first, run(7));
+        assert_ne!(first, run(8));
+        assert_ne!(first, (0..100).collect::<Vec<_>>());
+    }
+}
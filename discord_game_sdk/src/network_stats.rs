@@ -0,0 +1,284 @@
+use crate::{ChannelStats, Discord, LobbyID, NetworkChannelID, NetworkPeerID, Result, UserID};
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// Traffic statistics of peer and lobby networking
+///
+/// Messages sent through this helper and messages forwarded to it are counted
+/// per peer ID or lobby member, and per channel.
+/// Throughput is measured over a sliding window, one second by default.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # use std::time::Duration;
+/// struct MyEventHandler {
+///     stats: NetworkStats,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_network_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         peer_id: NetworkPeerID,
+///         channel_id: NetworkChannelID,
+///         data: &[u8],
+///     ) {
+///         self.stats.on_network_message(peer_id, channel_id, data);
+///     }
+///
+///     // ...
+/// }
+///
+/// # fn example(discord: Discord<'_, ()>, stats: &mut NetworkStats, peer_id: NetworkPeerID) -> Result<()> {
+/// stats.send_message(&discord, peer_id, 0, b"ping")?;
+///
+/// let peer = stats.peer(peer_id);
+/// println!("sending {:.0} B/s", peer.sent_throughput());
+///
+/// let idle = peer
+///     .last_received()
+///     .map_or(true, |at| at.elapsed() > Duration::from_secs(5));
+///
+/// if idle {
+///     eprintln!("peer {} stopped talking", peer_id);
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct NetworkStats {
+    window: Duration,
+    peers: HashMap<(NetworkPeerID, NetworkChannelID), ChannelStats>,
+    members: HashMap<(LobbyID, UserID, NetworkChannelID), ChannelStats>,
+}
+
+impl Default for NetworkStats {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_secs(1),
+            peers: HashMap::new(),
+            members: HashMap::new(),
+        }
+    }
+}
+
+impl NetworkStats {
+    /// Creates empty statistics measuring throughput over one second.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the duration of the sliding window used to measure throughput.
+    ///
+    /// Only applies to channels that were not used yet.
+    pub fn window(&mut self, window: Duration) -> &mut Self {
+        self.window = window;
+        self
+    }
+
+    /// Sends data to a given peer ID through the given channel, and counts it.
+    ///
+    /// See [`send_message`](struct.Discord.html#method.send_message).
+    pub fn send_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: impl AsRef<[u8]>,
+    ) -> Result<()> {
+        let data = data.as_ref();
+        let window = self.window;
+        let stats = self
+            .peers
+            .entry((peer_id, channel_id))
+            .or_insert_with(|| ChannelStats::new(window));
+
+        record(stats, discord.send_message(peer_id, channel_id, data), data)
+    }
+
+    /// Sends a network message to a lobby member, and counts it.
+    ///
+    /// See [`send_lobby_network_message`](struct.Discord.html#method.send_lobby_network_message).
+    pub fn send_lobby_network_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        user_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> Result<()> {
+        let window = self.window;
+        let stats = self
+            .members
+            .entry((lobby_id, user_id, channel_id))
+            .or_insert_with(|| ChannelStats::new(window));
+
+        record(
+            stats,
+            discord.send_lobby_network_message(lobby_id, user_id, channel_id, data),
+            data,
+        )
+    }
+
+    /// Counts a received message.
+    ///
+    /// Forward [`EventHandler::on_network_message`](trait.EventHandler.html#method.on_network_message) here.
+    pub fn on_network_message(
+        &mut self,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) {
+        let window = self.window;
+
+        self.peers
+            .entry((peer_id, channel_id))
+            .or_insert_with(|| ChannelStats::new(window))
+            .record_received(Instant::now(), data.len());
+    }
+
+    /// Counts a received message.
+    ///
+    /// Forward [`EventHandler::on_lobby_network_message`](trait.EventHandler.html#method.on_lobby_network_message) here.
+    pub fn on_lobby_network_message(
+        &mut self,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) {
+        let window = self.window;
+
+        self.members
+            .entry((lobby_id, member_id, channel_id))
+            .or_insert_with(|| ChannelStats::new(window))
+            .record_received(Instant::now(), data.len());
+    }
+
+    /// The statistics of every channel to a given peer ID, combined.
+    pub fn peer(&self, peer_id: NetworkPeerID) -> ChannelStats {
+        combine(
+            self.window,
+            self.peers
+                .iter()
+                .filter(|((id, _), _)| *id == peer_id)
+                .map(|(_, stats)| stats),
+        )
+    }
+
+    /// The statistics of a channel to a given peer ID, if it was used.
+    pub fn peer_channel(
+        &self,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+    ) -> Option<&ChannelStats> {
+        self.peers.get(&(peer_id, channel_id))
+    }
+
+    /// The statistics of every lobby network channel to a member, combined.
+    pub fn member(&self, lobby_id: LobbyID, user_id: UserID) -> ChannelStats {
+        combine(
+            self.window,
+            self.members
+                .iter()
+                .filter(|((lobby, member, _), _)| (*lobby, *member) == (lobby_id, user_id))
+                .map(|(_, stats)| stats),
+        )
+    }
+
+    /// The statistics of a lobby network channel to a member, if it was used.
+    pub fn member_channel(
+        &self,
+        lobby_id: LobbyID,
+        user_id: UserID,
+        channel_id: NetworkChannelID,
+    ) -> Option<&ChannelStats> {
+        self.members.get(&(lobby_id, user_id, channel_id))
+    }
+
+    /// Returns an `Iterator` over the statistics of every channel used with peer IDs.
+    pub fn iter_peer_channels(
+        &self,
+    ) -> impl '_ + Iterator<Item = (NetworkPeerID, NetworkChannelID, &ChannelStats)> {
+        self.peers
+            .iter()
+            .map(|((peer_id, channel_id), stats)| (*peer_id, *channel_id, stats))
+    }
+
+    /// Returns an `Iterator` over the statistics of every lobby network channel used with members.
+    pub fn iter_member_channels(
+        &self,
+    ) -> impl '_ + Iterator<Item = (LobbyID, UserID, NetworkChannelID, &ChannelStats)> {
+        self.members
+            .iter()
+            .map(|((lobby_id, user_id, channel_id), stats)| {
+                (*lobby_id, *user_id, *channel_id, stats)
+            })
+    }
+
+    /// Discards the statistics of a given peer ID.
+    ///
+    /// Call this after [`close_peer`](struct.Discord.html#method.close_peer).
+    pub fn forget_peer(&mut self, peer_id: NetworkPeerID) {
+        self.peers.retain(|(id, _), _| *id != peer_id);
+    }
+
+    /// Discards the statistics of a given lobby member.
+    ///
+    /// Call this from [`EventHandler::on_member_disconnect`](trait.EventHandler.html#method.on_member_disconnect).
+    pub fn forget_member(&mut self, lobby_id: LobbyID, member_id: UserID) {
+        self.members
+            .retain(|(lobby, member, _), _| (*lobby, *member) != (lobby_id, member_id));
+    }
+}
+
+fn record(stats: &mut ChannelStats, result: Result<()>, data: &[u8]) -> Result<()> {
+    match &result {
+        Ok(()) => stats.record_sent(Instant::now(), data.len()),
+        Err(_) => stats.record_send_failure(),
+    }
+
+    result
+}
+
+fn combine<'a>(window: Duration, stats: impl Iterator<Item = &'a ChannelStats>) -> ChannelStats {
+    stats.fold(ChannelStats::new(window), |mut total, stats| {
+        total.merge(stats);
+        total
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats() {
+        let mut stats = NetworkStats::new();
+        stats.window(Duration::from_secs(2));
+
+        stats.on_network_message(1, 0, &[0; 10]);
+        stats.on_network_message(1, 1, &[0; 30]);
+        stats.on_network_message(2, 0, &[0; 100]);
+
+        let peer = stats.peer(1);
+        assert_eq!(peer.packets_received(), 2);
+        assert_eq!(peer.bytes_received(), 40);
+        assert_eq!(peer.average_received_size(), Some(20.0));
+        assert_eq!(peer.average_sent_size(), None);
+        assert!(peer.last_received().is_some());
+
+        let now = Instant::now();
+        let channel = stats.peer_channel(1, 1).unwrap();
+        assert_eq!(channel.throughput(&channel.received_samples, now), 15.0);
+        assert_eq!(
+            channel.throughput(&channel.received_samples, now + Duration::from_secs(2)),
+            0.0
+        );
+
+        stats.forget_peer(1);
+        assert_eq!(stats.peer(1).packets_received(), 0);
+        assert_eq!(stats.iter_peer_channels().count(), 1);
+    }
+}
@@ -0,0 +1,564 @@
+use crate::{
+    Discord, LobbyID, NetworkChannelID, NetworkPeerID, StreamID, StreamReader, StreamWriter, UserID,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::{TryFrom, TryInto},
+    io,
+};
+
+const DATA: u8 = 0;
+const CREDIT: u8 = 1;
+const END: u8 = 2;
+
+pub(crate) const HEADER_LEN: usize = 5;
+
+/// Byte streams multiplexed over a reliable network channel
+///
+/// Streams are one-way: the sender opens a stream with [`open`](#method.open) and writes to it
+/// through a [`StreamWriter`](struct.StreamWriter.html), the receiver is notified of the new stream
+/// by [`on_network_message`](#method.on_network_message) and reads from it through a
+/// [`StreamReader`](struct.StreamReader.html). Any number of streams can be open at once.
+///
+/// Flow control limits the data in flight to a window, 64KiB by default, which must be the
+/// same on both ends. Writes that exceed the window fail with
+/// [`ErrorKind::WouldBlock`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock)
+/// until the receiver reads and grants more credit, as do reads when no data was received yet.
+///
+/// Each peer or lobby member may have up to 64 incoming streams open at once by default.
+///
+/// Every packet starts with a kind byte and the stream ID as a little-endian 32 bits integer.
+/// The channel must be opened with [`Reliability::Reliable`](enum.Reliability.html#variant.Reliable).
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # use std::io::{Read, Write};
+/// struct MyEventHandler {
+///     streams: NetworkStreams,
+///     replays: Vec<(NetworkPeerID, StreamID, Vec<u8>)>,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_network_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         peer_id: NetworkPeerID,
+///         channel_id: NetworkChannelID,
+///         data: &[u8],
+///     ) {
+///         match self.streams.on_network_message(peer_id, channel_id, data) {
+///             Ok(Some(stream_id)) => self.replays.push((peer_id, stream_id, Vec::new())),
+///             Ok(None) => {}
+///             Err(error) => eprintln!("invalid stream packet from {}: {}", peer_id, error),
+///         }
+///
+///         for (peer_id, stream_id, replay) in &mut self.replays {
+///             let mut reader = self.streams.reader(discord, *peer_id, *stream_id);
+///             let _ = reader.read_to_end(replay);
+///         }
+///     }
+///
+///     // ...
+/// }
+///
+/// # fn example(discord: Discord<'_, ()>, streams: &mut NetworkStreams, peer_id: NetworkPeerID, replay: &[u8]) -> std::io::Result<()> {
+/// let stream_id = streams.open(peer_id);
+/// let mut written = 0;
+///
+/// // Every frame, until the whole replay is written
+/// let mut writer = streams.writer(&discord, peer_id, stream_id);
+///
+/// match writer.write(&replay[written..]) {
+///     Ok(len) => written += len,
+///     Err(error) if error.kind() == std::io::ErrorKind::WouldBlock => {}
+///     Err(error) => return Err(error),
+/// }
+///
+/// if written == replay.len() {
+///     writer.finish()?;
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct NetworkStreams {
+    pub(crate) channel_id: NetworkChannelID,
+    pub(crate) window: u32,
+    pub(crate) packet_size: usize,
+    max_incoming_streams: usize,
+    next_stream_id: StreamID,
+    pub(crate) outgoing: HashMap<(Remote, StreamID), Outgoing>,
+    pub(crate) incoming: HashMap<(Remote, StreamID), Incoming>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub(crate) enum Remote {
+    Peer(NetworkPeerID),
+    Member(LobbyID, UserID),
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Outgoing {
+    credit: u32,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct Incoming {
+    buffer: VecDeque<u8>,
+    allowed: u32,
+    consumed: u32,
+    ended: bool,
+}
+
+impl NetworkStreams {
+    /// Creates streams over the given channel ID, with a 64KiB window and 1KiB packets.
+    pub fn new(channel_id: NetworkChannelID) -> Self {
+        Self {
+            channel_id,
+            window: 64 * 1024,
+            packet_size: 1024,
+            max_incoming_streams: 64,
+            next_stream_id: 0,
+            outgoing: HashMap::new(),
+            incoming: HashMap::new(),
+        }
+    }
+
+    /// Sets the number of bytes that may be in flight on each stream, at least 1.
+    ///
+    /// Must be the same on both ends and set before streams are opened.
+    pub fn window(&mut self, window: u32) -> &mut Self {
+        self.window = window.max(1);
+        self
+    }
+
+    /// Sets the maximum size of the data carried by each packet, excluding the header.
+    pub fn packet_size(&mut self, packet_size: usize) -> &mut Self {
+        self.packet_size = packet_size.max(1);
+        self
+    }
+
+    /// Sets the number of incoming streams each peer or lobby member may have open at once,
+    /// streams received beyond it are rejected.
+    ///
+    /// A stream stays open until the end of it was read.
+    pub fn max_incoming_streams(&mut self, max_incoming_streams: usize) -> &mut Self {
+        self.max_incoming_streams = max_incoming_streams;
+        self
+    }
+
+    /// Opens a stream to a given peer ID.
+    pub fn open(&mut self, peer_id: NetworkPeerID) -> StreamID {
+        self.open_to(Remote::Peer(peer_id))
+    }
+
+    /// Opens a stream to a lobby member.
+    pub fn open_lobby(&mut self, lobby_id: LobbyID, user_id: UserID) -> StreamID {
+        self.open_to(Remote::Member(lobby_id, user_id))
+    }
+
+    /// Borrows a stream opened to a given peer ID for writing.
+    pub fn writer<'a, 'd, E>(
+        &'a mut self,
+        discord: &'a Discord<'d, E>,
+        peer_id: NetworkPeerID,
+        stream_id: StreamID,
+    ) -> StreamWriter<'a, 'd, E> {
+        StreamWriter::new(self, discord, Remote::Peer(peer_id), stream_id)
+    }
+
+    /// Borrows a stream opened to a lobby member for writing.
+    pub fn lobby_writer<'a, 'd, E>(
+        &'a mut self,
+        discord: &'a Discord<'d, E>,
+        lobby_id: LobbyID,
+        user_id: UserID,
+        stream_id: StreamID,
+    ) -> StreamWriter<'a, 'd, E> {
+        StreamWriter::new(self, discord, Remote::Member(lobby_id, user_id), stream_id)
+    }
+
+    /// Borrows a stream received from a given peer ID for reading.
+    pub fn reader<'a, 'd, E>(
+        &'a mut self,
+        discord: &'a Discord<'d, E>,
+        peer_id: NetworkPeerID,
+        stream_id: StreamID,
+    ) -> StreamReader<'a, 'd, E> {
+        StreamReader::new(self, discord, Remote::Peer(peer_id), stream_id)
+    }
+
+    /// Borrows a stream received from a lobby member for reading.
+    pub fn lobby_reader<'a, 'd, E>(
+        &'a mut self,
+        discord: &'a Discord<'d, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        stream_id: StreamID,
+    ) -> StreamReader<'a, 'd, E> {
+        StreamReader::new(
+            self,
+            discord,
+            Remote::Member(lobby_id, member_id),
+            stream_id,
+        )
+    }
+
+    /// Buffers received data or credit, returning the ID of the stream if it is new.
+    ///
+    /// Packets received on other channels are ignored.
+    ///
+    /// Forward [`EventHandler::on_network_message`](trait.EventHandler.html#method.on_network_message) here.
+    ///
+    /// ## Errors
+    ///
+    /// [`ErrorKind::InvalidData`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidData)
+    /// if the packet is malformed, the sender exceeded the window, or opened too many streams.
+    pub fn on_network_message(
+        &mut self,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> io::Result<Option<StreamID>> {
+        if channel_id != self.channel_id {
+            return Ok(None);
+        }
+
+        self.receive(Remote::Peer(peer_id), data)
+    }
+
+    /// Buffers received data or credit, returning the ID of the stream if it is new.
+    ///
+    /// Packets received on other channels are ignored.
+    ///
+    /// Forward [`EventHandler::on_lobby_network_message`](trait.EventHandler.html#method.on_lobby_network_message) here.
+    ///
+    /// ## Errors
+    ///
+    /// [`ErrorKind::InvalidData`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.InvalidData)
+    /// if the packet is malformed, the sender exceeded the window, or opened too many streams.
+    pub fn on_lobby_network_message(
+        &mut self,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> io::Result<Option<StreamID>> {
+        if channel_id != self.channel_id {
+            return Ok(None);
+        }
+
+        self.receive(Remote::Member(lobby_id, member_id), data)
+    }
+
+    /// Discards every stream to and from a given peer ID.
+    ///
+    /// Call this after [`close_peer`](struct.Discord.html#method.close_peer).
+    pub fn forget_peer(&mut self, peer_id: NetworkPeerID) {
+        self.forget(Remote::Peer(peer_id))
+    }
+
+    /// Discards every stream to and from a given lobby member.
+    ///
+    /// Call this from [`EventHandler::on_member_disconnect`](trait.EventHandler.html#method.on_member_disconnect).
+    pub fn forget_member(&mut self, lobby_id: LobbyID, member_id: UserID) {
+        self.forget(Remote::Member(lobby_id, member_id))
+    }
+
+    fn open_to(&mut self, remote: Remote) -> StreamID {
+        let stream_id = self.next_stream_id;
+        self.next_stream_id = self.next_stream_id.wrapping_add(1);
+
+        let _ = self.outgoing.insert(
+            (remote, stream_id),
+            Outgoing {
+                credit: self.window,
+            },
+        );
+
+        stream_id
+    }
+
+    fn forget(&mut self, remote: Remote) {
+        self.outgoing.retain(|(r, _), _| *r != remote);
+        self.incoming.retain(|(r, _), _| *r != remote);
+    }
+
+    // Returns the packets carrying as much of `buf` as the credit allows, and its length
+    pub(crate) fn write_packets(
+        &mut self,
+        remote: Remote,
+        stream_id: StreamID,
+        buf: &[u8],
+    ) -> io::Result<(usize, Vec<Vec<u8>>)> {
+        let stream = self
+            .outgoing
+            .get_mut(&(remote, stream_id))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "stream is not open"))?;
+
+        let len = buf.len().min(stream.credit as usize);
+
+        if len == 0 && !buf.is_empty() {
+            return Err(io::ErrorKind::WouldBlock.into());
+        }
+
+        stream.credit -= len as u32;
+
+        let packets = buf[..len]
+            .chunks(self.packet_size)
+            .map(|chunk| packet(DATA, stream_id, chunk))
+            .collect();
+
+        Ok((len, packets))
+    }
+
+    // Gives back the credit of data that could not be sent
+    pub(crate) fn refund(&mut self, remote: Remote, stream_id: StreamID, len: usize) {
+        if let Some(stream) = self.outgoing.get_mut(&(remote, stream_id)) {
+            stream.credit = stream.credit.saturating_add(len as u32);
+        }
+    }
+
+    pub(crate) fn end_packet(&mut self, remote: Remote, stream_id: StreamID) -> Option<Vec<u8>> {
+        self.outgoing
+            .remove(&(remote, stream_id))
+            .map(|_| packet(END, stream_id, &[]))
+    }
+
+    // Returns the length of the data read
+    pub(crate) fn read_buffered(
+        &mut self,
+        remote: Remote,
+        stream_id: StreamID,
+        buf: &mut [u8],
+    ) -> io::Result<usize> {
+        let key = (remote, stream_id);
+
+        let stream = self
+            .incoming
+            .get_mut(&key)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "stream is not open"))?;
+
+        if stream.buffer.is_empty() {
+            if stream.ended {
+                let _ = self.incoming.remove(&key);
+                return Ok(0);
+            }
+
+            if !buf.is_empty() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+        }
+
+        let len = buf.len().min(stream.buffer.len());
+
+        for (byte, buffered) in buf.iter_mut().zip(stream.buffer.drain(..len)) {
+            *byte = buffered;
+        }
+
+        stream.consumed += len as u32;
+
+        Ok(len)
+    }
+
+    // Returns a packet granting credit if enough data was consumed, until it is granted
+    pub(crate) fn credit_packet(&self, remote: Remote, stream_id: StreamID) -> Option<Vec<u8>> {
+        match self.incoming.get(&(remote, stream_id)) {
+            Some(stream) if !stream.ended && stream.consumed >= (self.window / 2).max(1) => {
+                Some(packet(CREDIT, stream_id, &stream.consumed.to_le_bytes()))
+            }
+            _ => None,
+        }
+    }
+
+    // Allows the sender the data consumed, once the credit packet was sent
+    pub(crate) fn grant_credit(&mut self, remote: Remote, stream_id: StreamID) {
+        if let Some(stream) = self.incoming.get_mut(&(remote, stream_id)) {
+            stream.allowed += stream.consumed;
+            stream.consumed = 0;
+        }
+    }
+
+    fn receive(&mut self, remote: Remote, data: &[u8]) -> io::Result<Option<StreamID>> {
+        if data.len() < HEADER_LEN {
+            return Err(invalid_data("truncated stream packet"));
+        }
+
+        let kind = data[0];
+        let stream_id = StreamID::from_le_bytes(data[1..HEADER_LEN].try_into().unwrap());
+        let payload = &data[HEADER_LEN..];
+
+        if kind == CREDIT {
+            let credit = payload
+                .try_into()
+                .map(u32::from_le_bytes)
+                .map_err(|_| invalid_data("malformed credit"))?;
+
+            // Credit for a finished stream is expected and ignored
+            if let Some(stream) = self.outgoing.get_mut(&(remote, stream_id)) {
+                stream.credit = stream.credit.saturating_add(credit);
+            }
+
+            return Ok(None);
+        }
+
+        if kind != DATA && kind != END {
+            return Err(invalid_data("unknown stream packet kind"));
+        }
+
+        let key = (remote, stream_id);
+
+        if !self.incoming.contains_key(&key) {
+            let open = self.incoming.keys().filter(|(r, _)| *r == remote).count();
+
+            if open >= self.max_incoming_streams {
+                return Err(invalid_data("too many open streams"));
+            }
+        }
+
+        let window = self.window;
+        let mut new = false;
+
+        let stream = self.incoming.entry(key).or_insert_with(|| {
+            new = true;
+
+            Incoming {
+                buffer: VecDeque::new(),
+                allowed: window,
+                consumed: 0,
+                ended: false,
+            }
+        });
+
+        if stream.ended {
+            return Err(invalid_data("data received after the end of the stream"));
+        }
+
+        if kind == END {
+            stream.ended = true;
+        } else {
+            let len = u32::try_from(payload.len())
+                .ok()
+                .filter(|len| *len <= stream.allowed)
+                .ok_or_else(|| invalid_data("stream window exceeded"))?;
+
+            stream.allowed -= len;
+            stream.buffer.extend(payload);
+        }
+
+        Ok(if new { Some(stream_id) } else { None })
+    }
+}
+
+fn packet(kind: u8, stream_id: StreamID, payload: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(HEADER_LEN + payload.len());
+    packet.push(kind);
+    packet.extend_from_slice(&stream_id.to_le_bytes());
+    packet.extend_from_slice(payload);
+    packet
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flow_control() {
+        let mut sender = NetworkStreams::new(0);
+        let mut receiver = NetworkStreams::new(0);
+        sender.window(8).packet_size(3);
+        receiver.window(8);
+
+        let to_receiver = Remote::Peer(2);
+        let to_sender = Remote::Peer(1);
+
+        let first = sender.open_to(to_receiver);
+        let second = sender.open_to(to_receiver);
+
+        let (len, packets) = sender
+            .write_packets(to_receiver, first, b"0123456789")
+            .unwrap();
+        assert_eq!((len, packets.len()), (8, 3));
+
+        let error = sender.write_packets(to_receiver, first, b"89").unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WouldBlock);
+
+        assert_eq!(
+            receiver.receive(to_sender, &packets[0]).unwrap(),
+            Some(first)
+        );
+        assert_eq!(receiver.receive(to_sender, &packets[1]).unwrap(), None);
+        assert_eq!(receiver.receive(to_sender, &packets[2]).unwrap(), None);
+
+        assert_eq!(
+            receiver
+                .receive(to_sender, &packet(DATA, first, b"x"))
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+
+        let mut buf = [0; 5];
+        let len = receiver.read_buffered(to_sender, first, &mut buf).unwrap();
+        assert_eq!(&buf[..len], b"01234");
+
+        // The credit stays pending until it is sent
+        let credit = receiver.credit_packet(to_sender, first).unwrap();
+        assert_eq!(
+            receiver.credit_packet(to_sender, first),
+            Some(credit.clone())
+        );
+        receiver.grant_credit(to_sender, first);
+        assert_eq!(receiver.credit_packet(to_sender, first), None);
+
+        sender.receive(to_receiver, &credit).unwrap();
+        let (len, _) = sender.write_packets(to_receiver, first, b"89").unwrap();
+        assert_eq!(len, 2);
+
+        let (_, packets) = sender.write_packets(to_receiver, second, b"ab").unwrap();
+        let end = sender.end_packet(to_receiver, second).unwrap();
+        assert_eq!(
+            receiver.receive(to_sender, &packets[0]).unwrap(),
+            Some(second)
+        );
+        receiver.receive(to_sender, &end).unwrap();
+
+        // Every stream but the second one was opened
+        receiver.max_incoming_streams(2);
+        assert_eq!(
+            receiver
+                .receive(to_sender, &packet(DATA, 7, b"x"))
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(
+            receiver
+                .receive(Remote::Peer(3), &packet(DATA, 7, b"x"))
+                .unwrap(),
+            Some(7)
+        );
+
+        let (len, _) = sender.write_packets(to_receiver, first, b"abcdef").unwrap();
+        assert_eq!(len, 3);
+        sender.refund(to_receiver, first, 2);
+        let (len, _) = sender.write_packets(to_receiver, first, b"bcdef").unwrap();
+        assert_eq!(len, 2);
+
+        let len = receiver.read_buffered(to_sender, second, &mut buf).unwrap();
+        assert_eq!(&buf[..len], b"ab");
+        assert_eq!(
+            receiver.read_buffered(to_sender, second, &mut buf).unwrap(),
+            0
+        );
+        assert_eq!(
+            receiver
+                .read_buffered(to_sender, first, &mut buf[..0])
+                .unwrap(),
+            0
+        );
+    }
+}
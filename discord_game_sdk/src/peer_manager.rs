@@ -0,0 +1,440 @@
+use crate::{
+    Discord, LobbyID, LobbyMemberTransaction, NetworkChannelID, NetworkPeerID, Reliability, Result,
+    UserID,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Automatic peer connections between lobby members
+///
+/// The current user's peer ID and route are published in their member metadata
+/// of every lobby joined through [`join`](#method.join), and republished whenever the route changes.
+/// Routes published by other members are used to open, update and close peer connections,
+/// and every configured channel is opened on new connections.
+///
+/// A member sharing several lobbies with the current user is connected to once,
+/// the connection is closed when the last shared lobby is left.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// struct MyEventHandler {
+///     peers: PeerManager,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_network_route_update(&mut self, discord: &Discord<'_, Self>, route: &str) {
+///         self.peers.on_network_route_update(discord, route);
+///     }
+///
+///     fn on_member_connect(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///     ) {
+///         if let Ok(Some(peer_id)) = self.peers.on_member_connect(discord, lobby_id, member_id) {
+///             println!("connected to {} as peer {}", member_id, peer_id);
+///         }
+///     }
+///
+///     fn on_member_update(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///     ) {
+///         if let Ok(Some(peer_id)) = self.peers.on_member_update(discord, lobby_id, member_id) {
+///             println!("connected to {} as peer {}", member_id, peer_id);
+///         }
+///     }
+///
+///     fn on_member_disconnect(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///     ) {
+///         let _ = self.peers.on_member_disconnect(discord, lobby_id, member_id);
+///     }
+///
+///     // ...
+/// }
+///
+/// # fn example(discord: Discord<'_, MyEventHandler>, lobby_id: LobbyID) -> Result<()> {
+/// let mut peers = PeerManager::new();
+/// peers
+///     .channel(0, Reliability::Reliable)
+///     .channel(1, Reliability::Unreliable);
+///
+/// // After connecting to the lobby
+/// peers.join(&discord, lobby_id)?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PeerManager {
+    route: Option<String>,
+    channels: Vec<(NetworkChannelID, Reliability)>,
+    lobbies: HashSet<LobbyID>,
+    peers: HashMap<UserID, Peer>,
+}
+
+#[derive(Clone, Debug)]
+struct Peer {
+    peer_id: NetworkPeerID,
+    route: String,
+    lobbies: HashSet<LobbyID>,
+    // Whether the peer and its channels were opened, failures are retried on the next update
+    opened: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Step {
+    Open,
+    Update,
+    Reopen(NetworkPeerID),
+    Keep,
+}
+
+impl PeerManager {
+    /// The member metadata key under which the peer ID is stored
+    pub const PEER_ID_KEY: &'static str = "peer_manager.peer_id";
+
+    /// The member metadata key under which the route is stored
+    pub const ROUTE_KEY: &'static str = "peer_manager.route";
+
+    /// Creates a manager without channels, that has not joined any lobby yet.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens a channel on every new peer connection.
+    pub fn channel(&mut self, channel_id: NetworkChannelID, reliability: Reliability) -> &mut Self {
+        self.channels.push((channel_id, reliability));
+        self
+    }
+
+    /// The peer ID of a connected member.
+    pub fn peer_id(&self, user_id: UserID) -> Option<NetworkPeerID> {
+        self.peers.get(&user_id).map(|peer| peer.peer_id)
+    }
+
+    /// The member behind a connected peer ID.
+    pub fn user_id(&self, peer_id: NetworkPeerID) -> Option<UserID> {
+        self.peers
+            .iter()
+            .find(|(_, peer)| peer.peer_id == peer_id)
+            .map(|(user_id, _)| *user_id)
+    }
+
+    /// Returns an `Iterator` over the connected members and their peer IDs.
+    pub fn iter_peers(&self) -> impl '_ + Iterator<Item = (UserID, NetworkPeerID)> {
+        self.peers
+            .iter()
+            .map(|(user_id, peer)| (*user_id, peer.peer_id))
+    }
+
+    /// Publishes the current route in a lobby and connects to the members that published theirs.
+    ///
+    /// Must be called once after [`create_lobby`](struct.Discord.html#method.create_lobby) or
+    /// [`connect_lobby`](struct.Discord.html#method.connect_lobby) succeed.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if the current user is not available yet.
+    /// Failures to connect to a member are logged, and retried on their next update.
+    pub fn join<E>(&mut self, discord: &Discord<'_, E>, lobby_id: LobbyID) -> Result<()> {
+        let user_id = discord.current_user()?.id();
+
+        let _ = self.lobbies.insert(lobby_id);
+
+        if let Some(route) = &self.route {
+            publish(discord, lobby_id, user_id, discord.peer_id(), route);
+        }
+
+        for member_id in discord.iter_lobby_member_ids(lobby_id)? {
+            let member_id = member_id?;
+
+            if let Err(error) = self.connect(discord, lobby_id, member_id) {
+                log::warn!(
+                    "failed to connect to member {} of lobby {}: {}",
+                    member_id,
+                    lobby_id,
+                    error
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Closes the connections to members that no longer share a lobby with the current user.
+    ///
+    /// Call this after [`disconnect_lobby`](struct.Discord.html#method.disconnect_lobby) succeeds.
+    ///
+    /// ## Errors
+    ///
+    /// Connections are forgotten even if closing them fails, the first error is returned.
+    pub fn leave<E>(&mut self, discord: &Discord<'_, E>, lobby_id: LobbyID) -> Result<()> {
+        let _ = self.lobbies.remove(&lobby_id);
+
+        let members = self
+            .peers
+            .iter()
+            .filter(|(_, peer)| peer.lobbies.contains(&lobby_id))
+            .map(|(user_id, _)| *user_id)
+            .collect::<Vec<_>>();
+
+        let mut result = Ok(());
+
+        for member_id in members {
+            if let Err(error) = self.disconnect(discord, lobby_id, member_id) {
+                result = result.and(Err(error));
+            }
+        }
+
+        result
+    }
+
+    /// Publishes the new route in every joined lobby.
+    ///
+    /// Failures to publish are logged.
+    ///
+    /// Forward [`EventHandler::on_network_route_update`](trait.EventHandler.html#method.on_network_route_update) here.
+    pub fn on_network_route_update<E>(&mut self, discord: &Discord<'_, E>, route: &str) {
+        self.route = Some(route.to_string());
+
+        let user_id = match discord.current_user() {
+            Ok(user) => user.id(),
+            // The route is published once the lobbies are joined
+            Err(_) => return,
+        };
+
+        for lobby_id in &self.lobbies {
+            publish(discord, *lobby_id, user_id, discord.peer_id(), route);
+        }
+    }
+
+    /// Connects to the member if they published a route.
+    ///
+    /// Returns the peer ID of the member if a new connection was opened.
+    ///
+    /// Forward [`EventHandler::on_member_connect`](trait.EventHandler.html#method.on_member_connect) here.
+    pub fn on_member_connect<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> Result<Option<NetworkPeerID>> {
+        self.connect(discord, lobby_id, member_id)
+    }
+
+    /// Connects to the member, or updates the connection when their route changed.
+    ///
+    /// Returns the peer ID of the member if a new connection was opened.
+    ///
+    /// Forward [`EventHandler::on_member_update`](trait.EventHandler.html#method.on_member_update) here.
+    pub fn on_member_update<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> Result<Option<NetworkPeerID>> {
+        self.connect(discord, lobby_id, member_id)
+    }
+
+    /// Closes the connection to the member if they share no other lobby with the current user.
+    ///
+    /// Returns the peer ID of the member if the connection was closed.
+    ///
+    /// Forward [`EventHandler::on_member_disconnect`](trait.EventHandler.html#method.on_member_disconnect) here.
+    pub fn on_member_disconnect<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> Result<Option<NetworkPeerID>> {
+        self.disconnect(discord, lobby_id, member_id)
+    }
+
+    /// Closes the connections to members that no longer share a lobby with the current user.
+    ///
+    /// Forward [`EventHandler::on_lobby_delete`](trait.EventHandler.html#method.on_lobby_delete) here.
+    pub fn on_lobby_delete<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+    ) -> Result<()> {
+        self.leave(discord, lobby_id)
+    }
+
+    fn connect<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> Result<Option<NetworkPeerID>> {
+        if !self.lobbies.contains(&lobby_id) || member_id == discord.current_user()?.id() {
+            return Ok(None);
+        }
+
+        let peer_id = discord
+            .lobby_member_metadata(lobby_id, member_id, Self::PEER_ID_KEY)
+            .ok()
+            .and_then(|value| value.parse::<NetworkPeerID>().ok());
+
+        let route = discord
+            .lobby_member_metadata(lobby_id, member_id, Self::ROUTE_KEY)
+            .ok();
+
+        let (peer_id, route) = match (peer_id, route) {
+            (Some(peer_id), Some(route)) => (peer_id, route),
+            // The member has not published their route yet
+            _ => return Ok(None),
+        };
+
+        let step = plan(self.peers.get(&member_id), peer_id, &route);
+
+        match step {
+            Step::Keep | Step::Open => {}
+
+            Step::Update => discord.update_peer(peer_id, route.as_str())?,
+
+            Step::Reopen(previous) => {
+                // The member restarted their game, the previous peer is gone and may already be closed
+                if let Err(error) = discord.close_peer(previous) {
+                    log::warn!("failed to close previous peer {}: {}", previous, error);
+                }
+            }
+        }
+
+        // Recorded before opening, so the stale peer ID is never kept
+        let peer = self.peers.entry(member_id).or_insert_with(|| Peer {
+            peer_id,
+            route: route.clone(),
+            lobbies: HashSet::new(),
+            opened: false,
+        });
+
+        peer.peer_id = peer_id;
+        peer.route = route.clone();
+        let _ = peer.lobbies.insert(lobby_id);
+
+        match step {
+            Step::Open | Step::Reopen(_) => {
+                let result = self.open(discord, peer_id, &route);
+
+                if result.is_err() {
+                    // The peer may be half-open, it is opened again on the next update
+                    let _ = discord.close_peer(peer_id);
+                }
+
+                if let Some(peer) = self.peers.get_mut(&member_id) {
+                    peer.opened = result.is_ok();
+                }
+
+                result.map(|()| Some(peer_id))
+            }
+
+            Step::Update | Step::Keep => Ok(None),
+        }
+    }
+
+    fn open<E>(&self, discord: &Discord<'_, E>, peer_id: NetworkPeerID, route: &str) -> Result<()> {
+        discord.open_peer(peer_id, route)?;
+
+        for (channel_id, reliability) in &self.channels {
+            discord.open_channel(peer_id, *channel_id, *reliability)?;
+        }
+
+        Ok(())
+    }
+
+    fn disconnect<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> Result<Option<NetworkPeerID>> {
+        let peer = match self.peers.get_mut(&member_id) {
+            Some(peer) => peer,
+            None => return Ok(None),
+        };
+
+        let _ = peer.lobbies.remove(&lobby_id);
+
+        if !peer.lobbies.is_empty() {
+            return Ok(None);
+        }
+
+        let peer_id = peer.peer_id;
+        let opened = peer.opened;
+        let _ = self.peers.remove(&member_id);
+
+        if !opened {
+            return Ok(None);
+        }
+
+        discord.close_peer(peer_id)?;
+
+        Ok(Some(peer_id))
+    }
+}
+
+fn plan(peer: Option<&Peer>, peer_id: NetworkPeerID, route: &str) -> Step {
+    match peer {
+        None => Step::Open,
+        Some(peer) if !peer.opened => Step::Open,
+        Some(peer) if peer.peer_id != peer_id => Step::Reopen(peer.peer_id),
+        Some(peer) if peer.route != route => Step::Update,
+        Some(_) => Step::Keep,
+    }
+}
+
+fn publish<E>(
+    discord: &Discord<'_, E>,
+    lobby_id: LobbyID,
+    user_id: UserID,
+    peer_id: NetworkPeerID,
+    route: &str,
+) {
+    discord.update_member(
+        lobby_id,
+        user_id,
+        LobbyMemberTransaction::new()
+            .add_metadata(PeerManager::PEER_ID_KEY.to_string(), peer_id.to_string())
+            .add_metadata(PeerManager::ROUTE_KEY.to_string(), route.to_string()),
+        move |_, result| {
+            if let Err(error) = result {
+                log::warn!("failed to publish route in lobby {}: {}", lobby_id, error);
+            }
+        },
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan() {
+        let peer = Peer {
+            peer_id: 1,
+            route: "a".to_string(),
+            lobbies: HashSet::new(),
+            opened: true,
+        };
+
+        assert_eq!(plan(None, 1, "a"), Step::Open);
+        assert_eq!(plan(Some(&peer), 1, "a"), Step::Keep);
+        assert_eq!(plan(Some(&peer), 1, "b"), Step::Update);
+        assert_eq!(plan(Some(&peer), 2, "a"), Step::Reopen(1));
+
+        // Opening failed, the previous peer was already closed
+        let peer = Peer {
+            opened: false,
+            ..peer
+        };
+
+        assert_eq!(plan(Some(&peer), 1, "a"), Step::Open);
+        assert_eq!(plan(Some(&peer), 2, "a"), Step::Open);
+    }
+}
@@ -0,0 +1,18 @@
+use crate::{Codec, CodecError};
+use serde_crate::{de::DeserializeOwned, Serialize};
+
+/// [`Codec`](trait.Codec.html) for types implementing `serde` traits, using [`postcard`](https://docs.rs/postcard)
+///
+/// Requires the `postcard` feature.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct PostcardCodec;
+
+impl<T: Serialize + DeserializeOwned> Codec<T> for PostcardCodec {
+    fn encode(&self, value: &T) -> Result<Vec<u8>, CodecError> {
+        postcard_crate::to_allocvec(value).map_err(CodecError::new)
+    }
+
+    fn decode(&self, data: &[u8]) -> Result<T, CodecError> {
+        postcard_crate::from_bytes(data).map_err(CodecError::new)
+    }
+}
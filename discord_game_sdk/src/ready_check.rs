@@ -0,0 +1,456 @@
+use crate::{
+    Discord, LobbyID, LobbyMemberTransaction, LobbyTransaction, MetadataValue, ReadyState, Result,
+    UserID,
+};
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Ready-check and countdown for lobbies
+///
+/// Every member marks themselves ready in their member metadata.
+/// Once every member is ready, the lobby owner stores the time at which the game starts
+/// in the lobby metadata, and every member counts down to it.
+/// Any member can cancel the countdown, which is broadcast as a lobby message.
+///
+/// The component is opt-in, events must be forwarded to it from the
+/// [`EventHandler`](trait.EventHandler.html), and [`poll`](#method.poll) must be called
+/// regularly to notice that the countdown elapsed.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # use std::time::Duration;
+/// struct MyEventHandler {
+///     ready_check: ReadyCheck,
+/// }
+///
+/// impl MyEventHandler {
+///     fn on_ready_state(&self, lobby_id: LobbyID, state: ReadyState) {
+///         println!("lobby {} is now {:?}", lobby_id, state);
+///     }
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_member_update(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///     ) {
+///         if let Ok(Some(state)) = self.ready_check.on_member_update(discord, lobby_id, member_id) {
+///             self.on_ready_state(lobby_id, state);
+///         }
+///     }
+///
+///     fn on_lobby_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///         data: &[u8],
+///     ) {
+///         if let Ok(Some(state)) =
+///             self.ready_check.on_lobby_message(discord, lobby_id, member_id, data)
+///         {
+///             self.on_ready_state(lobby_id, state);
+///         }
+///     }
+///
+///     // ... on_lobby_update, on_member_connect and on_member_disconnect likewise
+/// }
+///
+/// # fn example(mut discord: Discord<'_, MyEventHandler>) -> Result<()> {
+/// *discord.event_handler_mut() = Some(MyEventHandler {
+///     ready_check: ReadyCheck::new(Duration::from_secs(5)),
+/// });
+///
+/// loop {
+///     discord.run_callbacks()?;
+///
+///     let handler = discord.event_handler_mut().as_mut().unwrap();
+///
+///     for lobby_id in handler.ready_check.poll() {
+///         println!("starting the game in lobby {}", lobby_id);
+///     }
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct ReadyCheck {
+    countdown: Duration,
+    lobbies: HashMap<LobbyID, LobbyReadyCheck>,
+}
+
+#[derive(Clone, Debug)]
+struct LobbyReadyCheck {
+    state: ReadyState,
+    // A cancelled start time is ignored until the owner removes it
+    cancelled: Option<u64>,
+    start_requested: bool,
+}
+
+impl Default for LobbyReadyCheck {
+    fn default() -> Self {
+        Self {
+            state: ReadyState::Waiting,
+            cancelled: None,
+            start_requested: false,
+        }
+    }
+}
+
+impl ReadyCheck {
+    /// The member metadata key under which the ready flag is stored
+    pub const READY_KEY: &'static str = "ready_check.ready";
+
+    /// The lobby metadata key under which the start time is stored, in milliseconds since the Unix epoch
+    pub const START_KEY: &'static str = "ready_check.starts_at";
+
+    /// The lobby message sent to cancel the countdown
+    pub const CANCEL_MESSAGE: &'static [u8] = b"ready_check.cancel";
+
+    /// Creates a ready-check where the game starts `countdown` after every member is ready.
+    pub fn new(countdown: Duration) -> Self {
+        Self {
+            countdown,
+            lobbies: HashMap::new(),
+        }
+    }
+
+    /// The current state of a given lobby.
+    pub fn state(&self, lobby_id: LobbyID) -> ReadyState {
+        self.lobbies
+            .get(&lobby_id)
+            .map(|lobby| lobby.state)
+            .unwrap_or(ReadyState::Waiting)
+    }
+
+    /// Marks the current user as ready or not.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if the current user is not available yet.
+    pub fn set_ready<'d, E>(
+        discord: &Discord<'d, E>,
+        lobby_id: LobbyID,
+        ready: bool,
+        callback: impl 'd + FnOnce(&Discord<'d, E>, Result<()>),
+    ) -> Result<()> {
+        let user_id = discord.current_user()?.id();
+
+        discord.update_member(
+            lobby_id,
+            user_id,
+            LobbyMemberTransaction::new()
+                .add_metadata(Self::READY_KEY.to_string(), ready.to_metadata_value()),
+            callback,
+        );
+
+        Ok(())
+    }
+
+    /// Cancels the countdown, marking the current user as not ready.
+    ///
+    /// Returns the new state if it changed.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if the current user or the lobby are not available.
+    /// Failures to broadcast the cancellation are passed to `callback`.
+    pub fn cancel<'d, E>(
+        &mut self,
+        discord: &Discord<'d, E>,
+        lobby_id: LobbyID,
+        callback: impl 'd + FnOnce(&Discord<'d, E>, Result<()>),
+    ) -> Result<Option<ReadyState>> {
+        Self::set_ready(discord, lobby_id, false, |_, result| {
+            if let Err(error) = result {
+                log::warn!("failed to clear ready flag: {}", error);
+            }
+        })?;
+
+        discord.send_lobby_message(lobby_id, Self::CANCEL_MESSAGE, callback);
+
+        self.cancelled(discord, lobby_id)
+    }
+
+    /// Goes back to [`ReadyState::Waiting`](enum.ReadyState.html#variant.Waiting) after the game
+    /// ended, removing the start time if the current user owns the lobby.
+    ///
+    /// Members must mark themselves ready again.
+    pub fn reset<E>(&mut self, discord: &Discord<'_, E>, lobby_id: LobbyID) -> Result<()> {
+        let _ = self.lobbies.remove(&lobby_id);
+
+        if discord.lobby(lobby_id)?.owner_id() == discord.current_user()?.id() {
+            delete_start(discord, lobby_id);
+        }
+
+        Ok(())
+    }
+
+    /// Moves every lobby whose countdown elapsed to
+    /// [`ReadyState::Started`](enum.ReadyState.html#variant.Started).
+    ///
+    /// Returns those lobbies.
+    pub fn poll(&mut self) -> Vec<LobbyID> {
+        let now = SystemTime::now();
+        let mut started = Vec::new();
+
+        for (lobby_id, lobby) in &mut self.lobbies {
+            if let ReadyState::CountingDown { starts_at } = lobby.state {
+                if now >= starts_at {
+                    lobby.state = ReadyState::Started;
+                    started.push(*lobby_id);
+                }
+            }
+        }
+
+        started
+    }
+
+    /// Forward [`EventHandler::on_lobby_update`](trait.EventHandler.html#method.on_lobby_update) here.
+    ///
+    /// Returns the new state if it changed.
+    pub fn on_lobby_update<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+    ) -> Result<Option<ReadyState>> {
+        self.refresh(discord, lobby_id)
+    }
+
+    /// Forgets the lobby.
+    ///
+    /// Forward [`EventHandler::on_lobby_delete`](trait.EventHandler.html#method.on_lobby_delete) here,
+    /// and call it after [`disconnect_lobby`](struct.Discord.html#method.disconnect_lobby) succeeds.
+    pub fn on_lobby_delete(&mut self, lobby_id: LobbyID) {
+        let _ = self.lobbies.remove(&lobby_id);
+    }
+
+    /// Forward [`EventHandler::on_member_connect`](trait.EventHandler.html#method.on_member_connect) here.
+    ///
+    /// Returns the new state if it changed.
+    pub fn on_member_connect<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        _member_id: UserID,
+    ) -> Result<Option<ReadyState>> {
+        self.refresh(discord, lobby_id)
+    }
+
+    /// Forward [`EventHandler::on_member_update`](trait.EventHandler.html#method.on_member_update) here.
+    ///
+    /// Returns the new state if it changed.
+    pub fn on_member_update<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        _member_id: UserID,
+    ) -> Result<Option<ReadyState>> {
+        self.refresh(discord, lobby_id)
+    }
+
+    /// Forward [`EventHandler::on_member_disconnect`](trait.EventHandler.html#method.on_member_disconnect) here.
+    ///
+    /// Returns the new state if it changed.
+    pub fn on_member_disconnect<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        _member_id: UserID,
+    ) -> Result<Option<ReadyState>> {
+        self.refresh(discord, lobby_id)
+    }
+
+    /// Handles cancellations, other messages are ignored.
+    ///
+    /// Forward [`EventHandler::on_lobby_message`](trait.EventHandler.html#method.on_lobby_message) here.
+    ///
+    /// Returns the new state if it changed.
+    pub fn on_lobby_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        _member_id: UserID,
+        data: &[u8],
+    ) -> Result<Option<ReadyState>> {
+        if data != Self::CANCEL_MESSAGE {
+            return Ok(None);
+        }
+
+        self.cancelled(discord, lobby_id)
+    }
+
+    fn cancelled<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+    ) -> Result<Option<ReadyState>> {
+        let lobby = self.lobbies.entry(lobby_id).or_default();
+
+        lobby.cancelled = start_time(discord, lobby_id);
+        lobby.start_requested = false;
+
+        if discord.lobby(lobby_id)?.owner_id() == discord.current_user()?.id() {
+            delete_start(discord, lobby_id);
+        }
+
+        Ok(if lobby.state == ReadyState::Waiting {
+            None
+        } else {
+            lobby.state = ReadyState::Waiting;
+            Some(ReadyState::Waiting)
+        })
+    }
+
+    fn refresh<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+    ) -> Result<Option<ReadyState>> {
+        let is_owner = discord.lobby(lobby_id)?.owner_id() == discord.current_user()?.id();
+
+        let mut ready = Vec::new();
+
+        for user_id in discord.iter_lobby_member_ids(lobby_id)? {
+            let flag = discord
+                .lobby_member_metadata(lobby_id, user_id?, Self::READY_KEY)
+                .ok()
+                .and_then(|value| bool::from_metadata_value(&value))
+                .unwrap_or(false);
+
+            ready.push(flag);
+        }
+
+        let start = start_time(discord, lobby_id);
+        let lobby = self.lobbies.entry(lobby_id).or_default();
+
+        if start.is_some() && start != lobby.cancelled {
+            lobby.cancelled = None;
+        }
+
+        let starts_at = start
+            .filter(|start| Some(*start) != lobby.cancelled)
+            .map(|start| UNIX_EPOCH + Duration::from_millis(start));
+
+        let state = next_state(lobby.state, &ready, starts_at, SystemTime::now());
+
+        if is_owner {
+            match state {
+                ReadyState::AllReady if !lobby.start_requested => {
+                    lobby.start_requested = true;
+
+                    let starts_at = SystemTime::now() + self.countdown;
+
+                    discord.update_lobby(
+                        lobby_id,
+                        LobbyTransaction::new()
+                            .add_metadata(Self::START_KEY.to_string(), unix_millis(starts_at)),
+                        |_, result| {
+                            if let Err(error) = result {
+                                log::warn!("failed to start countdown: {}", error);
+                            }
+                        },
+                    );
+                }
+
+                ReadyState::Waiting if start.is_some() => delete_start(discord, lobby_id),
+
+                _ => {}
+            }
+        }
+
+        if state != ReadyState::AllReady {
+            lobby.start_requested = false;
+        }
+
+        Ok(if state == lobby.state {
+            None
+        } else {
+            lobby.state = state;
+            Some(state)
+        })
+    }
+}
+
+fn next_state(
+    previous: ReadyState,
+    ready: &[bool],
+    starts_at: Option<SystemTime>,
+    now: SystemTime,
+) -> ReadyState {
+    if previous == ReadyState::Started {
+        return ReadyState::Started;
+    }
+
+    if ready.is_empty() || ready.iter().any(|ready| !ready) {
+        return ReadyState::Waiting;
+    }
+
+    match starts_at {
+        Some(starts_at) if now >= starts_at => ReadyState::Started,
+        Some(starts_at) => ReadyState::CountingDown { starts_at },
+        None => ReadyState::AllReady,
+    }
+}
+
+fn start_time<E>(discord: &Discord<'_, E>, lobby_id: LobbyID) -> Option<u64> {
+    discord
+        .lobby_metadata(lobby_id, ReadyCheck::START_KEY)
+        .ok()
+        .and_then(|value| value.parse().ok())
+}
+
+fn unix_millis(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_millis())
+        .unwrap_or(0)
+        .to_string()
+}
+
+fn delete_start<E>(discord: &Discord<'_, E>, lobby_id: LobbyID) {
+    let mut transaction = LobbyTransaction::new();
+    transaction.delete_metadata(ReadyCheck::START_KEY.to_string());
+
+    discord.update_lobby(lobby_id, &transaction, |_, result| {
+        if let Err(error) = result {
+            log::warn!("failed to clear countdown: {}", error);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_state() {
+        let now = UNIX_EPOCH + Duration::from_secs(100);
+        let later = now + Duration::from_secs(5);
+        let waiting = ReadyState::Waiting;
+
+        assert_eq!(next_state(waiting, &[], None, now), ReadyState::Waiting);
+        assert_eq!(
+            next_state(waiting, &[true, false], None, now),
+            ReadyState::Waiting
+        );
+        assert_eq!(
+            next_state(waiting, &[true, true], None, now),
+            ReadyState::AllReady
+        );
+        assert_eq!(
+            next_state(waiting, &[true, true], Some(later), now),
+            ReadyState::CountingDown { starts_at: later }
+        );
+        assert_eq!(
+            next_state(waiting, &[true], Some(now), now),
+            ReadyState::Started
+        );
+        assert_eq!(
+            next_state(ReadyState::Started, &[false], None, now),
+            ReadyState::Started
+        );
+    }
+}
@@ -0,0 +1,20 @@
+use std::time::SystemTime;
+
+/// State of a [`ReadyCheck`](struct.ReadyCheck.html) in a lobby
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ReadyState {
+    /// Some members are not ready
+    Waiting,
+
+    /// Every member is ready, the owner is about to start the countdown
+    AllReady,
+
+    /// The owner started the countdown
+    CountingDown {
+        /// When the game starts
+        starts_at: SystemTime,
+    },
+
+    /// The countdown elapsed, the game has started
+    Started,
+}
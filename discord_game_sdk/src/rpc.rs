@@ -0,0 +1,363 @@
+use crate::{
+    Codec, Discord, LobbyID, NetworkChannelID, NetworkPeerID, RpcCallID, RpcEndpoint, RpcError,
+    RpcEvent, UserID,
+};
+use std::{
+    collections::HashMap,
+    convert::TryInto,
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
+
+const HEADER_LEN: usize = 9;
+
+const REQUEST: u8 = 0;
+const RESPONSE: u8 = 1;
+const FAILURE: u8 = 2;
+
+/// Remote procedure calls over a network channel
+///
+/// Requests of type `Req` and responses of type `Res` are encoded with a
+/// [`Codec`](trait.Codec.html) and framed with a kind byte and a little-endian 64 bits call ID,
+/// which correlates every response to its request.
+///
+/// Calls that are not answered in time fail with [`RpcError::Timeout`](enum.RpcError.html#variant.Timeout)
+/// when [`poll`](#method.poll) is called, which should be done every frame after
+/// [`run_callbacks`](struct.Discord.html#method.run_callbacks).
+/// Requests that cannot be decoded are answered with an error automatically.
+///
+/// The channel should be opened with [`Reliability::Reliable`](enum.Reliability.html#variant.Reliable).
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # use std::time::Duration;
+/// struct MyEventHandler {
+///     rpc: Rpc<Vec<u8>, Vec<u8>, BytesCodec>,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_network_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         peer_id: NetworkPeerID,
+///         channel_id: NetworkChannelID,
+///         data: &[u8],
+///     ) {
+///         match self.rpc.on_network_message(discord, peer_id, channel_id, data) {
+///             Some(RpcEvent::Request { endpoint, call_id, request }) => {
+///                 let inventory = vec![request.len() as u8];
+///                 let _ = self.rpc.respond(discord, endpoint, call_id, Ok(&inventory));
+///             }
+///
+///             Some(RpcEvent::Response { result, .. }) => println!("{:?}", result),
+///
+///             None => {}
+///         }
+///     }
+///
+///     // ...
+/// }
+///
+/// # fn example(mut discord: Discord<'_, MyEventHandler>, peer_id: NetworkPeerID) -> std::result::Result<(), RpcError> {
+/// let mut rpc = Rpc::new(2, BytesCodec);
+///
+/// let call_id = rpc.call(
+///     &discord,
+///     RpcEndpoint::Peer(peer_id),
+///     &b"inventory".to_vec(),
+///     Duration::from_secs(5),
+/// )?;
+///
+/// // Every frame
+/// discord.run_callbacks()?;
+///
+/// for event in rpc.poll() {
+///     if let RpcEvent::Response { call_id, result: Err(error), .. } = event {
+///         eprintln!("call {} failed: {}", call_id, error);
+///     }
+/// }
+/// # Ok(()) }
+/// ```
+pub struct Rpc<Req, Res, C> {
+    channel_id: NetworkChannelID,
+    codec: C,
+    next_call_id: RpcCallID,
+    pending: HashMap<RpcCallID, (RpcEndpoint, Instant)>,
+    payloads: PhantomData<fn() -> (Req, Res)>,
+}
+
+impl<Req, Res, C: Codec<Req> + Codec<Res>> Rpc<Req, Res, C> {
+    /// Creates an RPC layer over the given channel ID, encoding payloads with `codec`.
+    pub fn new(channel_id: NetworkChannelID, codec: C) -> Self {
+        Self {
+            channel_id,
+            codec,
+            next_call_id: 0,
+            pending: HashMap::new(),
+            payloads: PhantomData,
+        }
+    }
+
+    /// The ID of the channel
+    pub fn channel_id(&self) -> NetworkChannelID {
+        self.channel_id
+    }
+
+    /// The number of calls waiting for a response
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Sends a request, its response is produced by
+    /// [`on_network_message`](#method.on_network_message) or
+    /// [`on_lobby_network_message`](#method.on_lobby_network_message) with the returned call ID.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if the request cannot be encoded or sent, the call is then not pending.
+    pub fn call<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        endpoint: RpcEndpoint,
+        request: &Req,
+        timeout: Duration,
+    ) -> Result<RpcCallID, RpcError> {
+        let call_id = self.next_call_id;
+        let payload = Codec::<Req>::encode(&self.codec, request)?;
+
+        self.send(discord, endpoint, &frame(REQUEST, call_id, &payload))?;
+
+        self.next_call_id += 1;
+        let _ = self
+            .pending
+            .insert(call_id, (endpoint, Instant::now() + timeout));
+
+        Ok(call_id)
+    }
+
+    /// Answers a request with a response, or an error message.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if the response cannot be encoded or sent.
+    pub fn respond<E>(
+        &self,
+        discord: &Discord<'_, E>,
+        endpoint: RpcEndpoint,
+        call_id: RpcCallID,
+        response: Result<&Res, &str>,
+    ) -> Result<(), RpcError> {
+        let data = match response {
+            Ok(response) => frame(
+                RESPONSE,
+                call_id,
+                &Codec::<Res>::encode(&self.codec, response)?,
+            ),
+            Err(message) => frame(FAILURE, call_id, message.as_bytes()),
+        };
+
+        self.send(discord, endpoint, &data)
+    }
+
+    /// Fails the calls whose timeout expired.
+    ///
+    /// Call this every frame after [`run_callbacks`](struct.Discord.html#method.run_callbacks).
+    pub fn poll(&mut self) -> Vec<RpcEvent<Req, Res>> {
+        self.expire(Instant::now())
+    }
+
+    /// Decodes a request or a response, returning `None` if the message was received
+    /// on another channel, was malformed, or answers a call that already timed out.
+    ///
+    /// Forward [`EventHandler::on_network_message`](trait.EventHandler.html#method.on_network_message) here.
+    pub fn on_network_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> Option<RpcEvent<Req, Res>> {
+        if channel_id != self.channel_id {
+            return None;
+        }
+
+        self.receive(discord, RpcEndpoint::Peer(peer_id), data)
+    }
+
+    /// Decodes a request or a response, returning `None` if the message was received
+    /// on another channel, was malformed, or answers a call that already timed out.
+    ///
+    /// Forward [`EventHandler::on_lobby_network_message`](trait.EventHandler.html#method.on_lobby_network_message) here.
+    pub fn on_lobby_network_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> Option<RpcEvent<Req, Res>> {
+        if channel_id != self.channel_id {
+            return None;
+        }
+
+        self.receive(discord, RpcEndpoint::Member(lobby_id, member_id), data)
+    }
+
+    fn send<E>(
+        &self,
+        discord: &Discord<'_, E>,
+        endpoint: RpcEndpoint,
+        data: &[u8],
+    ) -> Result<(), RpcError> {
+        match endpoint {
+            RpcEndpoint::Peer(peer_id) => discord.send_message(peer_id, self.channel_id, data)?,
+
+            RpcEndpoint::Member(lobby_id, user_id) => {
+                discord.send_lobby_network_message(lobby_id, user_id, self.channel_id, data)?
+            }
+        }
+
+        Ok(())
+    }
+
+    fn receive<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        endpoint: RpcEndpoint,
+        data: &[u8],
+    ) -> Option<RpcEvent<Req, Res>> {
+        if data.len() < HEADER_LEN {
+            log::warn!("dropped truncated RPC message from {:?}", endpoint);
+            return None;
+        }
+
+        let kind = data[0];
+        let call_id = RpcCallID::from_le_bytes(data[1..HEADER_LEN].try_into().unwrap());
+        let payload = &data[HEADER_LEN..];
+
+        if kind == REQUEST {
+            return match Codec::<Req>::decode(&self.codec, payload) {
+                Ok(request) => Some(RpcEvent::Request {
+                    endpoint,
+                    call_id,
+                    request,
+                }),
+
+                Err(error) => {
+                    let message = format!("invalid request: {}", error);
+
+                    if let Err(error) = self.respond(discord, endpoint, call_id, Err(&message)) {
+                        log::warn!("failed to reject RPC from {:?}: {}", endpoint, error);
+                    }
+
+                    None
+                }
+            };
+        }
+
+        // Responses are only accepted from the endpoint that was called
+        match self.pending.get(&call_id) {
+            Some((callee, _)) if *callee == endpoint => {}
+            _ => return None,
+        }
+
+        let result = match kind {
+            RESPONSE => Codec::<Res>::decode(&self.codec, payload).map_err(RpcError::from),
+            FAILURE => Err(RpcError::Remote(
+                String::from_utf8_lossy(payload).into_owned(),
+            )),
+            _ => {
+                log::warn!("dropped RPC message of unknown kind {}", kind);
+                return None;
+            }
+        };
+
+        let _ = self.pending.remove(&call_id);
+
+        Some(RpcEvent::Response {
+            endpoint,
+            call_id,
+            result,
+        })
+    }
+
+    fn expire(&mut self, now: Instant) -> Vec<RpcEvent<Req, Res>> {
+        let mut expired = self
+            .pending
+            .iter()
+            .filter(|(_, (_, deadline))| *deadline <= now)
+            .map(|(call_id, (endpoint, _))| (*call_id, *endpoint))
+            .collect::<Vec<_>>();
+
+        expired.sort_by_key(|(call_id, _)| *call_id);
+
+        expired
+            .into_iter()
+            .map(|(call_id, endpoint)| {
+                let _ = self.pending.remove(&call_id);
+
+                RpcEvent::Response {
+                    endpoint,
+                    call_id,
+                    result: Err(RpcError::Timeout),
+                }
+            })
+            .collect()
+    }
+}
+
+impl<Req, Res, C: std::fmt::Debug> std::fmt::Debug for Rpc<Req, Res, C> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("Rpc")
+            .field("channel_id", &self.channel_id)
+            .field("codec", &self.codec)
+            .field("next_call_id", &self.next_call_id)
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+fn frame(kind: u8, call_id: RpcCallID, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(HEADER_LEN + payload.len());
+    data.push(kind);
+    data.extend_from_slice(&call_id.to_le_bytes());
+    data.extend_from_slice(payload);
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BytesCodec;
+
+    #[test]
+    fn test_expire() {
+        let mut rpc = Rpc::<Vec<u8>, Vec<u8>, _>::new(0, BytesCodec);
+        let now = Instant::now();
+
+        for call_id in 0..3 {
+            let timeout = Duration::from_secs(call_id);
+            let _ = rpc
+                .pending
+                .insert(call_id, (RpcEndpoint::Peer(1), now + timeout));
+        }
+
+        let expired = rpc.expire(now + Duration::from_secs(1));
+        assert_eq!(expired.len(), 2);
+        assert_eq!(rpc.pending(), 1);
+
+        match &expired[1] {
+            RpcEvent::Response {
+                call_id,
+                result: Err(RpcError::Timeout),
+                ..
+            } => assert_eq!(*call_id, 1),
+            event => panic!("unexpected event {:?}", event),
+        }
+
+        assert_eq!(
+            frame(FAILURE, 1, b"x"),
+            vec![2, 1, 0, 0, 0, 0, 0, 0, 0, b'x']
+        );
+    }
+}
@@ -0,0 +1,11 @@
+use crate::{LobbyID, NetworkPeerID, UserID};
+
+/// Remote end of an [`Rpc`](struct.Rpc.html) call
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RpcEndpoint {
+    /// Reached with [`send_message`](struct.Discord.html#method.send_message)
+    Peer(NetworkPeerID),
+
+    /// Reached with [`send_lobby_network_message`](struct.Discord.html#method.send_lobby_network_message)
+    Member(LobbyID, UserID),
+}
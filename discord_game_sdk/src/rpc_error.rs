@@ -0,0 +1,51 @@
+use crate::{CodecError, Error};
+use std::fmt;
+
+/// Failure of an [`Rpc`](struct.Rpc.html) call
+#[derive(Debug)]
+pub enum RpcError {
+    /// No response was received before the timeout
+    Timeout,
+
+    /// The remote end responded with an error
+    Remote(String),
+
+    /// The request or response could not be encoded or decoded
+    Codec(CodecError),
+
+    /// The request could not be sent by the SDK
+    Discord(Error),
+}
+
+impl From<CodecError> for RpcError {
+    fn from(error: CodecError) -> Self {
+        Self::Codec(error)
+    }
+}
+
+impl From<Error> for RpcError {
+    fn from(error: Error) -> Self {
+        Self::Discord(error)
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "no response before the timeout"),
+            Self::Remote(message) => write!(f, "remote error: {}", message),
+            Self::Codec(error) => write!(f, "{}", error),
+            Self::Discord(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Codec(error) => Some(error),
+            Self::Discord(error) => Some(error),
+            _ => None,
+        }
+    }
+}
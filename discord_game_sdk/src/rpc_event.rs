@@ -0,0 +1,25 @@
+use crate::{RpcCallID, RpcEndpoint, RpcError};
+
+/// Event produced by an [`Rpc`](struct.Rpc.html)
+#[derive(Debug)]
+pub enum RpcEvent<Req, Res> {
+    /// A remote end made a call, answer it with [`Rpc::respond`](struct.Rpc.html#method.respond)
+    Request {
+        /// Caller
+        endpoint: RpcEndpoint,
+        /// ID to respond to
+        call_id: RpcCallID,
+        /// Decoded request
+        request: Req,
+    },
+
+    /// A call made by the current user completed, failed or timed out
+    Response {
+        /// Callee
+        endpoint: RpcEndpoint,
+        /// ID returned by [`Rpc::call`](struct.Rpc.html#method.call)
+        call_id: RpcCallID,
+        /// Decoded response
+        result: Result<Res, RpcError>,
+    },
+}
@@ -0,0 +1,29 @@
+/// Contents of a [`SaveSlot`](struct.SaveSlot.html)
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct SaveData {
+    pub(crate) generation: u64,
+    pub(crate) version: u32,
+    pub(crate) data: Vec<u8>,
+}
+
+impl SaveData {
+    /// The number of times the slot was saved, starting from 1
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The format version the data was saved with
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// The saved data
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Takes ownership of the saved data
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+}
@@ -0,0 +1,62 @@
+use crate::{CodecError, Error, SaveSlotError};
+use std::fmt;
+
+/// Error while saving or loading a value with a [`SaveFormat`](struct.SaveFormat.html)
+#[derive(Debug)]
+pub enum SaveError {
+    /// Every copy of the save is corrupted
+    Corrupted,
+
+    /// The save is in a newer version, or no migration upgrades it from this version
+    UnsupportedVersion(u32),
+
+    /// The value could not be encoded, or the save could not be decoded or migrated
+    Codec(CodecError),
+
+    /// The storage could not be read or written by the SDK
+    Discord(Error),
+}
+
+impl From<Error> for SaveError {
+    fn from(error: Error) -> Self {
+        Self::Discord(error)
+    }
+}
+
+impl From<CodecError> for SaveError {
+    fn from(error: CodecError) -> Self {
+        Self::Codec(error)
+    }
+}
+
+impl From<SaveSlotError> for SaveError {
+    fn from(error: SaveSlotError) -> Self {
+        match error {
+            SaveSlotError::Corrupted => Self::Corrupted,
+            SaveSlotError::Discord(error) => Self::Discord(error),
+        }
+    }
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Corrupted => write!(f, "every copy of the save is corrupted"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "no migration from save version {}", version)
+            }
+            Self::Codec(error) => write!(f, "{}", error),
+            Self::Discord(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Corrupted | Self::UnsupportedVersion(_) => None,
+            Self::Codec(error) => Some(error),
+            Self::Discord(error) => Some(error),
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use crate::{Codec, CodecError, Discord, SaveData, SaveError, SaveSlot};
+use std::{collections::BTreeMap, rc::Rc};
+
+type Migration = Rc<dyn Fn(&[u8]) -> Result<Vec<u8>, CodecError>>;
+
+/// Format of values saved to the game's allocated storage
+///
+/// Values are encoded with a [`Codec`](trait.Codec.html), such as
+/// [`JsonCodec`](struct.JsonCodec.html), `BincodeCodec` or `MessagePackCodec`,
+/// and stored in a crash-safe [`SaveSlot`](struct.SaveSlot.html) tagged with the version of the format.
+///
+/// When the layout of saved values changes, the version is bumped and a migration is registered to
+/// upgrade saves from the previous version. Loading runs the migrations from the version of the save
+/// up to the current one, saves are upgraded in storage the next time they are saved.
+///
+/// Saves do not record their codec, so a name must always be saved and loaded with the same one.
+/// Loading a save written with another codec fails with
+/// [`SaveError::Codec`](enum.SaveError.html#variant.Codec). [`Discord::save`](struct.Discord.html#method.save)
+/// and [`Discord::load`](struct.Discord.html#method.load) use `JsonCodec` in version 0.
+///
+/// Requires the `serde` feature.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # fn example(discord: Discord<'_, ()>) -> std::result::Result<(), SaveError> {
+/// let mut format = SaveFormat::new(JsonCodec);
+///
+/// // Version 0 saved the best score alone, version 1 saves every score
+/// format.version(1).migration(0, |data| {
+///     let best: u32 = JsonCodec.decode(data)?;
+///     JsonCodec.encode(&vec![best])
+/// });
+///
+/// let scores: Vec<u32> = format.load(&discord, "scores")?.unwrap_or_default();
+///
+/// format.save(&discord, "scores", &scores)?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone)]
+pub struct SaveFormat<C> {
+    codec: C,
+    version: u32,
+    // Upgrades from each version to the next
+    migrations: BTreeMap<u32, Migration>,
+}
+
+impl<C> SaveFormat<C> {
+    /// Creates a format encoding values with a codec, in version 0.
+    pub fn new(codec: C) -> Self {
+        Self {
+            codec,
+            version: 0,
+            migrations: BTreeMap::new(),
+        }
+    }
+
+    /// Sets the current version of the format, written with every save.
+    pub fn version(&mut self, version: u32) -> &mut Self {
+        self.version = version;
+        self
+    }
+
+    /// Registers a migration upgrading the encoded data of saves in a given version
+    /// to the next version.
+    pub fn migration(
+        &mut self,
+        version: u32,
+        migrate: impl 'static + Fn(&[u8]) -> Result<Vec<u8>, CodecError>,
+    ) -> &mut Self {
+        let _ = self.migrations.insert(version, Rc::new(migrate));
+        self
+    }
+
+    /// Encodes and saves a value under a given name.
+    pub fn save<E, T>(
+        &self,
+        discord: &Discord<'_, E>,
+        name: &str,
+        value: &T,
+    ) -> Result<(), SaveError>
+    where
+        C: Codec<T>,
+    {
+        let data = self.codec.encode(value)?;
+
+        self.slot(name).save(discord, &data)?;
+
+        Ok(())
+    }
+
+    /// Encodes and saves a value under a given name asynchronously.
+    pub fn save_async<'d, E, T>(
+        &self,
+        discord: &Discord<'d, E>,
+        name: &str,
+        value: &T,
+        callback: impl 'd + FnOnce(&Discord<'d, E>, Result<(), SaveError>),
+    ) where
+        C: Codec<T>,
+    {
+        let data = match self.codec.encode(value) {
+            Ok(data) => data,
+            Err(error) => return callback(discord, Err(error.into())),
+        };
+
+        self.slot(name)
+            .save_async(discord, &data, move |discord, result| {
+                callback(discord, result.map(|_| ()).map_err(Into::into))
+            });
+    }
+
+    /// Loads and decodes the value saved under a given name, `None` if it was never saved.
+    ///
+    /// Saves in an older version are migrated to the current one.
+    pub fn load<E, T>(&self, discord: &Discord<'_, E>, name: &str) -> Result<Option<T>, SaveError>
+    where
+        C: Codec<T>,
+    {
+        match self.slot(name).load(discord)? {
+            Some(save) => self.decode(save).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Loads and decodes the value saved under a given name asynchronously,
+    /// `None` if it was never saved.
+    ///
+    /// Saves in an older version are migrated to the current one.
+    pub fn load_async<'d, E, T>(
+        &self,
+        discord: &Discord<'d, E>,
+        name: &str,
+        callback: impl 'd + FnOnce(&Discord<'d, E>, Result<Option<T>, SaveError>),
+    ) where
+        C: 'd + Clone + Codec<T>,
+    {
+        let format = self.clone();
+
+        self.slot(name).load_async(discord, move |discord, save| {
+            let value = match save {
+                Ok(Some(save)) => format.decode(save).map(Some),
+                Ok(None) => Ok(None),
+                Err(error) => Err(error.into()),
+            };
+
+            callback(discord, value)
+        });
+    }
+
+    fn slot(&self, name: &str) -> SaveSlot {
+        let mut slot = SaveSlot::new(name);
+        slot.version(self.version);
+        slot
+    }
+
+    fn decode<T>(&self, save: SaveData) -> Result<T, SaveError>
+    where
+        C: Codec<T>,
+    {
+        let data = self.migrate(save.version, save.data)?;
+
+        Ok(self.codec.decode(&data)?)
+    }
+
+    fn migrate(&self, mut version: u32, mut data: Vec<u8>) -> Result<Vec<u8>, SaveError> {
+        if version > self.version {
+            return Err(SaveError::UnsupportedVersion(version));
+        }
+
+        while version < self.version {
+            let migrate = self
+                .migrations
+                .get(&version)
+                .ok_or(SaveError::UnsupportedVersion(version))?;
+
+            data = migrate(&data)?;
+            version += 1;
+        }
+
+        Ok(data)
+    }
+}
+
+impl<C: std::fmt::Debug> std::fmt::Debug for SaveFormat<C> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("SaveFormat")
+            .field("codec", &self.codec)
+            .field("version", &self.version)
+            .field("migrations", &self.migrations.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JsonCodec;
+
+    #[test]
+    fn test_migrate() {
+        let mut format = SaveFormat::new(JsonCodec);
+        format
+            .version(2)
+            .migration(0, |data| {
+                let best: u32 = JsonCodec.decode(data)?;
+                JsonCodec.encode(&vec![best])
+            })
+            .migration(1, |data| {
+                let mut scores: Vec<u32> = JsonCodec.decode(data)?;
+                scores.sort();
+                JsonCodec.encode(&scores)
+            });
+
+        let save = |version, data: &[u8]| SaveData {
+            generation: 1,
+            version,
+            data: data.to_vec(),
+        };
+
+        let scores: Vec<u32> = format.decode(save(0, b"120")).unwrap();
+        assert_eq!(scores, vec![120]);
+
+        let scores: Vec<u32> = format.decode(save(1, b"[95,120,3]")).unwrap();
+        assert_eq!(scores, vec![3, 95, 120]);
+
+        let scores: Vec<u32> = format.decode(save(2, b"[95,120,3]")).unwrap();
+        assert_eq!(scores, vec![95, 120, 3]);
+
+        assert!(matches!(
+            format.decode::<Vec<u32>>(save(3, b"[]")),
+            Err(SaveError::UnsupportedVersion(3))
+        ));
+        assert!(matches!(
+            format.decode::<Vec<u32>>(save(1, b"{}")),
+            Err(SaveError::Codec(_))
+        ));
+
+        format.version(3);
+        assert!(matches!(
+            format.decode::<Vec<u32>>(save(0, b"120")),
+            Err(SaveError::UnsupportedVersion(2))
+        ));
+    }
+}
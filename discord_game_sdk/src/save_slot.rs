@@ -0,0 +1,455 @@
+use crate::{Discord, Error, SaveData, SaveSlotError};
+use std::convert::TryInto;
+
+const MAGIC: &[u8; 4] = b"DGSS";
+
+// Magic, format version, generation, data length and checksum
+const HEADER_LEN: usize = 28;
+
+// Copies claiming to be larger are corrupted, rather than allocated
+const MAX_DATA_LEN: usize = 64 * 1024 * 1024;
+
+/// Crash-safe save slot over the game's allocated storage
+///
+/// Every save is written alternately to two files, named after the slot with an `.a` and a `.b`
+/// suffix, so a crash while writing can only corrupt the older copy. Each copy starts with a header
+/// holding a generation counter, the format version of the data, and a CRC-32 checksum.
+///
+/// Loading returns the newest valid copy, and falls back to the older one if it is corrupted
+/// or cannot be read. Saves hold up to 64MiB of data.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # fn example(discord: Discord<'_, ()>) -> std::result::Result<(), SaveSlotError> {
+/// let mut slot = SaveSlot::new("profile_1");
+/// slot.version(2);
+///
+/// if let Some(save) = slot.load(&discord)? {
+///     println!("loaded save #{} in version {}", save.generation(), save.version());
+/// }
+///
+/// slot.save(&discord, b"level = 3")?;
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SaveSlot {
+    filenames: [String; 2],
+    version: u32,
+    // Generation and index of the newest valid copy, once known
+    newest: Option<(u64, usize)>,
+}
+
+impl SaveSlot {
+    /// Creates a slot saving data in format version 0.
+    pub fn new(name: &str) -> Self {
+        Self {
+            filenames: [format!("{}.a\0", name), format!("{}.b\0", name)],
+            version: 0,
+            newest: None,
+        }
+    }
+
+    /// Sets the format version written with the data.
+    pub fn version(&mut self, version: u32) -> &mut Self {
+        self.version = version;
+        self
+    }
+
+    /// Reads the newest valid copy, `None` if the slot was never saved.
+    ///
+    /// Corrupted copies are logged.
+    ///
+    /// ## Errors
+    ///
+    /// [`SaveSlotError::Corrupted`](enum.SaveSlotError.html#variant.Corrupted) if every copy is corrupted.
+    pub fn load<E>(&mut self, discord: &Discord<'_, E>) -> Result<Option<SaveData>, SaveSlotError> {
+        let mut copies = Vec::with_capacity(2);
+
+        for filename in &self.filenames {
+            copies.push(read(discord, filename)?);
+        }
+
+        let newest = newest(copies);
+
+        self.newest = match &newest {
+            Ok(Some((index, save))) => Some((save.generation, *index)),
+            _ => None,
+        };
+
+        newest.map(|newest| newest.map(|(_, save)| save))
+    }
+
+    // Reads the newest valid copy asynchronously, for slots created to be loaded once
+    #[cfg(feature = "serde")]
+    pub(crate) fn load_async<'d, E>(
+        &self,
+        discord: &Discord<'d, E>,
+        callback: impl 'd + FnOnce(&Discord<'d, E>, Result<Option<SaveData>, SaveSlotError>),
+    ) {
+        read_all_async(discord, self.filenames.clone(), move |discord, newest| {
+            callback(discord, newest.map(|newest| newest.map(|(_, save)| save)))
+        });
+    }
+
+    /// Writes data over the older copy, returning its generation.
+    ///
+    /// The slot is loaded first if it was not yet, to find the older copy.
+    /// If every copy is corrupted, the generations start over.
+    ///
+    /// ## Errors
+    ///
+    /// [`Error::InvalidFileSize`](enum.Error.html#variant.InvalidFileSize) if `data` exceeds 64MiB.
+    /// If writing fails, the newest copy is left untouched.
+    pub fn save<E>(&mut self, discord: &Discord<'_, E>, data: &[u8]) -> Result<u64, SaveSlotError> {
+        if data.len() > MAX_DATA_LEN {
+            return Err(Error::InvalidFileSize.into());
+        }
+
+        if self.newest.is_none() {
+            match self.load(discord) {
+                Ok(_) | Err(SaveSlotError::Corrupted) => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        let (generation, index) = next(self.newest);
+
+        let save = SaveData {
+            generation,
+            version: self.version,
+            data: data.to_vec(),
+        };
+
+        discord.write_file(self.filenames[index].as_str(), encode(&save))?;
+
+        self.newest = Some((generation, index));
+
+        Ok(generation)
+    }
+
+    // Writes data over the older copy asynchronously, for slots created to be saved once.
+    // Both copies are read first to find the older one.
+    #[cfg(feature = "serde")]
+    pub(crate) fn save_async<'d, E>(
+        &self,
+        discord: &Discord<'d, E>,
+        data: &[u8],
+        callback: impl 'd + FnOnce(&Discord<'d, E>, Result<u64, SaveSlotError>),
+    ) {
+        if data.len() > MAX_DATA_LEN {
+            return callback(discord, Err(Error::InvalidFileSize.into()));
+        }
+
+        let filenames = self.filenames.clone();
+        let version = self.version;
+        let data = data.to_vec();
+
+        read_all_async(discord, filenames.clone(), move |discord, newest| {
+            let newest = match newest {
+                Ok(newest) => newest.map(|(index, save)| (save.generation, index)),
+                Err(SaveSlotError::Corrupted) => None,
+                Err(error) => return callback(discord, Err(error)),
+            };
+
+            let (generation, index) = next(newest);
+
+            let save = SaveData {
+                generation,
+                version,
+                data,
+            };
+
+            discord.write_file_async(
+                filenames[index].clone(),
+                encode(&save),
+                move |discord, result| {
+                    callback(discord, result.map(|()| generation).map_err(Into::into))
+                },
+            );
+        });
+    }
+
+    /// Deletes both copies.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if a copy exists and cannot be deleted.
+    pub fn delete<E>(&mut self, discord: &Discord<'_, E>) -> Result<(), SaveSlotError> {
+        self.newest = None;
+
+        for filename in &self.filenames {
+            if discord.file_exists(filename.as_str())? {
+                discord.delete_file(filename.as_str())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// A copy of the save as read from storage
+enum SaveCopy {
+    Missing,
+    Corrupted,
+    Valid(SaveData),
+}
+
+fn read<E>(discord: &Discord<'_, E>, filename: &str) -> Result<SaveCopy, Error> {
+    if !discord.file_exists(filename)? {
+        return Ok(SaveCopy::Missing);
+    }
+
+    let size = match discord.file_stat(filename) {
+        Ok(stat) => stat.size(),
+        Err(error) => return Ok(unreadable(filename, error)),
+    };
+
+    if !valid_size(size) {
+        return Ok(corrupted(filename));
+    }
+
+    let mut buffer = vec![0; size as usize];
+
+    match discord.read_file(filename, &mut buffer) {
+        Ok(read) => {
+            buffer.truncate(read as usize);
+            Ok(check(filename, &buffer))
+        }
+
+        Err(error) => Ok(unreadable(filename, error)),
+    }
+}
+
+#[cfg(feature = "serde")]
+fn read_async<'d, E>(
+    discord: &Discord<'d, E>,
+    filename: String,
+    callback: impl 'd + FnOnce(&Discord<'d, E>, Result<SaveCopy, Error>),
+) {
+    match discord.file_exists(filename.as_str()) {
+        Ok(true) => {}
+        Ok(false) => return callback(discord, Ok(SaveCopy::Missing)),
+        Err(error) => return callback(discord, Err(error)),
+    }
+
+    match discord.file_stat(filename.as_str()) {
+        Ok(stat) if valid_size(stat.size()) => {
+            discord.read_file_async(filename.clone(), move |discord, data| {
+                let copy = match data {
+                    Ok(data) => check(&filename, data),
+                    Err(error) => unreadable(&filename, error),
+                };
+
+                callback(discord, Ok(copy))
+            })
+        }
+        Ok(_) => callback(discord, Ok(corrupted(&filename))),
+        Err(error) => callback(discord, Ok(unreadable(&filename, error))),
+    }
+}
+
+// Reads both copies one after the other, and picks the newest
+#[cfg(feature = "serde")]
+fn read_all_async<'d, E>(
+    discord: &Discord<'d, E>,
+    filenames: [String; 2],
+    callback: impl 'd + FnOnce(&Discord<'d, E>, Result<Option<(usize, SaveData)>, SaveSlotError>),
+) {
+    let [first, second] = filenames;
+
+    read_async(discord, first, move |discord, first| match first {
+        Ok(first) => read_async(discord, second, move |discord, second| {
+            callback(
+                discord,
+                second
+                    .map_err(SaveSlotError::from)
+                    .and_then(|second| newest(vec![first, second])),
+            )
+        }),
+        Err(error) => callback(discord, Err(error.into())),
+    });
+}
+
+fn check(filename: &str, buffer: &[u8]) -> SaveCopy {
+    match decode(buffer) {
+        Some(save) => SaveCopy::Valid(save),
+        None => corrupted(filename),
+    }
+}
+
+fn corrupted(filename: &str) -> SaveCopy {
+    log::warn!("save {} is corrupted", filename.trim_end_matches('\0'));
+    SaveCopy::Corrupted
+}
+
+// Copies that cannot be read are treated as corrupted, so the other one is used
+fn unreadable(filename: &str, error: Error) -> SaveCopy {
+    log::warn!(
+        "failed to read save {}: {}",
+        filename.trim_end_matches('\0'),
+        error
+    );
+    SaveCopy::Corrupted
+}
+
+fn valid_size(size: u64) -> bool {
+    size >= HEADER_LEN as u64 && size <= (HEADER_LEN + MAX_DATA_LEN) as u64
+}
+
+// The index and contents of the newest valid copy
+fn newest(copies: Vec<SaveCopy>) -> Result<Option<(usize, SaveData)>, SaveSlotError> {
+    let mut corrupted = false;
+    let mut newest: Option<(usize, SaveData)> = None;
+
+    for (index, copy) in copies.into_iter().enumerate() {
+        match copy {
+            SaveCopy::Missing => {}
+            SaveCopy::Corrupted => corrupted = true,
+            SaveCopy::Valid(save) => {
+                let newer = match &newest {
+                    Some((_, newest)) => save.generation > newest.generation,
+                    None => true,
+                };
+
+                if newer {
+                    newest = Some((index, save));
+                }
+            }
+        }
+    }
+
+    match newest {
+        None if corrupted => Err(SaveSlotError::Corrupted),
+        newest => Ok(newest),
+    }
+}
+
+// Generation and index of the copy to write, given the newest copy
+fn next(newest: Option<(u64, usize)>) -> (u64, usize) {
+    match newest {
+        Some((generation, index)) => (generation + 1, 1 - index),
+        None => (1, 0),
+    }
+}
+
+fn encode(save: &SaveData) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(HEADER_LEN + save.data.len());
+    buffer.extend_from_slice(MAGIC);
+    buffer.extend_from_slice(&save.version.to_le_bytes());
+    buffer.extend_from_slice(&save.generation.to_le_bytes());
+    buffer.extend_from_slice(&(save.data.len() as u64).to_le_bytes());
+
+    let checksum = crc32(&[&buffer, &save.data[..]]);
+
+    buffer.extend_from_slice(&checksum.to_le_bytes());
+    buffer.extend_from_slice(&save.data);
+    buffer
+}
+
+fn decode(buffer: &[u8]) -> Option<SaveData> {
+    if buffer.len() < HEADER_LEN || &buffer[..4] != MAGIC {
+        return None;
+    }
+
+    let version = u32::from_le_bytes(buffer[4..8].try_into().unwrap());
+    let generation = u64::from_le_bytes(buffer[8..16].try_into().unwrap());
+    let len = u64::from_le_bytes(buffer[16..24].try_into().unwrap());
+    let checksum = u32::from_le_bytes(buffer[24..HEADER_LEN].try_into().unwrap());
+    let data = &buffer[HEADER_LEN..];
+
+    if data.len() as u64 != len || crc32(&[&buffer[..24], data]) != checksum {
+        return None;
+    }
+
+    Some(SaveData {
+        generation,
+        version,
+        data: data.to_vec(),
+    })
+}
+
+// CRC-32 (IEEE), computed bitwise since saves are written rarely
+fn crc32(parts: &[&[u8]]) -> u32 {
+    let mut crc = !0u32;
+
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        crc ^= u32::from(*byte);
+
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (!(crc & 1)).wrapping_add(1));
+        }
+    }
+
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encoding() {
+        assert_eq!(crc32(&[b"1234", b"56789"]), 0xCBF4_3926);
+
+        let save = SaveData {
+            generation: 7,
+            version: 2,
+            data: b"level = 3".to_vec(),
+        };
+
+        let mut buffer = encode(&save);
+        assert_eq!(decode(&buffer), Some(save));
+
+        // Truncated by a crash
+        assert_eq!(decode(&buffer[..buffer.len() - 1]), None);
+
+        let last = buffer.len() - 1;
+        buffer[last] ^= 1;
+        assert_eq!(decode(&buffer), None);
+
+        assert!(!valid_size(HEADER_LEN as u64 - 1));
+        assert!(valid_size(HEADER_LEN as u64));
+        assert!(!valid_size(u64::MAX));
+    }
+
+    #[test]
+    fn test_fallback() {
+        let save = |generation| SaveData {
+            generation,
+            version: 0,
+            data: vec![],
+        };
+
+        // The newer copy is read, and the older one is overwritten next
+        let copy = newest(vec![SaveCopy::Valid(save(4)), SaveCopy::Valid(save(5))]).unwrap();
+        assert_eq!(copy, Some((1, save(5))));
+        assert_eq!(
+            next(copy.map(|(index, save)| (save.generation, index))),
+            (6, 0)
+        );
+
+        // A crash corrupted the newer copy, the older one is read and overwritten next
+        let copy = newest(vec![SaveCopy::Valid(save(4)), SaveCopy::Corrupted]).unwrap();
+        assert_eq!(copy, Some((0, save(4))));
+        assert_eq!(
+            next(copy.map(|(index, save)| (save.generation, index))),
+            (5, 1)
+        );
+
+        assert_eq!(
+            newest(vec![SaveCopy::Corrupted, SaveCopy::Corrupted]),
+            Err(SaveSlotError::Corrupted)
+        );
+
+        // The first save was written, the other copy is overwritten next
+        let copy = newest(vec![SaveCopy::Missing, SaveCopy::Valid(save(1))]).unwrap();
+        assert_eq!(copy, Some((1, save(1))));
+        assert_eq!(
+            next(copy.map(|(index, save)| (save.generation, index))),
+            (2, 0)
+        );
+
+        assert_eq!(newest(vec![SaveCopy::Missing, SaveCopy::Missing]), Ok(None));
+        assert_eq!(next(None), (1, 0));
+    }
+}
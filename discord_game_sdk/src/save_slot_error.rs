@@ -0,0 +1,36 @@
+use crate::Error;
+use std::fmt;
+
+/// Error while loading or saving a [`SaveSlot`](struct.SaveSlot.html)
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SaveSlotError {
+    /// Every copy of the save is corrupted
+    Corrupted,
+
+    /// The storage could not be read or written by the SDK
+    Discord(Error),
+}
+
+impl From<Error> for SaveSlotError {
+    fn from(error: Error) -> Self {
+        Self::Discord(error)
+    }
+}
+
+impl fmt::Display for SaveSlotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Corrupted => write!(f, "every copy of the save is corrupted"),
+            Self::Discord(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for SaveSlotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Corrupted => None,
+            Self::Discord(error) => Some(error),
+        }
+    }
+}
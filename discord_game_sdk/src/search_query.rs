@@ -3,10 +3,29 @@ use crate::{sys, to_result::ToResult, Cast, Comparison, Distance, Result};
 /// Lobby Search
 ///
 /// > [Struct in official docs](https://discordapp.com/developers/docs/game-sdk/lobbies#data-models-lobbysearchquery-struct)
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # fn example(discord: Discord<'_, ()>) -> Result<()> {
+/// discord.lobby_search(
+///     SearchQuery::new()
+///         .filter("mode".into(), Comparison::Equal, "ranked".into(), Cast::String)
+///         .filter("region".into(), Comparison::Equal, "eu".into(), Cast::String)
+///         .filter("skill".into(), Comparison::GreaterThanOrEqual, "1200".into(), Cast::Number)
+///         .sort("skill".into(), "1500".into(), Cast::Number)
+///         .limit(10),
+///     |discord, result| {
+///         if let Err(error) = result {
+///             eprintln!("failed to search lobbies: {}", error);
+///         }
+///     },
+/// );
+/// # Ok(()) }
+/// ```
 #[derive(Clone, Debug, Default)]
 pub struct SearchQuery {
-    pub(crate) filter: Option<(String, String, Comparison, Cast)>,
-    pub(crate) sort: Option<(String, String, Cast)>,
+    pub(crate) filters: Vec<(String, String, Comparison, Cast)>,
+    pub(crate) sorts: Vec<(String, String, Cast)>,
     pub(crate) limit: Option<u32>,
     pub(crate) distance: Option<Distance>,
 }
@@ -21,6 +40,8 @@ impl SearchQuery {
 
     /// Filters lobbies based on metadata comparison.
     ///
+    /// Can be called multiple times, lobbies must match every filter.
+    ///
     /// ## Performance
     ///
     /// A nul byte will be appended to `key` and `value` if one is not present.
@@ -41,12 +62,14 @@ impl SearchQuery {
             value.push('\0')
         }
 
-        self.filter = Some((key, value, comparison, cast));
+        self.filters.push((key, value, comparison, cast));
         self
     }
 
     /// Sorts the filtered lobbies based on "near-ness" to a given value
     ///
+    /// Can be called multiple times, sorts are applied in the order they were added.
+    ///
     /// ## Performance
     ///
     /// A nul byte will be appended to `key` and `value` if one is not present.
@@ -61,7 +84,7 @@ impl SearchQuery {
             value.push('\0')
         }
 
-        self.sort = Some((key, value, cast));
+        self.sorts.push((key, value, cast));
         self
     }
 
@@ -82,7 +105,7 @@ impl SearchQuery {
     }
 
     pub(crate) unsafe fn process(&self, tx: *mut sys::IDiscordLobbySearchQuery) -> Result<()> {
-        if let Some((key, value, comparison, cast)) = self.filter.as_ref() {
+        for (key, value, comparison, cast) in &self.filters {
             (*tx).filter.unwrap()(
                 tx,
                 // XXX: *mut should be *const
@@ -95,7 +118,7 @@ impl SearchQuery {
             .to_result()?;
         }
 
-        if let Some((key, value, cast)) = self.sort.as_ref() {
+        for (key, value, cast) in &self.sorts {
             (*tx).sort.unwrap()(
                 tx,
                 // XXX: *mut should be *const
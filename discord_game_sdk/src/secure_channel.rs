@@ -0,0 +1,890 @@
+use crate::{
+    Discord, LobbyID, LobbyMemberTransaction, NetworkChannelID, NetworkPeerID, Result,
+    SecureChannelError, UserID,
+};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::{collections::HashMap, convert::TryInto};
+use x25519_dalek::{PublicKey, ReusableSecret};
+
+const HANDSHAKE: u8 = 0;
+const DATA: u8 = 1;
+
+const HEADER_LEN: usize = 9;
+const KEY_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+const REPLAY_WINDOW: u64 = 64;
+
+// Kind and static public key, sent before the key of the remote is known
+const HELLO_LEN: usize = 1 + KEY_LEN;
+// Kind, static and ephemeral public keys, ephemeral key of the remote being answered, and tag
+const HANDSHAKE_LEN: usize = 1 + 3 * KEY_LEN + TAG_LEN;
+
+const KDF_INFO: &[u8] = b"discord_game_sdk secure channel v2";
+const HANDSHAKE_INFO: &[u8] = b"discord_game_sdk secure channel v2 handshake";
+
+/// Authenticated and encrypted network channels
+///
+/// Every instance holds a static X25519 key pair generated on creation.
+/// Static public keys are exchanged either through lobby member metadata with [`publish`](#method.publish),
+/// or with a handshake message sent on a network channel with [`handshake`](#method.handshake).
+///
+/// Each session with a peer or lobby member then runs a handshake, started with
+/// [`handshake`](#method.handshake) or [`lobby_handshake`](#method.lobby_handshake) and answered
+/// automatically, exchanging ephemeral X25519 keys authenticated by the static keys. Both static and
+/// ephemeral keys derive two ChaCha20-Poly1305 keys, one per direction, so no two sessions share keys.
+/// The handshake completes in two round trips, it should be sent on a reliable channel or repeated
+/// until it completes. Sending before it completes fails.
+///
+/// Every packet carries a counter used as nonce. Packets that fail authentication,
+/// were already received, or are more than 64 packets older than the newest one are rejected.
+/// Packets are bound to the session and to the channel ID they are sent on.
+///
+/// Keys read from member metadata are bound to the member by Discord, and take precedence over
+/// handshakes. A handshake alone only proves that both ends share a key, not who they are.
+/// Use [`bind_peer`](#method.bind_peer) to use the key of a lobby member for their peer ID.
+///
+/// Requires the `encryption` feature.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// struct MyEventHandler {
+///     secure: SecureChannel,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_member_update(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///     ) {
+///         if let Err(error) = self.secure.on_member_update(discord, lobby_id, member_id) {
+///             eprintln!("invalid key from {}: {}", member_id, error);
+///         }
+///     }
+///
+///     fn on_lobby_network_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         lobby_id: LobbyID,
+///         member_id: UserID,
+///         channel_id: NetworkChannelID,
+///         data: &[u8],
+///     ) {
+///         match self
+///             .secure
+///             .on_lobby_network_message(discord, lobby_id, member_id, channel_id, data)
+///         {
+///             Ok(Some(message)) => println!("received {} bytes", message.len()),
+///             Ok(None) => {}
+///             Err(error) => eprintln!("rejected packet from {}: {}", member_id, error),
+///         }
+///     }
+///
+///     // ...
+/// }
+///
+/// # fn example(discord: Discord<'_, MyEventHandler>, secure: &mut SecureChannel, lobby_id: LobbyID, user_id: UserID) -> std::result::Result<(), SecureChannelError> {
+/// // After connecting to the lobby
+/// secure.publish(&discord, lobby_id, |_, result| {
+///     if let Err(error) = result {
+///         eprintln!("failed to publish public key: {}", error);
+///     }
+/// })?;
+///
+/// // Once the member's key was received
+/// secure.lobby_handshake(&discord, lobby_id, user_id, 0)?;
+///
+/// // Once the handshake completed
+/// secure.send_lobby_network_message(&discord, lobby_id, user_id, 0, b"secret")?;
+/// # Ok(()) }
+/// ```
+pub struct SecureChannel {
+    secret: ReusableSecret,
+    public: PublicKey,
+    sessions: HashMap<Remote, Session>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Remote {
+    Peer(NetworkPeerID),
+    Member(LobbyID, UserID),
+}
+
+struct Session {
+    remote_key: [u8; KEY_LEN],
+    pinned: bool,
+    // Diffie-Hellman of the static keys, authenticates handshakes
+    static_shared: [u8; KEY_LEN],
+    ephemeral: ReusableSecret,
+    ephemeral_public: [u8; KEY_LEN],
+    // Ephemeral keys of the remote accepted by this session, each is accepted once
+    remote_ephemerals: Vec<[u8; KEY_LEN]>,
+    keys: Option<Keys>,
+}
+
+// Keys derived once the handshake completed
+struct Keys {
+    send: ChaCha20Poly1305,
+    receive: ChaCha20Poly1305,
+    // Ephemeral public keys of both ends, bound to every packet
+    context: [u8; 2 * KEY_LEN],
+    next_counter: u64,
+    replay: ReplayWindow,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct ReplayWindow {
+    newest: Option<u64>,
+    seen: u64,
+}
+
+impl Default for SecureChannel {
+    fn default() -> Self {
+        let secret = ReusableSecret::random();
+
+        Self {
+            public: PublicKey::from(&secret),
+            secret,
+            sessions: HashMap::new(),
+        }
+    }
+}
+
+impl SecureChannel {
+    /// The member metadata key under which the public key is stored, in hexadecimal
+    pub const PUBLIC_KEY_KEY: &'static str = "secure_channel.public_key";
+
+    /// Creates a channel with a new random key pair.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The public key of the current user
+    pub fn public_key(&self) -> [u8; 32] {
+        self.public.to_bytes()
+    }
+
+    /// Stores the public key in the member metadata of the current user.
+    ///
+    /// ## Errors
+    ///
+    /// Fails if the current user is not available yet.
+    pub fn publish<'d, E>(
+        &self,
+        discord: &Discord<'d, E>,
+        lobby_id: LobbyID,
+        callback: impl 'd + FnOnce(&Discord<'d, E>, Result<()>),
+    ) -> Result<()> {
+        let user_id = discord.current_user()?.id();
+
+        discord.update_member(
+            lobby_id,
+            user_id,
+            LobbyMemberTransaction::new().add_metadata(
+                Self::PUBLIC_KEY_KEY.to_string(),
+                to_hex(self.public.as_bytes()),
+            ),
+            callback,
+        );
+
+        Ok(())
+    }
+
+    /// Reads the public key of a member from their metadata.
+    ///
+    /// Forward [`EventHandler::on_member_connect`](trait.EventHandler.html#method.on_member_connect) here.
+    ///
+    /// ## Errors
+    ///
+    /// [`SecureChannelError::Malformed`](enum.SecureChannelError.html#variant.Malformed)
+    /// if the published key is invalid.
+    pub fn on_member_connect<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> std::result::Result<(), SecureChannelError> {
+        self.on_member_update(discord, lobby_id, member_id)
+    }
+
+    /// Reads the public key of a member from their metadata.
+    ///
+    /// Forward [`EventHandler::on_member_update`](trait.EventHandler.html#method.on_member_update) here.
+    ///
+    /// ## Errors
+    ///
+    /// [`SecureChannelError::Malformed`](enum.SecureChannelError.html#variant.Malformed)
+    /// if the published key is invalid.
+    pub fn on_member_update<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> std::result::Result<(), SecureChannelError> {
+        let key = match discord.lobby_member_metadata(lobby_id, member_id, Self::PUBLIC_KEY_KEY) {
+            Ok(key) => key,
+            // The member has not published their key yet
+            Err(_) => return Ok(()),
+        };
+
+        let key = from_hex(&key).ok_or(SecureChannelError::Malformed)?;
+
+        self.establish(Remote::Member(lobby_id, member_id), key, true)
+    }
+
+    /// Forgets the key of a member.
+    ///
+    /// Forward [`EventHandler::on_member_disconnect`](trait.EventHandler.html#method.on_member_disconnect) here.
+    pub fn on_member_disconnect(&mut self, lobby_id: LobbyID, member_id: UserID) {
+        let _ = self.sessions.remove(&Remote::Member(lobby_id, member_id));
+    }
+
+    /// Uses the key published by a lobby member for their peer ID.
+    ///
+    /// The session with the peer ID is separate from the session with the member,
+    /// and runs its own handshake.
+    ///
+    /// ## Errors
+    ///
+    /// [`SecureChannelError::UnknownPeer`](enum.SecureChannelError.html#variant.UnknownPeer)
+    /// if the member did not publish their key.
+    pub fn bind_peer(
+        &mut self,
+        peer_id: NetworkPeerID,
+        lobby_id: LobbyID,
+        member_id: UserID,
+    ) -> std::result::Result<(), SecureChannelError> {
+        let key = self
+            .sessions
+            .get(&Remote::Member(lobby_id, member_id))
+            .filter(|session| session.pinned)
+            .map(|session| session.remote_key)
+            .ok_or(SecureChannelError::UnknownPeer)?;
+
+        self.establish(Remote::Peer(peer_id), key, true)
+    }
+
+    /// Forgets the key of a peer ID.
+    ///
+    /// Call this after [`close_peer`](struct.Discord.html#method.close_peer).
+    pub fn forget_peer(&mut self, peer_id: NetworkPeerID) {
+        let _ = self.sessions.remove(&Remote::Peer(peer_id));
+    }
+
+    /// Starts or repeats the handshake with a given peer ID through the given channel.
+    ///
+    /// Sends only the static public key if the key of the peer is not known yet.
+    pub fn handshake<E>(
+        &self,
+        discord: &Discord<'_, E>,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+    ) -> Result<()> {
+        discord.send_message(
+            peer_id,
+            channel_id,
+            self.handshake_packet(Remote::Peer(peer_id), None),
+        )
+    }
+
+    /// Starts or repeats the handshake with a lobby member through the given lobby network channel.
+    ///
+    /// Sends only the static public key if the key of the member is not known yet.
+    pub fn lobby_handshake<E>(
+        &self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        user_id: UserID,
+        channel_id: NetworkChannelID,
+    ) -> Result<()> {
+        let packet = self.handshake_packet(Remote::Member(lobby_id, user_id), None);
+
+        discord.send_lobby_network_message(lobby_id, user_id, channel_id, &packet)
+    }
+
+    /// Encrypts and sends data to a given peer ID through the given channel.
+    pub fn send_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: impl AsRef<[u8]>,
+    ) -> std::result::Result<(), SecureChannelError> {
+        let packet = self.seal(Remote::Peer(peer_id), channel_id, data.as_ref())?;
+
+        Ok(discord.send_message(peer_id, channel_id, packet)?)
+    }
+
+    /// Encrypts and sends a network message to a lobby member.
+    pub fn send_lobby_network_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        user_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> std::result::Result<(), SecureChannelError> {
+        let packet = self.seal(Remote::Member(lobby_id, user_id), channel_id, data)?;
+
+        Ok(discord.send_lobby_network_message(lobby_id, user_id, channel_id, &packet)?)
+    }
+
+    /// Authenticates and decrypts a packet, or processes a handshake and returns `None`.
+    ///
+    /// Forward [`EventHandler::on_network_message`](trait.EventHandler.html#method.on_network_message) here.
+    pub fn on_network_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> std::result::Result<Option<Vec<u8>>, SecureChannelError> {
+        let remote = Remote::Peer(peer_id);
+
+        if let Some(answer) = self.receive_handshake(remote, data)? {
+            discord.send_message(peer_id, channel_id, answer)?;
+        }
+
+        self.open(remote, channel_id, data)
+    }
+
+    /// Authenticates and decrypts a packet, or processes a handshake and returns `None`.
+    ///
+    /// Forward [`EventHandler::on_lobby_network_message`](trait.EventHandler.html#method.on_lobby_network_message) here.
+    pub fn on_lobby_network_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> std::result::Result<Option<Vec<u8>>, SecureChannelError> {
+        let remote = Remote::Member(lobby_id, member_id);
+
+        if let Some(answer) = self.receive_handshake(remote, data)? {
+            discord.send_lobby_network_message(lobby_id, member_id, channel_id, &answer)?;
+        }
+
+        self.open(remote, channel_id, data)
+    }
+
+    // Answers a given ephemeral key of the remote, or the last one accepted
+    fn handshake_packet(&self, remote: Remote, answered: Option<[u8; KEY_LEN]>) -> Vec<u8> {
+        let session = match self.sessions.get(&remote) {
+            Some(session) => session,
+            None => {
+                let mut packet = Vec::with_capacity(HELLO_LEN);
+                packet.push(HANDSHAKE);
+                packet.extend_from_slice(self.public.as_bytes());
+                return packet;
+            }
+        };
+
+        let answered = answered
+            .or_else(|| session.remote_ephemerals.last().copied())
+            .unwrap_or([0; KEY_LEN]);
+
+        let mut packet = Vec::with_capacity(HANDSHAKE_LEN);
+        packet.push(HANDSHAKE);
+        packet.extend_from_slice(self.public.as_bytes());
+        packet.extend_from_slice(&session.ephemeral_public);
+        packet.extend_from_slice(&answered);
+
+        let tag = handshake_tag(self.public.to_bytes(), session, &packet);
+        packet.extend_from_slice(&tag);
+        packet
+    }
+
+    // Returns the handshake to answer with, if any
+    fn receive_handshake(
+        &mut self,
+        remote: Remote,
+        data: &[u8],
+    ) -> std::result::Result<Option<Vec<u8>>, SecureChannelError> {
+        if data.first() != Some(&HANDSHAKE) {
+            return Ok(None);
+        }
+
+        if data.len() == HELLO_LEN {
+            self.establish(remote, data[1..].try_into().unwrap(), false)?;
+            return Ok(Some(self.handshake_packet(remote, None)));
+        }
+
+        if data.len() != HANDSHAKE_LEN {
+            return Err(SecureChannelError::Malformed);
+        }
+
+        let key: [u8; KEY_LEN] = data[1..1 + KEY_LEN].try_into().unwrap();
+        let ephemeral: [u8; KEY_LEN] = data[1 + KEY_LEN..1 + 2 * KEY_LEN].try_into().unwrap();
+        let answered: [u8; KEY_LEN] = data[1 + 2 * KEY_LEN..1 + 3 * KEY_LEN].try_into().unwrap();
+        let tag = &data[1 + 3 * KEY_LEN..];
+
+        self.establish(remote, key, false)?;
+
+        let local_key = self.public.to_bytes();
+        let session = self.sessions.get_mut(&remote).unwrap();
+
+        let expected = handshake_tag(local_key, session, &data[..1 + 3 * KEY_LEN]);
+
+        // Compared in constant time
+        if expected
+            .iter()
+            .zip(tag)
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            != 0
+        {
+            return Err(SecureChannelError::Forged);
+        }
+
+        // Only a handshake answering the ephemeral key of this session proves it is not replayed,
+        // others are answered so the remote can prove it
+        if answered == session.ephemeral_public {
+            // Each ephemeral key is accepted once, so replayed handshakes never derive keys again
+            if session.remote_ephemerals.contains(&ephemeral) {
+                return Ok(None);
+            }
+
+            session.keys = Some(derive_keys(local_key, session, ephemeral)?);
+            session.remote_ephemerals.push(ephemeral);
+        }
+
+        Ok(Some(self.handshake_packet(remote, Some(ephemeral))))
+    }
+
+    fn establish(
+        &mut self,
+        remote: Remote,
+        remote_key: [u8; KEY_LEN],
+        pinned: bool,
+    ) -> std::result::Result<(), SecureChannelError> {
+        if let Some(session) = self.sessions.get_mut(&remote) {
+            if session.remote_key == remote_key {
+                session.pinned |= pinned;
+                return Ok(());
+            }
+
+            if session.pinned && !pinned {
+                return Err(SecureChannelError::Forged);
+            }
+        }
+
+        if remote_key == self.public.to_bytes() {
+            return Err(SecureChannelError::Malformed);
+        }
+
+        let shared = self.secret.diffie_hellman(&PublicKey::from(remote_key));
+
+        if !shared.was_contributory() {
+            return Err(SecureChannelError::Malformed);
+        }
+
+        // Every session has its own ephemeral key, so its keys are never derived by another one
+        let ephemeral = ReusableSecret::random();
+
+        let _ = self.sessions.insert(
+            remote,
+            Session {
+                remote_key,
+                pinned,
+                static_shared: shared.to_bytes(),
+                ephemeral_public: PublicKey::from(&ephemeral).to_bytes(),
+                ephemeral,
+                remote_ephemerals: Vec::new(),
+                keys: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    fn seal(
+        &mut self,
+        remote: Remote,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> std::result::Result<Vec<u8>, SecureChannelError> {
+        let keys = self
+            .sessions
+            .get_mut(&remote)
+            .and_then(|session| session.keys.as_mut())
+            .ok_or(SecureChannelError::UnknownPeer)?;
+
+        let counter = keys.next_counter;
+        keys.next_counter += 1;
+
+        let header = header(counter);
+
+        let ciphertext = keys
+            .send
+            .encrypt(
+                &nonce(counter),
+                Payload {
+                    msg: data,
+                    aad: &aad(&header, channel_id, &keys.context),
+                },
+            )
+            .expect("encryption of in-memory data");
+
+        let mut packet = Vec::with_capacity(HEADER_LEN + ciphertext.len());
+        packet.extend_from_slice(&header);
+        packet.extend_from_slice(&ciphertext);
+        Ok(packet)
+    }
+
+    fn open(
+        &mut self,
+        remote: Remote,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> std::result::Result<Option<Vec<u8>>, SecureChannelError> {
+        match data.first() {
+            Some(&HANDSHAKE) => return Ok(None),
+            Some(&DATA) if data.len() >= HEADER_LEN => {}
+            _ => return Err(SecureChannelError::Malformed),
+        }
+
+        let keys = self
+            .sessions
+            .get_mut(&remote)
+            .and_then(|session| session.keys.as_mut())
+            .ok_or(SecureChannelError::UnknownPeer)?;
+
+        let counter = u64::from_le_bytes(data[1..HEADER_LEN].try_into().unwrap());
+
+        if !keys.replay.check(counter) {
+            return Err(SecureChannelError::Replayed);
+        }
+
+        let plaintext = keys
+            .receive
+            .decrypt(
+                &nonce(counter),
+                Payload {
+                    msg: &data[HEADER_LEN..],
+                    aad: &aad(&data[..HEADER_LEN], channel_id, &keys.context),
+                },
+            )
+            .map_err(|_| SecureChannelError::Forged)?;
+
+        // Only authenticated counters move the window
+        keys.replay.commit(counter);
+
+        Ok(Some(plaintext))
+    }
+}
+
+impl std::fmt::Debug for SecureChannel {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("SecureChannel")
+            .field("public_key", &to_hex(self.public.as_bytes()))
+            .field("sessions", &self.sessions.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl ReplayWindow {
+    fn check(&self, counter: u64) -> bool {
+        match self.newest {
+            None => true,
+            Some(newest) if counter > newest => true,
+            Some(newest) => {
+                let age = newest - counter;
+                age < REPLAY_WINDOW && self.seen & (1 << age) == 0
+            }
+        }
+    }
+
+    fn commit(&mut self, counter: u64) {
+        match self.newest {
+            Some(newest) if counter <= newest => self.seen |= 1 << (newest - counter),
+
+            Some(newest) => {
+                let shift = counter - newest;
+                self.seen = if shift >= REPLAY_WINDOW {
+                    1
+                } else {
+                    (self.seen << shift) | 1
+                };
+                self.newest = Some(counter);
+            }
+
+            None => {
+                self.seen = 1;
+                self.newest = Some(counter);
+            }
+        }
+    }
+}
+
+fn header(counter: u64) -> [u8; HEADER_LEN] {
+    let mut header = [DATA; HEADER_LEN];
+    header[1..].copy_from_slice(&counter.to_le_bytes());
+    header
+}
+
+fn aad(header: &[u8], channel_id: NetworkChannelID, context: &[u8]) -> Vec<u8> {
+    let mut aad = header.to_vec();
+    aad.extend_from_slice(&channel_id.to_le_bytes());
+    aad.extend_from_slice(context);
+    aad
+}
+
+// Orders a pair of values after the static keys of both ends, so both ends agree
+fn ordered(
+    local_key: [u8; KEY_LEN],
+    remote_key: [u8; KEY_LEN],
+    local: [u8; KEY_LEN],
+    remote: [u8; KEY_LEN],
+) -> [u8; 2 * KEY_LEN] {
+    let (low, high) = if local_key < remote_key {
+        (local, remote)
+    } else {
+        (remote, local)
+    };
+
+    let mut pair = [0; 2 * KEY_LEN];
+    pair[..KEY_LEN].copy_from_slice(&low);
+    pair[KEY_LEN..].copy_from_slice(&high);
+    pair
+}
+
+fn handshake_tag(local_key: [u8; KEY_LEN], session: &Session, transcript: &[u8]) -> [u8; TAG_LEN] {
+    let salt = ordered(local_key, session.remote_key, local_key, session.remote_key);
+
+    let mut tag = [0; TAG_LEN];
+    Hkdf::<Sha256>::new(Some(&salt), &session.static_shared)
+        .expand_multi_info(&[HANDSHAKE_INFO, transcript], &mut tag)
+        .expect("valid output length");
+    tag
+}
+
+fn derive_keys(
+    local_key: [u8; KEY_LEN],
+    session: &Session,
+    remote_ephemeral: [u8; KEY_LEN],
+) -> std::result::Result<Keys, SecureChannelError> {
+    let ephemeral_shared = session
+        .ephemeral
+        .diffie_hellman(&PublicKey::from(remote_ephemeral));
+
+    if !ephemeral_shared.was_contributory() {
+        return Err(SecureChannelError::Malformed);
+    }
+
+    let remote_key = session.remote_key;
+    let salt = ordered(local_key, remote_key, local_key, remote_key);
+    let context = ordered(
+        local_key,
+        remote_key,
+        session.ephemeral_public,
+        remote_ephemeral,
+    );
+
+    let mut secret = [0; 2 * KEY_LEN];
+    secret[..KEY_LEN].copy_from_slice(&session.static_shared);
+    secret[KEY_LEN..].copy_from_slice(ephemeral_shared.as_bytes());
+
+    let mut keys = [0; 2 * KEY_LEN];
+    Hkdf::<Sha256>::new(Some(&salt), &secret)
+        .expand_multi_info(&[KDF_INFO, &context], &mut keys)
+        .expect("valid output length");
+
+    let low_to_high = ChaCha20Poly1305::new(Key::from_slice(&keys[..KEY_LEN]));
+    let high_to_low = ChaCha20Poly1305::new(Key::from_slice(&keys[KEY_LEN..]));
+
+    let (send, receive) = if local_key < remote_key {
+        (low_to_high, high_to_low)
+    } else {
+        (high_to_low, low_to_high)
+    };
+
+    Ok(Keys {
+        send,
+        receive,
+        context,
+        next_counter: 0,
+        replay: ReplayWindow::default(),
+    })
+}
+
+fn nonce(counter: u64) -> Nonce {
+    let mut nonce = [0; 12];
+    nonce[4..].copy_from_slice(&counter.to_le_bytes());
+    *Nonce::from_slice(&nonce)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<[u8; KEY_LEN]> {
+    if hex.len() != 2 * KEY_LEN || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut key = [0; KEY_LEN];
+
+    for (i, byte) in key.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).ok()?;
+    }
+
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Delivers handshakes back and forth until no answer is left
+    fn connect(
+        alice: &mut SecureChannel,
+        to_bob: Remote,
+        bob: &mut SecureChannel,
+        to_alice: Remote,
+    ) {
+        let mut packet = alice.handshake_packet(to_bob, None);
+
+        for turn in 0..8 {
+            let answer = if turn % 2 == 0 {
+                bob.receive_handshake(to_alice, &packet)
+            } else {
+                alice.receive_handshake(to_bob, &packet)
+            };
+
+            match answer.unwrap() {
+                Some(answer) => packet = answer,
+                None => return,
+            }
+        }
+
+        panic!("handshake did not complete");
+    }
+
+    #[test]
+    fn test_seal_open() {
+        let mut alice = SecureChannel::new();
+        let mut bob = SecureChannel::new();
+
+        let to_bob = Remote::Peer(2);
+        let to_alice = Remote::Peer(1);
+
+        assert_eq!(
+            alice.seal(to_bob, 0, b"hi"),
+            Err(SecureChannelError::UnknownPeer)
+        );
+
+        connect(&mut alice, to_bob, &mut bob, to_alice);
+
+        let first = alice.seal(to_bob, 0, b"first").unwrap();
+        let second = alice.seal(to_bob, 0, b"second").unwrap();
+
+        assert_eq!(bob.open(to_alice, 0, &second), Ok(Some(b"second".to_vec())));
+        assert_eq!(bob.open(to_alice, 0, &first), Ok(Some(b"first".to_vec())));
+        assert_eq!(
+            bob.open(to_alice, 0, &first),
+            Err(SecureChannelError::Replayed)
+        );
+
+        let mut forged = alice.seal(to_bob, 0, b"third").unwrap();
+        *forged.last_mut().unwrap() ^= 1;
+        assert_eq!(
+            bob.open(to_alice, 0, &forged),
+            Err(SecureChannelError::Forged)
+        );
+
+        let other_channel = alice.seal(to_bob, 0, b"fourth").unwrap();
+        assert_eq!(
+            bob.open(to_alice, 1, &other_channel),
+            Err(SecureChannelError::Forged)
+        );
+
+        // A handshake already accepted is not answered, and does not reset the session
+        let replayed = bob.handshake_packet(to_alice, None);
+        assert_eq!(alice.receive_handshake(to_bob, &replayed), Ok(None));
+
+        let fifth = alice.seal(to_bob, 0, b"fifth").unwrap();
+        assert_eq!(bob.open(to_alice, 0, &fifth), Ok(Some(b"fifth".to_vec())));
+
+        let mut forged = bob.handshake_packet(to_alice, None);
+        forged[1 + KEY_LEN] ^= 1;
+        assert_eq!(
+            alice.receive_handshake(to_bob, &forged),
+            Err(SecureChannelError::Forged)
+        );
+    }
+
+    #[test]
+    fn test_sessions() {
+        let mut alice = SecureChannel::new();
+        let mut bob = SecureChannel::new();
+
+        let (to_bob, to_alice) = (Remote::Member(1, 2), Remote::Member(1, 1));
+
+        alice.establish(to_bob, bob.public_key(), true).unwrap();
+        bob.establish(to_alice, alice.public_key(), true).unwrap();
+        connect(&mut alice, to_bob, &mut bob, to_alice);
+
+        alice.bind_peer(20, 1, 2).unwrap();
+        bob.bind_peer(10, 1, 1).unwrap();
+        connect(&mut alice, Remote::Peer(20), &mut bob, Remote::Peer(10));
+
+        // Same remote key and counter, different sessions
+        let member = alice.seal(to_bob, 0, b"same").unwrap();
+        let peer = alice.seal(Remote::Peer(20), 0, b"same").unwrap();
+        assert_eq!(member[..HEADER_LEN], peer[..HEADER_LEN]);
+        assert_ne!(member, peer);
+
+        assert_eq!(
+            bob.open(Remote::Peer(10), 0, &member),
+            Err(SecureChannelError::Forged)
+        );
+
+        // A new session after reconnecting does not accept packets of the previous one
+        alice.on_member_disconnect(1, 2);
+        bob.on_member_disconnect(1, 1);
+        alice.establish(to_bob, bob.public_key(), true).unwrap();
+        bob.establish(to_alice, alice.public_key(), true).unwrap();
+        connect(&mut alice, to_bob, &mut bob, to_alice);
+
+        let reconnected = alice.seal(to_bob, 0, b"same").unwrap();
+        assert_ne!(member, reconnected);
+        assert_eq!(
+            bob.open(to_alice, 0, &member),
+            Err(SecureChannelError::Forged)
+        );
+        assert_eq!(
+            bob.open(to_alice, 0, &reconnected),
+            Ok(Some(b"same".to_vec()))
+        );
+    }
+
+    #[test]
+    fn test_replay_window() {
+        let mut window = ReplayWindow::default();
+
+        window.commit(100);
+        assert!(!window.check(100));
+        assert!(window.check(99));
+        assert!(!window.check(36));
+
+        window.commit(99);
+        assert!(!window.check(99));
+
+        window.commit(200);
+        assert!(!window.check(100));
+        assert!(window.check(199));
+    }
+
+    #[test]
+    fn test_hex() {
+        let key = [0xab; KEY_LEN];
+        assert_eq!(from_hex(&to_hex(&key)), Some(key));
+        assert_eq!(from_hex("zz"), None);
+    }
+}
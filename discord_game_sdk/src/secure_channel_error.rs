@@ -0,0 +1,50 @@
+use crate::Error;
+use std::fmt;
+
+/// Error of a [`SecureChannel`](struct.SecureChannel.html)
+///
+/// Requires the `encryption` feature.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum SecureChannelError {
+    /// No key was exchanged with the peer or lobby member, or the handshake did not complete yet
+    UnknownPeer,
+
+    /// The packet is truncated or the public key is invalid
+    Malformed,
+
+    /// The packet was not encrypted with the key exchanged with the sender, or was altered
+    Forged,
+
+    /// The packet was already received, or is too old to tell
+    Replayed,
+
+    /// The packet could not be sent by the SDK
+    Discord(Error),
+}
+
+impl From<Error> for SecureChannelError {
+    fn from(error: Error) -> Self {
+        Self::Discord(error)
+    }
+}
+
+impl fmt::Display for SecureChannelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownPeer => write!(f, "no session established with the peer"),
+            Self::Malformed => write!(f, "malformed packet"),
+            Self::Forged => write!(f, "packet failed authentication"),
+            Self::Replayed => write!(f, "packet was replayed"),
+            Self::Discord(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for SecureChannelError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Discord(error) => Some(error),
+            _ => None,
+        }
+    }
+}
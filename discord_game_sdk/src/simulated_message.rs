@@ -0,0 +1,27 @@
+use crate::{LobbyID, NetworkChannelID, NetworkPeerID, UserID};
+
+/// Message delivered by a [`NetworkSimulator`](struct.NetworkSimulator.html)
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum SimulatedMessage {
+    /// Message received from a peer ID
+    Peer {
+        /// Peer that sent the message
+        peer_id: NetworkPeerID,
+        /// Channel the message was received on
+        channel_id: NetworkChannelID,
+        /// Content of the message
+        data: Vec<u8>,
+    },
+
+    /// Message received from a lobby member
+    Lobby {
+        /// Lobby the message was received in
+        lobby_id: LobbyID,
+        /// Member that sent the message
+        member_id: UserID,
+        /// Channel the message was received on
+        channel_id: NetworkChannelID,
+        /// Content of the message
+        data: Vec<u8>,
+    },
+}
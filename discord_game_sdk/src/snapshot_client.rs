@@ -0,0 +1,348 @@
+use crate::{
+    network_streams::Remote,
+    snapshot_host::{is_newer, patch, ACK, DELTA, FULL, HEADER_LEN},
+    Discord, LobbyID, NetworkChannelID, NetworkPeerID, SnapshotInterpolation, SnapshotSequence,
+    UserID,
+};
+use std::{
+    collections::VecDeque,
+    convert::TryInto,
+    time::{Duration, Instant},
+};
+
+/// Reconstruction and interpolation of the snapshots sent by a [`SnapshotHost`](struct.SnapshotHost.html)
+///
+/// Every snapshot received is reconstructed from its baseline, acknowledged to the host,
+/// and kept in a buffer with the time at which the host took it. Snapshots older than the
+/// latest one received are dropped.
+///
+/// The host clock is estimated from the snapshots that arrived the fastest, and
+/// [`interpolate`](#method.interpolate) returns the snapshots surrounding a point in the past
+/// of the host clock, so the game can render smoothly despite jitter and losses.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// # use std::time::Duration;
+/// struct MyEventHandler {
+///     client: SnapshotClient,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_network_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         peer_id: NetworkPeerID,
+///         channel_id: NetworkChannelID,
+///         data: &[u8],
+///     ) {
+///         self.client.on_network_message(discord, peer_id, channel_id, data);
+///     }
+///
+///     // ...
+/// }
+///
+/// # fn example(client: &SnapshotClient) {
+/// // Every frame
+/// if let Some(interpolation) = client.interpolate(Duration::from_millis(100)) {
+///     println!(
+///         "rendering {} bytes and {} bytes blended at {}",
+///         interpolation.from().len(),
+///         interpolation.to().len(),
+///         interpolation.alpha()
+///     );
+/// }
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SnapshotClient {
+    channel_id: NetworkChannelID,
+    history: usize,
+    max_snapshot_size: usize,
+    snapshots: VecDeque<(SnapshotSequence, Duration, Vec<u8>)>,
+    // Local time at which the snapshot that arrived the fastest was received, and its host time
+    offset: Option<(Instant, Duration)>,
+}
+
+impl SnapshotClient {
+    /// Creates a client receiving snapshots over the given channel ID,
+    /// keeping the last 32 snapshots and accepting snapshots up to 1MiB.
+    pub fn new(channel_id: NetworkChannelID) -> Self {
+        Self {
+            channel_id,
+            history: 32,
+            max_snapshot_size: 1024 * 1024,
+            snapshots: VecDeque::new(),
+            offset: None,
+        }
+    }
+
+    /// Sets the number of snapshots kept for interpolation and as baselines, at least 1.
+    ///
+    /// Should not be larger than the history of the host.
+    pub fn history(&mut self, history: usize) -> &mut Self {
+        self.history = history.max(1);
+        self
+    }
+
+    /// Sets the maximum size of a snapshot, larger snapshots are dropped.
+    pub fn max_snapshot_size(&mut self, max_snapshot_size: usize) -> &mut Self {
+        self.max_snapshot_size = max_snapshot_size;
+        self
+    }
+
+    /// The latest snapshot
+    pub fn latest(&self) -> Option<(SnapshotSequence, &[u8])> {
+        self.snapshots
+            .back()
+            .map(|(sequence, _, data)| (*sequence, data.as_slice()))
+    }
+
+    /// Returns an `Iterator` over the snapshots kept, from the oldest,
+    /// with the host time at which they were taken.
+    pub fn iter_snapshots(&self) -> impl '_ + Iterator<Item = (SnapshotSequence, Duration, &[u8])> {
+        self.snapshots
+            .iter()
+            .map(|(sequence, time, data)| (*sequence, *time, data.as_slice()))
+    }
+
+    /// The estimated host clock, once a snapshot was received
+    pub fn host_time(&self) -> Option<Duration> {
+        self.offset.map(|(arrival, time)| time + arrival.elapsed())
+    }
+
+    /// The snapshots surrounding the host time minus a delay, once a snapshot was received.
+    ///
+    /// The delay should cover the time between two snapshots and the jitter of the network.
+    pub fn interpolate(&self, delay: Duration) -> Option<SnapshotInterpolation<'_>> {
+        let target = self
+            .host_time()?
+            .checked_sub(delay)
+            .unwrap_or_else(|| Duration::from_secs(0));
+
+        interpolate(&self.snapshots, target)
+    }
+
+    /// Reconstructs and acknowledges a snapshot, returning its sequence number.
+    ///
+    /// Returns `None` if the message was received on another channel, was malformed,
+    /// was older than the latest snapshot, was too large, or its baseline is no longer kept.
+    /// Failures to acknowledge are logged.
+    ///
+    /// Forward [`EventHandler::on_network_message`](trait.EventHandler.html#method.on_network_message) here.
+    pub fn on_network_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> Option<SnapshotSequence> {
+        if channel_id != self.channel_id {
+            return None;
+        }
+
+        let sequence = self.receive(Instant::now(), Remote::Peer(peer_id), data)?;
+
+        if let Err(error) = discord.send_message(peer_id, channel_id, ack(sequence)) {
+            log::warn!("failed to acknowledge snapshot {}: {}", sequence, error);
+        }
+
+        Some(sequence)
+    }
+
+    /// Reconstructs and acknowledges a snapshot, returning its sequence number.
+    ///
+    /// Returns `None` if the message was received on another channel, was malformed,
+    /// was older than the latest snapshot, was too large, or its baseline is no longer kept.
+    /// Failures to acknowledge are logged.
+    ///
+    /// Forward [`EventHandler::on_lobby_network_message`](trait.EventHandler.html#method.on_lobby_network_message) here.
+    pub fn on_lobby_network_message<E>(
+        &mut self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) -> Option<SnapshotSequence> {
+        if channel_id != self.channel_id {
+            return None;
+        }
+
+        let sequence = self.receive(Instant::now(), Remote::Member(lobby_id, member_id), data)?;
+
+        if let Err(error) =
+            discord.send_lobby_network_message(lobby_id, member_id, channel_id, &ack(sequence))
+        {
+            log::warn!("failed to acknowledge snapshot {}: {}", sequence, error);
+        }
+
+        Some(sequence)
+    }
+
+    fn receive(&mut self, now: Instant, remote: Remote, data: &[u8]) -> Option<SnapshotSequence> {
+        if data.len() < HEADER_LEN {
+            log::warn!("dropped truncated snapshot from {:?}", remote);
+            return None;
+        }
+
+        let kind = data[0];
+        let sequence = SnapshotSequence::from_le_bytes(data[1..5].try_into().unwrap());
+        let baseline = SnapshotSequence::from_le_bytes(data[5..9].try_into().unwrap());
+        let time = Duration::from_micros(u64::from_le_bytes(data[9..17].try_into().unwrap()));
+        let len = u32::from_le_bytes(data[17..HEADER_LEN].try_into().unwrap()) as usize;
+        let body = &data[HEADER_LEN..];
+
+        match self.latest() {
+            Some((latest, _)) if !is_newer(sequence, latest) => return None,
+            _ => {}
+        }
+
+        if len > self.max_snapshot_size {
+            log::warn!("dropped snapshot of {} bytes from {:?}", len, remote);
+            return None;
+        }
+
+        let snapshot = match kind {
+            FULL if body.len() == len => Some(body.to_vec()),
+
+            DELTA => {
+                let baseline = self
+                    .snapshots
+                    .iter()
+                    .find(|(sequence, _, _)| *sequence == baseline)?;
+
+                patch(&baseline.2, len, body)
+            }
+
+            _ => None,
+        };
+
+        let snapshot = match snapshot {
+            Some(snapshot) => snapshot,
+            None => {
+                log::warn!("dropped malformed snapshot from {:?}", remote);
+                return None;
+            }
+        };
+
+        // A snapshot that arrived faster than the estimate brings the host clock forward
+        let faster = match self.host_time_at(now) {
+            Some(host_time) => time > host_time,
+            None => true,
+        };
+
+        if faster {
+            self.offset = Some((now, time));
+        }
+
+        self.snapshots.push_back((sequence, time, snapshot));
+
+        while self.snapshots.len() > self.history {
+            let _ = self.snapshots.pop_front();
+        }
+
+        Some(sequence)
+    }
+
+    fn host_time_at(&self, now: Instant) -> Option<Duration> {
+        self.offset.map(|(arrival, time)| time + (now - arrival))
+    }
+}
+
+fn ack(sequence: SnapshotSequence) -> Vec<u8> {
+    let mut data = vec![ACK];
+    data.extend_from_slice(&sequence.to_le_bytes());
+    data
+}
+
+fn interpolate(
+    snapshots: &VecDeque<(SnapshotSequence, Duration, Vec<u8>)>,
+    target: Duration,
+) -> Option<SnapshotInterpolation<'_>> {
+    let first = snapshots.front()?;
+
+    // Snapshots are ordered by sequence number, and so by host time
+    let from = snapshots
+        .iter()
+        .rev()
+        .find(|(_, time, _)| *time <= target)
+        .unwrap_or(first);
+
+    let to = snapshots
+        .iter()
+        .find(|(_, time, _)| *time > target && *time > from.1)
+        .filter(|_| from.1 <= target)
+        .unwrap_or(from);
+
+    let alpha = if to.1 > from.1 {
+        (target - from.1).as_secs_f32() / (to.1 - from.1).as_secs_f32()
+    } else {
+        0.0
+    };
+
+    Some(SnapshotInterpolation {
+        from: (from.0, &from.2),
+        to: (to.0, &to.2),
+        alpha,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate() {
+        let mut client = SnapshotClient::new(0);
+        let now = Instant::now();
+        let remote = Remote::Peer(1);
+
+        let snapshot = |sequence: u32, baseline: u32, kind, time: u64, body: &[u8], len: u32| {
+            let mut data = vec![kind];
+            data.extend_from_slice(&sequence.to_le_bytes());
+            data.extend_from_slice(&baseline.to_le_bytes());
+            data.extend_from_slice(&time.to_le_bytes());
+            data.extend_from_slice(&len.to_le_bytes());
+            data.extend_from_slice(body);
+            data
+        };
+
+        assert_eq!(
+            client.receive(now, remote, &snapshot(0, 0, FULL, 1000, b"ab", 2)),
+            Some(0)
+        );
+        assert_eq!(
+            client.receive(now, remote, &snapshot(2, 0, DELTA, 3000, &[1, 1, b'c'], 2)),
+            Some(2)
+        );
+        assert_eq!(
+            client.receive(now, remote, &snapshot(1, 0, FULL, 2000, b"xx", 2)),
+            None
+        );
+        assert_eq!(
+            client.receive(now, remote, &snapshot(3, 9, DELTA, 4000, &[], 2)),
+            None
+        );
+        assert_eq!(client.latest(), Some((2, &b"ac"[..])));
+        assert_eq!(client.host_time_at(now), Some(Duration::from_micros(3000)));
+
+        let interpolation = interpolate(&client.snapshots, Duration::from_micros(1500)).unwrap();
+        assert_eq!(
+            (interpolation.from(), interpolation.to()),
+            (&b"ab"[..], &b"ac"[..])
+        );
+        assert!((interpolation.alpha() - 0.25).abs() < 1e-6);
+
+        let interpolation = interpolate(&client.snapshots, Duration::from_micros(500)).unwrap();
+        assert_eq!(
+            (interpolation.from_sequence(), interpolation.to_sequence()),
+            (0, 0)
+        );
+
+        let interpolation = interpolate(&client.snapshots, Duration::from_micros(5000)).unwrap();
+        assert_eq!(
+            (interpolation.from_sequence(), interpolation.to_sequence()),
+            (2, 2)
+        );
+    }
+}
@@ -0,0 +1,382 @@
+use crate::{
+    network_streams::Remote, Discord, LobbyID, NetworkChannelID, NetworkPeerID, Result,
+    SnapshotSequence, UserID,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    convert::{TryFrom, TryInto},
+    time::{Duration, Instant},
+};
+
+pub(crate) const FULL: u8 = 0;
+pub(crate) const DELTA: u8 = 1;
+pub(crate) const ACK: u8 = 2;
+
+// Kind, sequence, baseline, host time in microseconds and snapshot size
+pub(crate) const HEADER_LEN: usize = 21;
+pub(crate) const ACK_LEN: usize = 5;
+
+/// Host-authoritative snapshot replication
+///
+/// The host takes snapshots of the world with [`snapshot`](#method.snapshot) and sends the latest
+/// to every client, usually over an unreliable channel. Each snapshot is delta-encoded against the
+/// last snapshot acknowledged by the client, or sent whole if that snapshot is no longer kept.
+/// Clients reconstruct and acknowledge snapshots with a [`SnapshotClient`](struct.SnapshotClient.html).
+///
+/// Snapshots are opaque bytes, the delta encoding stores the bytes that changed and works best when
+/// the layout of the world is stable between snapshots.
+///
+/// ```rust
+/// # use discord_game_sdk::*;
+/// struct MyEventHandler {
+///     host: SnapshotHost,
+/// }
+///
+/// impl EventHandler for MyEventHandler {
+///     fn on_network_message(
+///         &mut self,
+///         discord: &Discord<'_, Self>,
+///         peer_id: NetworkPeerID,
+///         channel_id: NetworkChannelID,
+///         data: &[u8],
+///     ) {
+///         self.host.on_network_message(peer_id, channel_id, data);
+///     }
+///
+///     // ...
+/// }
+///
+/// # fn example(discord: Discord<'_, ()>, host: &mut SnapshotHost, peers: &[NetworkPeerID], world: Vec<u8>) -> Result<()> {
+/// // Every network tick
+/// host.snapshot(world);
+///
+/// for peer_id in peers {
+///     host.send_message(&discord, *peer_id)?;
+/// }
+/// # Ok(()) }
+/// ```
+#[derive(Clone, Debug)]
+pub struct SnapshotHost {
+    channel_id: NetworkChannelID,
+    history: usize,
+    epoch: Instant,
+    next_sequence: SnapshotSequence,
+    snapshots: VecDeque<(SnapshotSequence, Duration, Vec<u8>)>,
+    acked: HashMap<Remote, SnapshotSequence>,
+}
+
+impl SnapshotHost {
+    /// Creates a host sending snapshots over the given channel ID,
+    /// and keeping the last 32 snapshots as baselines.
+    pub fn new(channel_id: NetworkChannelID) -> Self {
+        Self {
+            channel_id,
+            history: 32,
+            epoch: Instant::now(),
+            next_sequence: 0,
+            snapshots: VecDeque::new(),
+            acked: HashMap::new(),
+        }
+    }
+
+    /// Sets the number of snapshots kept as baselines, at least 1.
+    ///
+    /// Clients acknowledging slower than this are sent whole snapshots.
+    pub fn history(&mut self, history: usize) -> &mut Self {
+        self.history = history.max(1);
+        self
+    }
+
+    /// The latest snapshot
+    pub fn latest(&self) -> Option<(SnapshotSequence, &[u8])> {
+        self.snapshots
+            .back()
+            .map(|(sequence, _, data)| (*sequence, data.as_slice()))
+    }
+
+    /// Takes a snapshot of the world, to be sent to clients.
+    pub fn snapshot(&mut self, data: Vec<u8>) -> SnapshotSequence {
+        let sequence = self.next_sequence;
+        self.next_sequence = self.next_sequence.wrapping_add(1);
+
+        self.snapshots
+            .push_back((sequence, self.epoch.elapsed(), data));
+
+        while self.snapshots.len() > self.history {
+            let _ = self.snapshots.pop_front();
+        }
+
+        sequence
+    }
+
+    /// Sends the latest snapshot to a given peer ID.
+    ///
+    /// Does nothing if no snapshot was taken yet.
+    pub fn send_message<E>(&self, discord: &Discord<'_, E>, peer_id: NetworkPeerID) -> Result<()> {
+        match self.encode(Remote::Peer(peer_id)) {
+            Some(data) => discord.send_message(peer_id, self.channel_id, data),
+            None => Ok(()),
+        }
+    }
+
+    /// Sends the latest snapshot to a lobby member.
+    ///
+    /// Does nothing if no snapshot was taken yet.
+    pub fn send_lobby_network_message<E>(
+        &self,
+        discord: &Discord<'_, E>,
+        lobby_id: LobbyID,
+        user_id: UserID,
+    ) -> Result<()> {
+        match self.encode(Remote::Member(lobby_id, user_id)) {
+            Some(data) => {
+                discord.send_lobby_network_message(lobby_id, user_id, self.channel_id, &data)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Records the acknowledgements of a client.
+    ///
+    /// Messages received on other channels are ignored, and malformed messages are logged.
+    ///
+    /// Forward [`EventHandler::on_network_message`](trait.EventHandler.html#method.on_network_message) here.
+    pub fn on_network_message(
+        &mut self,
+        peer_id: NetworkPeerID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) {
+        if channel_id == self.channel_id {
+            self.receive(Remote::Peer(peer_id), data);
+        }
+    }
+
+    /// Records the acknowledgements of a client.
+    ///
+    /// Messages received on other channels are ignored, and malformed messages are logged.
+    ///
+    /// Forward [`EventHandler::on_lobby_network_message`](trait.EventHandler.html#method.on_lobby_network_message) here.
+    pub fn on_lobby_network_message(
+        &mut self,
+        lobby_id: LobbyID,
+        member_id: UserID,
+        channel_id: NetworkChannelID,
+        data: &[u8],
+    ) {
+        if channel_id == self.channel_id {
+            self.receive(Remote::Member(lobby_id, member_id), data);
+        }
+    }
+
+    /// Forgets the acknowledgements of a given peer ID, it is sent a whole snapshot next.
+    pub fn forget_peer(&mut self, peer_id: NetworkPeerID) {
+        let _ = self.acked.remove(&Remote::Peer(peer_id));
+    }
+
+    /// Forgets the acknowledgements of a lobby member, they are sent a whole snapshot next.
+    pub fn forget_member(&mut self, lobby_id: LobbyID, member_id: UserID) {
+        let _ = self.acked.remove(&Remote::Member(lobby_id, member_id));
+    }
+
+    fn receive(&mut self, remote: Remote, data: &[u8]) {
+        if data.len() != ACK_LEN || data[0] != ACK {
+            log::warn!(
+                "dropped malformed snapshot acknowledgement from {:?}",
+                remote
+            );
+            return;
+        }
+
+        let sequence = SnapshotSequence::from_le_bytes(data[1..ACK_LEN].try_into().unwrap());
+
+        // Acknowledgements may arrive out of order, the latest one makes the smallest deltas
+        let newer = match self.acked.get(&remote) {
+            Some(acked) => is_newer(sequence, *acked),
+            None => true,
+        };
+
+        if newer {
+            let _ = self.acked.insert(remote, sequence);
+        }
+    }
+
+    fn encode(&self, remote: Remote) -> Option<Vec<u8>> {
+        let (sequence, time, data) = self.snapshots.back()?;
+
+        let baseline = self.acked.get(&remote).and_then(|acked| {
+            self.snapshots
+                .iter()
+                .find(|(sequence, _, _)| sequence == acked)
+        });
+
+        let time = u64::try_from(time.as_micros()).unwrap_or(u64::MAX);
+        let full = || packet(FULL, *sequence, *sequence, time, data, data);
+
+        Some(match baseline {
+            Some((baseline, _, previous)) => {
+                let delta = packet(
+                    DELTA,
+                    *sequence,
+                    *baseline,
+                    time,
+                    data,
+                    &diff(previous, data),
+                );
+
+                if delta.len() < HEADER_LEN + data.len() {
+                    delta
+                } else {
+                    full()
+                }
+            }
+
+            None => full(),
+        })
+    }
+}
+
+// Whether a sequence number comes after another, allowing them to wrap around
+pub(crate) fn is_newer(sequence: SnapshotSequence, other: SnapshotSequence) -> bool {
+    sequence != other && sequence.wrapping_sub(other) < SnapshotSequence::MAX / 2
+}
+
+fn packet(
+    kind: u8,
+    sequence: SnapshotSequence,
+    baseline: SnapshotSequence,
+    time: u64,
+    snapshot: &[u8],
+    body: &[u8],
+) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(HEADER_LEN + body.len());
+    packet.push(kind);
+    packet.extend_from_slice(&sequence.to_le_bytes());
+    packet.extend_from_slice(&baseline.to_le_bytes());
+    packet.extend_from_slice(&time.to_le_bytes());
+    packet.extend_from_slice(&(snapshot.len() as u32).to_le_bytes());
+    packet.extend_from_slice(body);
+    packet
+}
+
+// Runs of changed bytes, each preceded by the number of unchanged bytes before it and its length.
+// Bytes past the end of the baseline are compared to zero.
+fn diff(baseline: &[u8], snapshot: &[u8]) -> Vec<u8> {
+    let mut delta = Vec::new();
+    let mut unchanged = 0;
+    let mut i = 0;
+
+    while i < snapshot.len() {
+        if baseline.get(i).copied().unwrap_or(0) == snapshot[i] {
+            unchanged += 1;
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+
+        while i < snapshot.len() && baseline.get(i).copied().unwrap_or(0) != snapshot[i] {
+            i += 1;
+        }
+
+        write_varint(&mut delta, unchanged);
+        write_varint(&mut delta, i - start);
+        delta.extend_from_slice(&snapshot[start..i]);
+
+        unchanged = 0;
+    }
+
+    delta
+}
+
+// Reconstructs a snapshot of a given size from its baseline and the runs produced by `diff`
+pub(crate) fn patch(baseline: &[u8], len: usize, mut delta: &[u8]) -> Option<Vec<u8>> {
+    let mut snapshot = baseline.to_vec();
+    snapshot.resize(len, 0);
+
+    let mut i = 0usize;
+
+    while !delta.is_empty() {
+        let unchanged = read_varint(&mut delta)?;
+        let changed = read_varint(&mut delta)?;
+
+        let start = i.checked_add(unchanged)?;
+        i = start.checked_add(changed)?;
+
+        if i > len || changed > delta.len() {
+            return None;
+        }
+
+        snapshot[start..i].copy_from_slice(&delta[..changed]);
+        delta = &delta[changed..];
+    }
+
+    Some(snapshot)
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+
+    buffer.push(value as u8);
+}
+
+fn read_varint(buffer: &mut &[u8]) -> Option<usize> {
+    let mut value = 0usize;
+
+    for shift in (0..64).step_by(7) {
+        let (byte, rest) = buffer.split_first()?;
+        *buffer = rest;
+
+        value |= usize::from(byte & 0x7F).checked_shl(shift)?;
+
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delta() {
+        let baseline = b"position 10 20 health 100".to_vec();
+        let snapshot = b"position 11 20 health 90 armor 5".to_vec();
+
+        let delta = diff(&baseline, &snapshot);
+        assert!(delta.len() < snapshot.len());
+        assert_eq!(
+            patch(&baseline, snapshot.len(), &delta),
+            Some(snapshot.clone())
+        );
+        assert_eq!(
+            patch(&snapshot, 8, &diff(&snapshot, b"position")),
+            Some(b"position".to_vec())
+        );
+        assert_eq!(patch(&baseline, 4, &delta), None);
+
+        let mut host = SnapshotHost::new(0);
+        host.history(2);
+        let remote = Remote::Peer(1);
+
+        host.snapshot(baseline);
+        assert_eq!(host.encode(remote).unwrap()[0], FULL);
+
+        host.receive(remote, &[ACK, 0, 0, 0, 0]);
+        host.snapshot(snapshot);
+        assert_eq!(host.encode(remote).unwrap()[0], DELTA);
+
+        // The baseline is dropped from the history
+        host.snapshot(Vec::new());
+        assert_eq!(host.encode(remote).unwrap()[0], FULL);
+
+        assert!(is_newer(0, u32::MAX));
+        assert!(!is_newer(u32::MAX, 0));
+    }
+}
@@ -0,0 +1,38 @@
+use crate::SnapshotSequence;
+
+/// Pair of snapshots surrounding a point in time, see
+/// [`SnapshotClient::interpolate`](struct.SnapshotClient.html#method.interpolate)
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SnapshotInterpolation<'a> {
+    pub(crate) from: (SnapshotSequence, &'a [u8]),
+    pub(crate) to: (SnapshotSequence, &'a [u8]),
+    pub(crate) alpha: f32,
+}
+
+impl<'a> SnapshotInterpolation<'a> {
+    /// The sequence number of the snapshot before the point in time
+    pub fn from_sequence(&self) -> SnapshotSequence {
+        self.from.0
+    }
+
+    /// The snapshot before the point in time
+    pub fn from(&self) -> &'a [u8] {
+        self.from.1
+    }
+
+    /// The sequence number of the snapshot after the point in time
+    pub fn to_sequence(&self) -> SnapshotSequence {
+        self.to.0
+    }
+
+    /// The snapshot after the point in time, the same as [`from`](#method.from)
+    /// when no later snapshot was received
+    pub fn to(&self) -> &'a [u8] {
+        self.to.1
+    }
+
+    /// How far the point in time is between the two snapshots, from 0 to 1
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+}
@@ -0,0 +1,256 @@
+use crate::{utils::io_error, Discord};
+use std::{
+    cell::RefCell,
+    convert::TryFrom,
+    io::{self, SeekFrom},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// Readable and seekable handle to a file of the game's allocated storage,
+/// opened with [`open_file`](struct.Discord.html#method.open_file)
+///
+/// The file is read in chunks with
+/// [`read_file_async_partial`](struct.Discord.html#method.read_file_async_partial),
+/// so large files are never loaded whole.
+///
+/// The size of the file is read when it is opened.
+///
+/// ## Blocking
+///
+/// Reading a chunk blocks until it arrives, for up to 30 seconds by default, by calling
+/// [`run_callbacks`](struct.Discord.html#method.run_callbacks) every millisecond.
+/// Events are then dispatched to the [`EventHandler`](trait.EventHandler.html) from inside `read`,
+/// so the handler may run before the read returns, and should not expect the file to be read
+/// in between.
+pub struct StorageFile<'a, 'd, E> {
+    discord: &'a mut Discord<'d, E>,
+    filename: String,
+    size: u64,
+    position: u64,
+    chunk_size: u64,
+    chunk: Option<(u64, Vec<u8>)>,
+    timeout: Duration,
+}
+
+impl<'a, 'd, E> StorageFile<'a, 'd, E> {
+    pub(crate) fn new(discord: &'a mut Discord<'d, E>, filename: String, size: u64) -> Self {
+        Self {
+            discord,
+            filename,
+            size,
+            position: 0,
+            chunk_size: 64 * 1024,
+            chunk: None,
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Sets the size of the chunks read at once, 64KiB by default,
+    /// between 1 and 4 294 967 295 bytes, the limit of the SDK.
+    pub fn chunk_size(&mut self, chunk_size: u64) -> &mut Self {
+        self.chunk_size = chunk_size.max(1).min(u64::from(u32::MAX));
+        self
+    }
+
+    /// Sets how long reading a chunk waits for it to arrive, 30 seconds by default.
+    ///
+    /// Reads waiting longer fail with
+    /// [`ErrorKind::TimedOut`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.TimedOut).
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// The size of the file in bytes
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl<E> io::Read for StorageFile<'_, '_, E> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let discord = &mut *self.discord;
+        let filename = self.filename.as_str();
+        let timeout = self.timeout;
+
+        let chunk = load(
+            &mut self.chunk,
+            self.chunk_size,
+            self.size,
+            self.position,
+            |offset, length| read_partial(discord, filename, timeout, offset, length),
+        )?;
+        let len = buf.len().min(chunk.len());
+
+        buf[..len].copy_from_slice(&chunk[..len]);
+        self.position += len as u64;
+
+        Ok(len)
+    }
+}
+
+impl<E> io::Seek for StorageFile<'_, '_, E> {
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        let position = match position {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => offset_by(self.size, offset),
+            SeekFrom::Current(offset) => offset_by(self.position, offset),
+        };
+
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )),
+        }
+    }
+}
+
+impl<E> std::fmt::Debug for StorageFile<'_, '_, E> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("StorageFile")
+            .field("filename", &self.filename.trim_end_matches('\0'))
+            .field("size", &self.size)
+            .field("position", &self.position)
+            .field("chunk_size", &self.chunk_size)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+// Returns the data from `position` to the end of its chunk, reading the chunk if it is not loaded
+fn load(
+    chunk: &mut Option<(u64, Vec<u8>)>,
+    chunk_size: u64,
+    size: u64,
+    position: u64,
+    read_partial: impl FnOnce(u64, u64) -> io::Result<Vec<u8>>,
+) -> io::Result<&[u8]> {
+    let offset = position - position % chunk_size;
+
+    let loaded = match chunk {
+        Some((chunk_offset, _)) => *chunk_offset == offset,
+        None => false,
+    };
+
+    if !loaded {
+        let length = chunk_size.min(size - offset);
+        let data = read_partial(offset, length)?;
+
+        if (data.len() as u64) < length {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file is shorter than when it was opened",
+            ));
+        }
+
+        *chunk = Some((offset, data));
+    }
+
+    let (offset, data) = chunk.as_ref().unwrap();
+    let start = usize::try_from(position - offset).unwrap_or(usize::MAX);
+
+    Ok(data.get(start..).unwrap_or(&[]))
+}
+
+// Waits for a chunk by running callbacks, sleeping in between
+fn read_partial<E>(
+    discord: &mut Discord<'_, E>,
+    filename: &str,
+    timeout: Duration,
+    offset: u64,
+    length: u64,
+) -> io::Result<Vec<u8>> {
+    let slot = Rc::new(RefCell::new(None));
+    let result = slot.clone();
+
+    discord.read_file_async_partial(
+        filename,
+        offset,
+        length,
+        move |_, data: crate::Result<&[u8]>| {
+            *result.borrow_mut() = Some(data.map(<[u8]>::to_vec));
+        },
+    );
+
+    let start = Instant::now();
+
+    loop {
+        if let Some(data) = slot.borrow_mut().take() {
+            return data.map_err(io_error);
+        }
+
+        if start.elapsed() >= timeout {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "timed out waiting for a chunk of the file",
+            ));
+        }
+
+        discord.run_callbacks().map_err(io_error)?;
+
+        if slot.borrow().is_none() {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
+
+fn offset_by(base: u64, offset: i64) -> Option<u64> {
+    if offset >= 0 {
+        base.checked_add(offset as u64)
+    } else {
+        base.checked_sub(offset.wrapping_neg() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_offset_by() {
+        assert_eq!(offset_by(10, -10), Some(0));
+        assert_eq!(offset_by(10, -11), None);
+        assert_eq!(offset_by(10, 5), Some(15));
+        assert_eq!(offset_by(u64::MAX, 1), None);
+        assert_eq!(offset_by(u64::MAX, i64::MIN), Some(u64::MAX / 2));
+    }
+
+    #[test]
+    fn test_load() {
+        let file = b"0123456789";
+        let mut chunk = None;
+        let mut reads = Vec::new();
+
+        let mut read = |position, chunk: &mut Option<(u64, Vec<u8>)>| {
+            load(chunk, 4, 10, position, |offset, length| {
+                reads.push((offset, length));
+                Ok(file[offset as usize..][..length as usize].to_vec())
+            })
+            .unwrap()
+            .to_vec()
+        };
+
+        assert_eq!(read(1, &mut chunk), b"123");
+        assert_eq!(read(3, &mut chunk), b"3");
+        assert_eq!(read(4, &mut chunk), b"4567");
+        assert_eq!(read(8, &mut chunk), b"89");
+        assert_eq!(read(9, &mut chunk), b"9");
+        assert_eq!(read(0, &mut chunk), b"0123");
+        assert_eq!(reads, vec![(0, 4), (4, 4), (8, 2), (0, 4)]);
+
+        // The file shrank since it was opened
+        let error = load(&mut chunk, 4, 10, 4, |_, _| Ok(b"45".to_vec())).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
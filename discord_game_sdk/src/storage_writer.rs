@@ -0,0 +1,73 @@
+use crate::{Discord, Result};
+use std::io;
+
+/// Buffered writer to a file of the game's allocated storage,
+/// created with [`create_file`](struct.Discord.html#method.create_file)
+///
+/// Storage can only be written whole, so everything written is buffered in memory and
+/// the file is replaced with [`write_file`](struct.Discord.html#method.write_file)
+/// when the writer is closed with [`close`](#method.close).
+///
+/// Dropping the writer without closing it discards the buffered data, the file is left as is.
+pub struct StorageWriter<'a, 'd, E> {
+    discord: &'a Discord<'d, E>,
+    filename: String,
+    buffer: Vec<u8>,
+    closed: bool,
+}
+
+impl<'a, 'd, E> StorageWriter<'a, 'd, E> {
+    pub(crate) fn new(discord: &'a Discord<'d, E>, filename: String) -> Self {
+        Self {
+            discord,
+            filename,
+            buffer: Vec::new(),
+            closed: false,
+        }
+    }
+
+    /// The data written so far
+    pub fn buffer(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    /// Writes the buffered data to the file, replacing its contents.
+    pub fn close(mut self) -> Result<()> {
+        self.closed = true;
+        self.discord
+            .write_file(self.filename.as_str(), &self.buffer)
+    }
+}
+
+impl<E> io::Write for StorageWriter<'_, '_, E> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    /// Does nothing, the file is only written on close.
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<E> Drop for StorageWriter<'_, '_, E> {
+    fn drop(&mut self) {
+        if !self.closed && !self.buffer.is_empty() {
+            log::warn!(
+                "discarded {} bytes written to {} without closing",
+                self.buffer.len(),
+                self.filename.trim_end_matches('\0')
+            );
+        }
+    }
+}
+
+impl<E> std::fmt::Debug for StorageWriter<'_, '_, E> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("StorageWriter")
+            .field("filename", &self.filename.trim_end_matches('\0'))
+            .field("len", &self.buffer.len())
+            .finish()
+    }
+}
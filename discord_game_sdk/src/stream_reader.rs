@@ -0,0 +1,65 @@
+use crate::{network_streams::Remote, stream_writer::send, Discord, NetworkStreams, StreamID};
+use std::io;
+
+/// Reading end of a stream, see [`NetworkStreams`](struct.NetworkStreams.html)
+///
+/// Reads fail with
+/// [`ErrorKind::WouldBlock`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock)
+/// while no data is buffered, and return `Ok(0)` once the sender finished the stream.
+/// The stream is then discarded, and later reads fail with
+/// [`ErrorKind::NotFound`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.NotFound).
+///
+/// Reading grants the sender more credit once half the window was read. If it cannot be sent,
+/// the failure is logged and it is sent again on the next read.
+pub struct StreamReader<'a, 'd, E> {
+    streams: &'a mut NetworkStreams,
+    discord: &'a Discord<'d, E>,
+    remote: Remote,
+    stream_id: StreamID,
+}
+
+impl<'a, 'd, E> StreamReader<'a, 'd, E> {
+    pub(crate) fn new(
+        streams: &'a mut NetworkStreams,
+        discord: &'a Discord<'d, E>,
+        remote: Remote,
+        stream_id: StreamID,
+    ) -> Self {
+        Self {
+            streams,
+            discord,
+            remote,
+            stream_id,
+        }
+    }
+
+    /// The ID of the stream
+    pub fn stream_id(&self) -> StreamID {
+        self.stream_id
+    }
+}
+
+impl<E> io::Read for StreamReader<'_, '_, E> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.streams.read_buffered(self.remote, self.stream_id, buf);
+
+        // Credit that could not be sent is sent again on the next read, the data read is kept
+        if let Some(packet) = self.streams.credit_packet(self.remote, self.stream_id) {
+            match send(self.discord, self.streams, self.remote, &packet) {
+                Ok(()) => self.streams.grant_credit(self.remote, self.stream_id),
+                Err(error) => log::warn!("failed to grant stream credit: {}", error),
+            }
+        }
+
+        read
+    }
+}
+
+impl<E> std::fmt::Debug for StreamReader<'_, '_, E> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("StreamReader")
+            .field("remote", &self.remote)
+            .field("stream_id", &self.stream_id)
+            .finish()
+    }
+}
@@ -0,0 +1,107 @@
+use crate::{
+    network_streams::{Remote, HEADER_LEN},
+    utils::io_error,
+    Discord, NetworkStreams, StreamID,
+};
+use std::io;
+
+/// Writing end of a stream, see [`NetworkStreams`](struct.NetworkStreams.html)
+///
+/// Writes fail with
+/// [`ErrorKind::WouldBlock`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.WouldBlock)
+/// while the receiver has not granted enough credit, and with
+/// [`ErrorKind::NotFound`](https://doc.rust-lang.org/std/io/enum.ErrorKind.html#variant.NotFound)
+/// once the stream is finished. If sending fails after part of the data went out, the write
+/// returns the number of bytes sent and the rest can be written again.
+pub struct StreamWriter<'a, 'd, E> {
+    streams: &'a mut NetworkStreams,
+    discord: &'a Discord<'d, E>,
+    remote: Remote,
+    stream_id: StreamID,
+}
+
+impl<'a, 'd, E> StreamWriter<'a, 'd, E> {
+    pub(crate) fn new(
+        streams: &'a mut NetworkStreams,
+        discord: &'a Discord<'d, E>,
+        remote: Remote,
+        stream_id: StreamID,
+    ) -> Self {
+        Self {
+            streams,
+            discord,
+            remote,
+            stream_id,
+        }
+    }
+
+    /// The ID of the stream
+    pub fn stream_id(&self) -> StreamID {
+        self.stream_id
+    }
+
+    /// Marks the end of the stream, the receiver reads it as end of file once
+    /// every written byte was read.
+    pub fn finish(self) -> io::Result<()> {
+        match self.streams.end_packet(self.remote, self.stream_id) {
+            Some(packet) => send(self.discord, self.streams, self.remote, &packet),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "stream is not open",
+            )),
+        }
+    }
+}
+
+impl<E> io::Write for StreamWriter<'_, '_, E> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let (len, packets) = self
+            .streams
+            .write_packets(self.remote, self.stream_id, buf)?;
+
+        let mut sent = 0;
+
+        for packet in packets {
+            if let Err(error) = send(self.discord, self.streams, self.remote, &packet) {
+                // The caller retries what was not sent, its credit is given back
+                self.streams.refund(self.remote, self.stream_id, len - sent);
+
+                return if sent > 0 { Ok(sent) } else { Err(error) };
+            }
+
+            sent += packet.len() - HEADER_LEN;
+        }
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.discord.flush_network().map_err(io_error)
+    }
+}
+
+impl<E> std::fmt::Debug for StreamWriter<'_, '_, E> {
+    fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt.debug_struct("StreamWriter")
+            .field("remote", &self.remote)
+            .field("stream_id", &self.stream_id)
+            .finish()
+    }
+}
+
+pub(crate) fn send<E>(
+    discord: &Discord<'_, E>,
+    streams: &NetworkStreams,
+    remote: Remote,
+    packet: &[u8],
+) -> io::Result<()> {
+    let sent = match remote {
+        Remote::Peer(peer_id) => discord.send_message(peer_id, streams.channel_id, packet),
+
+        Remote::Member(lobby_id, user_id) => {
+            discord.send_lobby_network_message(lobby_id, user_id, streams.channel_id, packet)
+        }
+    };
+
+    sent.map_err(io_error)
+}
@@ -64,6 +64,12 @@ pub(crate) unsafe fn charptr_to_str<'a>(ptr: *const u8) -> &'a str {
     }
 }
 
+// `io::Error::other` is too recent for the supported Rust versions
+#[allow(unknown_lints, clippy::io_other_error)]
+pub(crate) fn io_error(error: crate::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, error)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
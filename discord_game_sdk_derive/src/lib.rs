@@ -0,0 +1,137 @@
+//! This crate provides derive macros for [`discord_game_sdk`].
+//!
+//! *This crate is not official, it is not supported by the Discord Game SDK Developers.*
+//!
+//! It should not be used directly, enable the `derive` feature of [`discord_game_sdk`] instead.
+//!
+//! [`discord_game_sdk`]: https://docs.rs/discord_game_sdk
+
+#![doc(html_root_url = "https://docs.rs/discord_game_sdk_derive/1.0.1")]
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Lit, Meta, NestedMeta};
+
+/// Derives `discord_game_sdk::Metadata` for structs with named fields.
+///
+/// Field attributes:
+///
+/// - `#[metadata(rename = "key")]`: store the field under `key` instead of its name
+/// - `#[metadata(default)]`: use `Default::default()` when the key is missing
+#[proc_macro_derive(Metadata, attributes(metadata))]
+pub fn derive_metadata(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand_metadata(&input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+fn expand_metadata(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    input,
+                    "Metadata can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "Metadata can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut encode = Vec::new();
+    let mut decode = Vec::new();
+
+    for field in fields {
+        let ident = field.ident.as_ref().unwrap();
+        let mut key = ident.to_string();
+        let mut default = false;
+
+        for attr in field.attrs.iter().filter(|a| a.path.is_ident("metadata")) {
+            let list = match attr.parse_meta()? {
+                Meta::List(list) => list,
+                meta => return Err(syn::Error::new_spanned(meta, "expected #[metadata(...)]")),
+            };
+
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("rename") => {
+                        match nv.lit {
+                            Lit::Str(lit) => key = lit.value(),
+                            lit => return Err(syn::Error::new_spanned(lit, "expected a string")),
+                        }
+                    }
+
+                    NestedMeta::Meta(Meta::Path(path)) if path.is_ident("default") => {
+                        default = true;
+                    }
+
+                    nested => {
+                        return Err(syn::Error::new_spanned(
+                            nested,
+                            "expected `rename = \"...\"` or `default`",
+                        ))
+                    }
+                }
+            }
+        }
+
+        encode.push(quote! {
+            let _ = metadata.insert(
+                ::std::string::String::from(#key),
+                ::discord_game_sdk::MetadataValue::to_metadata_value(&self.#ident),
+            );
+        });
+
+        decode.push(if default {
+            quote! {
+                #ident: match ::discord_game_sdk::MetadataValue::from_metadata(metadata, #key) {
+                    ::std::result::Result::Err(::discord_game_sdk::MetadataError::Missing(_)) => {
+                        ::std::default::Default::default()
+                    }
+                    result => result?,
+                },
+            }
+        } else {
+            quote! {
+                #ident: ::discord_game_sdk::MetadataValue::from_metadata(metadata, #key)?,
+            }
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::discord_game_sdk::Metadata for #name #ty_generics #where_clause {
+            fn to_metadata(
+                &self,
+            ) -> ::std::collections::HashMap<::std::string::String, ::std::string::String> {
+                let mut metadata = ::std::collections::HashMap::new();
+                #(#encode)*
+                metadata
+            }
+
+            fn from_metadata(
+                metadata: &::std::collections::HashMap<
+                    ::std::string::String,
+                    ::std::string::String,
+                >,
+            ) -> ::std::result::Result<Self, ::discord_game_sdk::MetadataError> {
+                ::std::result::Result::Ok(Self {
+                    #(#decode)*
+                })
+            }
+        }
+    })
+}